@@ -0,0 +1,38 @@
+//! Poll the vitals of several wall connectors concurrently.
+//!
+//! ```bash
+//! cargo run --features async --example async_poll -- 192.168.1.221 192.168.1.222
+//! ```
+
+use tesla_wallcon_monitor::AsyncWallConnectorClient;
+
+#[tokio::main]
+async fn main() {
+    let addrs: Vec<String> = std::env::args().skip(1).collect();
+    if addrs.is_empty() {
+        eprintln!("Usage: async_poll <ADDR>...");
+        std::process::exit(1);
+    }
+
+    let http = reqwest::Client::new();
+    let mut tasks = tokio::task::JoinSet::new();
+    for addr in addrs {
+        let client = AsyncWallConnectorClient::new(&addr)
+            .expect("Failed to create client")
+            .with_client(http.clone());
+        tasks.spawn(async move { (addr, client.vitals().await) });
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        match joined.expect("Task panicked") {
+            (addr, Ok(vitals)) => println!(
+                "{}: connected={} current={:.1} A session={:.3} kWh",
+                addr,
+                vitals.vehicle_connected,
+                vitals.vehicle_current_a,
+                vitals.session_energy_wh / 1000.0
+            ),
+            (addr, Err(e)) => println!("{}: error: {}", addr, e),
+        }
+    }
+}
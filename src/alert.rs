@@ -0,0 +1,252 @@
+//! Decoding of the `current_alerts` entries in [`crate::Vitals`].
+//!
+//! The shape of the entries is undocumented and none of the logs in `data/`
+//! has a non-empty `current_alerts`, so the decoding is a guess: a bare code
+//! (`"PCS_a052"` or `52`) or an object carrying the code with an optional
+//! description and severity under one of the keys below. Every alert keeps
+//! the entry exactly as sent in `raw`, and anything not matching these shapes
+//! is kept as raw JSON only, so no information is lost when the guess is
+//! wrong.
+
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::models::{Lifetime, Vitals};
+
+// Guessed, not seen in a real payload yet
+const CODE_KEYS: &[&str] = &["code", "alert_id", "id", "name"];
+const DESCRIPTION_KEYS: &[&str] = &["description", "desc", "message", "text"];
+const SEVERITY_KEYS: &[&str] = &["severity", "level", "type"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Unknown,
+    Info,
+    Warning,
+    Error,
+}
+
+impl AlertSeverity {
+    fn parse(value: &str) -> Self {
+        match value.to_ascii_lowercase().as_str() {
+            "info" | "information" | "notice" => Self::Info,
+            "warn" | "warning" => Self::Warning,
+            "error" | "fault" | "critical" | "fatal" => Self::Error,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for AlertSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::Unknown => "unknown",
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        };
+        f.write_str(text)
+    }
+}
+
+/// One entry of `current_alerts`.
+///
+/// Serializes as `{"code", "description", "severity", "raw"}` where `raw` is
+/// the entry exactly as the firmware sent it; deserializing that form again
+/// decodes `raw`, so alerts round trip through our own JSON output.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "Value")]
+pub struct Alert {
+    pub code: Option<String>,
+    pub description: Option<String>,
+    pub severity: AlertSeverity,
+    pub raw: Value,
+}
+
+impl Alert {
+    /// Decode a raw `current_alerts` entry.
+    pub fn from_raw(raw: Value) -> Self {
+        let mut alert = Alert {
+            code: None,
+            description: None,
+            severity: AlertSeverity::Unknown,
+            raw,
+        };
+        match &alert.raw {
+            Value::String(code) => alert.code = Some(code.clone()),
+            Value::Number(code) => alert.code = Some(code.to_string()),
+            Value::Object(map) => {
+                alert.code = first_key(map, CODE_KEYS);
+                alert.description = first_key(map, DESCRIPTION_KEYS);
+                alert.severity = first_key(map, SEVERITY_KEYS)
+                    .map(|s| AlertSeverity::parse(&s))
+                    .unwrap_or(AlertSeverity::Unknown);
+            }
+            _ => {}
+        }
+        alert
+    }
+
+    /// True if the entry didn't match any known shape.
+    pub fn is_unknown(&self) -> bool {
+        self.code.is_none()
+    }
+
+    /// Key identifying the alert across polls: its code, or the raw JSON.
+    pub fn key(&self) -> String {
+        self.code.clone().unwrap_or_else(|| self.raw.to_string())
+    }
+}
+
+fn first_key(map: &serde_json::Map<String, Value>, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| match map.get(*key)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+impl From<Value> for Alert {
+    fn from(value: Value) -> Self {
+        // Our own serialized form, see the type docs
+        if let Value::Object(map) = &value
+            && map.contains_key("severity")
+            && let Some(raw) = map.get("raw")
+        {
+            return Alert::from_raw(raw.clone());
+        }
+        Alert::from_raw(value)
+    }
+}
+
+impl Serialize for Alert {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Decoded<'a> {
+            code: &'a Option<String>,
+            description: &'a Option<String>,
+            severity: AlertSeverity,
+            raw: &'a Value,
+        }
+        Decoded {
+            code: &self.code,
+            description: &self.description,
+            severity: self.severity,
+            raw: &self.raw,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(
+                f,
+                "{} [{}] {}",
+                code,
+                self.severity,
+                self.description.as_deref().unwrap_or("no description")
+            ),
+            None => write!(f, "unrecognized alert {}", self.raw),
+        }
+    }
+}
+
+/// An alert seen by an [`AlertTracker`].
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackedAlert {
+    pub alert: Alert,
+    /// Number of times the alert was raised, i.e. appeared in
+    /// `current_alerts` after being absent. Alerts already active on the
+    /// first update start at 0 as they predate the baseline count.
+    pub occurrences: u32,
+    /// Whether the alert was present in the latest vitals.
+    pub active: bool,
+}
+
+/// Follows `current_alerts` over successive vitals to report which alerts
+/// contributed to the increase of [`Lifetime::alert_count`].
+///
+/// Alerts that are raised and cleared between two polls only show up in
+/// the lifetime counter, see [`AlertTracker::unobserved`].
+#[derive(Debug, Default)]
+pub struct AlertTracker {
+    primed: bool,
+    baseline_count: Option<u32>,
+    latest_count: Option<u32>,
+    alerts: Vec<TrackedAlert>,
+    index: HashMap<String, usize>,
+}
+
+impl AlertTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the alerts of `vitals`, returning the ones newly raised.
+    pub fn update_vitals(&mut self, vitals: &Vitals) -> Vec<Alert> {
+        let first = !std::mem::replace(&mut self.primed, true);
+        let mut raised = Vec::new();
+        let mut present = Vec::new();
+        for alert in &vitals.current_alerts {
+            let key = alert.key();
+            match self.index.get(&key) {
+                Some(&i) => {
+                    let tracked = &mut self.alerts[i];
+                    if !tracked.active {
+                        tracked.occurrences += 1;
+                        raised.push(alert.clone());
+                    }
+                    tracked.alert = alert.clone();
+                    present.push(i);
+                }
+                None => {
+                    self.index.insert(key, self.alerts.len());
+                    present.push(self.alerts.len());
+                    self.alerts.push(TrackedAlert {
+                        alert: alert.clone(),
+                        occurrences: if first { 0 } else { 1 },
+                        active: true,
+                    });
+                    if !first {
+                        raised.push(alert.clone());
+                    }
+                }
+            }
+        }
+        for (i, tracked) in self.alerts.iter_mut().enumerate() {
+            tracked.active = present.contains(&i);
+        }
+        raised
+    }
+
+    /// Record the lifetime `alert_count`, the first call sets the baseline.
+    pub fn update_lifetime(&mut self, lifetime: &Lifetime) {
+        self.baseline_count.get_or_insert(lifetime.alert_count);
+        self.latest_count = Some(lifetime.alert_count);
+    }
+
+    /// Alerts seen so far, in the order they were first raised.
+    pub fn alerts(&self) -> &[TrackedAlert] {
+        &self.alerts
+    }
+
+    /// Increase of the lifetime `alert_count` since the first update.
+    pub fn count_delta(&self) -> u32 {
+        match (self.baseline_count, self.latest_count) {
+            (Some(base), Some(latest)) => latest.saturating_sub(base),
+            _ => 0,
+        }
+    }
+
+    /// Alerts counted by the connector that never showed up in
+    /// `current_alerts` while we were polling.
+    pub fn unobserved(&self) -> u32 {
+        let observed: u32 = self.alerts.iter().map(|t| t.occurrences).sum();
+        self.count_delta().saturating_sub(observed)
+    }
+}
@@ -0,0 +1,179 @@
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+use crate::client::{
+    DEFAULT_CONNECT_TIMEOUT, DEFAULT_PORT, DEFAULT_TIMEOUT, Recorder, endpoint_url,
+};
+use crate::error::{Error, host_port, parse, resolved};
+use crate::logfile::{Payload, log_response};
+use crate::models::{Lifetime, Version, Vitals, WifiStatus};
+use crate::retry::{RetryPolicy, is_transient};
+
+/// Async counterpart of [`crate::WallConnectorClient`], enabled with the
+/// `async` cargo feature.
+///
+/// It doesn't start a runtime of its own, so it can be embedded in an
+/// existing tokio based service and cloned cheaply to poll many connectors
+/// concurrently. Requests are not retried unless a [`RetryPolicy`] is set.
+#[derive(Debug, Clone)]
+pub struct AsyncWallConnectorClient {
+    addr: String,
+    port: u16,
+    timeout: Duration,
+    connect_timeout: Duration,
+    retry: RetryPolicy,
+    recorder: Option<Arc<dyn Recorder>>,
+    client: reqwest::Client,
+}
+
+fn build_client(connect_timeout: Duration) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .connect_timeout(connect_timeout)
+        .build()
+}
+
+impl AsyncWallConnectorClient {
+    /// Create a client for the wall connector at `addr` (name or IP address)
+    /// using [`DEFAULT_PORT`], [`DEFAULT_TIMEOUT`] and
+    /// [`DEFAULT_CONNECT_TIMEOUT`].
+    pub fn new(addr: &str) -> Result<Self, Error> {
+        Ok(Self {
+            addr: addr.to_string(),
+            port: DEFAULT_PORT,
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            retry: RetryPolicy::none(),
+            recorder: None,
+            client: build_client(DEFAULT_CONNECT_TIMEOUT)?,
+        })
+    }
+
+    /// Share an existing `reqwest::Client`, e.g. one connection pool for a
+    /// whole fleet of connectors. Its connect timeout applies instead of
+    /// [`AsyncWallConnectorClient::connect_timeout`].
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Time a request may take in total, connecting included.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Time to wait for the connection to be established, this rebuilds the
+    /// underlying `reqwest` client.
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Result<Self, Error> {
+        self.client = build_client(connect_timeout)?;
+        self.connect_timeout = connect_timeout;
+        Ok(self)
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Hand every successful response to `recorder`. It is called on the
+    /// task making the request, so it should be quick.
+    pub fn with_recorder(mut self, recorder: Arc<dyn Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    pub fn retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Full URL of an `/api/1` endpoint, e.g. `url("vitals")`.
+    pub fn url(&self, endpoint: &str) -> String {
+        endpoint_url(&self.addr, self.port, endpoint)
+    }
+
+    /// Body of `endpoint`, retried according to the retry policy. Every
+    /// response is logged, error pages included.
+    async fn fetch(&self, endpoint: &str) -> reqwest::Result<String> {
+        self.retry
+            .run_async(
+                || async {
+                    let start = Instant::now();
+                    let response = self
+                        .client
+                        .get(self.url(endpoint))
+                        .timeout(self.timeout)
+                        .send()
+                        .await?;
+                    let status = response.status().as_u16();
+                    let error = response.error_for_status_ref().err();
+                    let text = response.text().await?;
+                    log_response(&self.addr, endpoint, status, start.elapsed(), &text);
+                    // Error pages aren't data, don't parse them
+                    match error {
+                        Some(error) => Err(error),
+                        None => Ok(text),
+                    }
+                },
+                is_transient,
+            )
+            .await
+    }
+
+    /// Async counterpart of [`crate::error::resolve`].
+    async fn resolve(&self) -> Result<(), Error> {
+        let lookup = tokio::net::lookup_host(host_port(&self.addr, self.port)).await;
+        resolved(&self.addr, lookup)
+    }
+
+    async fn get<T: DeserializeOwned + Clone + Into<Payload>>(
+        &self,
+        endpoint: &str,
+    ) -> Result<T, Error> {
+        self.resolve().await?;
+        let text = self.fetch(endpoint).await?;
+        let parsed: T = parse(endpoint, &text)?;
+        if let Some(recorder) = &self.recorder {
+            recorder.record(OffsetDateTime::now_utc(), &parsed.clone().into());
+        }
+        Ok(parsed)
+    }
+
+    pub async fn version(&self) -> Result<Version, Error> {
+        self.get("version").await
+    }
+
+    pub async fn wifi_status(&self) -> Result<WifiStatus, Error> {
+        self.get("wifi_status").await
+    }
+
+    pub async fn lifetime(&self) -> Result<Lifetime, Error> {
+        self.get("lifetime").await
+    }
+
+    pub async fn vitals(&self) -> Result<Vitals, Error> {
+        self.get("vitals").await
+    }
+}
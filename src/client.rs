@@ -0,0 +1,177 @@
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+use crate::error::{Error, parse, resolve};
+use crate::logfile::{Payload, log_response};
+use crate::models::{Lifetime, Version, Vitals, WifiStatus};
+use crate::retry::{RetryPolicy, is_transient};
+
+/// Port the wall connector serves its API on.
+pub const DEFAULT_PORT: u16 = 80;
+
+/// Timeout applied to each request unless overridden.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Timeout for establishing a connection unless overridden.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) fn endpoint_url(addr: &str, port: u16, endpoint: &str) -> String {
+    if port == DEFAULT_PORT {
+        format!("http://{}/api/1/{}", addr, endpoint)
+    } else {
+        format!("http://{}:{}/api/1/{}", addr, port, endpoint)
+    }
+}
+
+/// Receives every response a client parsed successfully, e.g. to store it.
+pub trait Recorder: fmt::Debug + Send + Sync {
+    fn record(&self, timestamp: OffsetDateTime, payload: &Payload);
+}
+
+/// Blocking client for the `/api/1` endpoints of a Tesla Wall Connector.
+///
+/// The underlying `reqwest` client is created once and reused, so keeping a
+/// `WallConnectorClient` around in a polling loop reuses connections.
+/// Requests are not retried unless a [`RetryPolicy`] is set.
+#[derive(Debug, Clone)]
+pub struct WallConnectorClient {
+    addr: String,
+    port: u16,
+    timeout: Duration,
+    connect_timeout: Duration,
+    retry: RetryPolicy,
+    recorder: Option<Arc<dyn Recorder>>,
+    client: reqwest::blocking::Client,
+}
+
+fn build_client(connect_timeout: Duration) -> reqwest::Result<reqwest::blocking::Client> {
+    reqwest::blocking::Client::builder()
+        .connect_timeout(connect_timeout)
+        .build()
+}
+
+impl WallConnectorClient {
+    /// Create a client for the wall connector at `addr` (name or IP address)
+    /// using [`DEFAULT_PORT`], [`DEFAULT_TIMEOUT`] and
+    /// [`DEFAULT_CONNECT_TIMEOUT`].
+    pub fn new(addr: &str) -> Result<Self, Error> {
+        Ok(Self {
+            addr: addr.to_string(),
+            port: DEFAULT_PORT,
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            retry: RetryPolicy::none(),
+            recorder: None,
+            client: build_client(DEFAULT_CONNECT_TIMEOUT)?,
+        })
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Time a request may take in total, connecting included.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Time to wait for the connection to be established, this rebuilds the
+    /// underlying `reqwest` client.
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Result<Self, Error> {
+        self.client = build_client(connect_timeout)?;
+        self.connect_timeout = connect_timeout;
+        Ok(self)
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Hand every successful response to `recorder`.
+    pub fn with_recorder(mut self, recorder: Arc<dyn Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    pub fn retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Full URL of an `/api/1` endpoint, e.g. `url("vitals")`.
+    pub fn url(&self, endpoint: &str) -> String {
+        endpoint_url(&self.addr, self.port, endpoint)
+    }
+
+    /// Body of `endpoint`, retried according to the retry policy. Every
+    /// response is logged, error pages included.
+    fn fetch(&self, endpoint: &str) -> reqwest::Result<String> {
+        self.retry.run(
+            || {
+                let start = Instant::now();
+                let response = self
+                    .client
+                    .get(self.url(endpoint))
+                    .timeout(self.timeout)
+                    .send()?;
+                let status = response.status().as_u16();
+                let error = response.error_for_status_ref().err();
+                let text = response.text()?;
+                log_response(&self.addr, endpoint, status, start.elapsed(), &text);
+                // Error pages aren't data, don't parse them
+                match error {
+                    Some(error) => Err(error),
+                    None => Ok(text),
+                }
+            },
+            is_transient,
+        )
+    }
+
+    fn get<T: DeserializeOwned + Clone + Into<Payload>>(&self, endpoint: &str) -> Result<T, Error> {
+        resolve(&self.addr, self.port)?;
+        let text = self.fetch(endpoint)?;
+        let parsed: T = parse(endpoint, &text)?;
+        if let Some(recorder) = &self.recorder {
+            recorder.record(OffsetDateTime::now_utc(), &parsed.clone().into());
+        }
+        Ok(parsed)
+    }
+
+    pub fn version(&self) -> Result<Version, Error> {
+        self.get("version")
+    }
+
+    pub fn wifi_status(&self) -> Result<WifiStatus, Error> {
+        self.get("wifi_status")
+    }
+
+    pub fn lifetime(&self) -> Result<Lifetime, Error> {
+        self.get("lifetime")
+    }
+
+    pub fn vitals(&self) -> Result<Vitals, Error> {
+        self.get("vitals")
+    }
+}
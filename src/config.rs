@@ -0,0 +1,190 @@
+//! Configuration file with named devices and defaults for the CLI.
+//!
+//! Read from `tesla-wallcon-monitor/config.toml` in the XDG config directory,
+//! `$XDG_CONFIG_HOME` or `~/.config` on Linux and macOS alike, unless another
+//! file is given. Every setting is optional, command line flags take
+//! precedence:
+//!
+//! ```toml
+//! command = "vitals"
+//! delay = 10
+//! log = "/var/log/wallcon.log"
+//! log_format = "jsonl"
+//! db = "/var/lib/wallcon/history.db"
+//! units = "imperial"
+//! format = "json"
+//!
+//! [devices]
+//! home = "192.168.1.221"
+//! garage = "192.168.1.222"
+//!
+//! [http]
+//! connect_timeout = 3000
+//! read_timeout = 8000
+//! retries = 3
+//! backoff = 500
+//!
+//! [exporter]
+//! listen = "0.0.0.0:9869"
+//! cache = 5
+//!
+//! [serve]
+//! listen = "0.0.0.0:8088"
+//!
+//! [tariff]
+//! currency = "$"
+//! rate = 0.15
+//!
+//! [daemon]
+//! interval = 30
+//!
+//! [[daemon.sinks]]
+//! type = "file"
+//! path = "/var/lib/tesla-wallcon-monitor/wallcon.log"
+//! ```
+//!
+//! See [`crate::tariff`] for the tariff settings and [`crate::daemon`] for
+//! the daemon and its sinks.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::format::Units;
+use crate::logfile::LogFormat;
+use crate::output::OutputFormat;
+use crate::tariff::Tariff;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Command run when none is given.
+    pub command: Option<String>,
+    pub delay: Option<u64>,
+    pub log: Option<PathBuf>,
+    pub log_format: Option<LogFormat>,
+    pub db: Option<PathBuf>,
+    pub units: Option<Units>,
+    pub format: Option<OutputFormat>,
+    /// Addresses by name, the names can be used in place of an address.
+    #[serde(default)]
+    pub devices: BTreeMap<String, String>,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub exporter: ExporterConfig,
+    #[serde(default)]
+    pub serve: ServeConfig,
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+    /// Electricity tariff for the cost of charging.
+    pub tariff: Option<Tariff>,
+}
+
+/// Timeouts and backoff in milliseconds.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    pub connect_timeout: Option<u64>,
+    pub read_timeout: Option<u64>,
+    pub retries: Option<u32>,
+    pub backoff: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExporterConfig {
+    pub listen: Option<String>,
+    pub cache: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServeConfig {
+    pub listen: Option<String>,
+}
+
+/// What the `daemon` tool polls and where the records go, see
+/// [`crate::daemon`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Names of `[devices]` or addresses, all `[devices]` if empty.
+    #[serde(default)]
+    pub devices: Vec<String>,
+    /// Endpoints polled each interval, `["vitals"]` if empty.
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    /// Seconds between polls, `delay` if not set.
+    pub interval: Option<u64>,
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+}
+
+/// A destination of the daemon's records. `device` limits a sink to the
+/// records of one device.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum SinkConfig {
+    /// Append lines in the format of `--log`.
+    File {
+        path: PathBuf,
+        device: Option<String>,
+    },
+    /// Print one JSON object per line.
+    Stdout { device: Option<String> },
+    /// Publish to an MQTT broker like the mqtt command.
+    Mqtt {
+        broker: String,
+        user: Option<String>,
+        password: Option<String>,
+        topic: Option<String>,
+        discovery_prefix: Option<String>,
+        device: Option<String>,
+    },
+}
+
+impl Config {
+    /// `config.toml` in the `tesla-wallcon-monitor` directory of the user's
+    /// config directory, `$XDG_CONFIG_HOME` or `~/.config` on Unix.
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("tesla-wallcon-monitor").join("config.toml"))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Read the file at [`Config::default_path`], an empty config if there
+    /// is none.
+    pub fn read_default() -> Result<Self, Box<dyn std::error::Error>> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::read(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Address of the device named `name`.
+    pub fn device(&self, name: &str) -> Option<&str> {
+        self.devices.get(name).map(String::as_str)
+    }
+}
+
+/// `$XDG_CONFIG_HOME`, or `~/.config` when it isn't set, on every Unix
+/// including macOS, where [`dirs::config_dir`] would be
+/// `~/Library/Application Support`. The platform's config directory
+/// elsewhere.
+fn config_dir() -> Option<PathBuf> {
+    if !cfg!(unix) {
+        return dirs::config_dir();
+    }
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        // Relative paths are invalid and ignored, as the spec demands
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+}
@@ -0,0 +1,233 @@
+//! Cost of the energy delivered, by session, day and month.
+//!
+//! The energy of a sample is the growth of `session_energy_wh` since the
+//! previous one. It is spread evenly over the time between the two samples
+//! and split at the hours, so each part is billed at the rate of its hour
+//! even when samples are far apart. The first sample of a session in
+//! progress spreads its energy over `session_s`.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use time::{Date, Duration, OffsetDateTime};
+
+use crate::models::Vitals;
+use crate::session::{Session, SessionDetector, SessionEnd, restart};
+use crate::tariff::Tariff;
+
+/// Energy and cost billed under one name of the tariff.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeriodCost {
+    pub name: String,
+    pub energy_wh: f64,
+    pub cost: f64,
+}
+
+/// Energy and cost, split by the names of the tariff.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Cost {
+    pub energy_wh: f64,
+    pub cost: f64,
+    pub periods: Vec<PeriodCost>,
+}
+
+impl Cost {
+    fn new(tariff: &Tariff) -> Self {
+        Cost {
+            energy_wh: 0.0,
+            cost: 0.0,
+            periods: tariff
+                .names()
+                .into_iter()
+                .map(|name| PeriodCost {
+                    name: name.to_string(),
+                    energy_wh: 0.0,
+                    cost: 0.0,
+                })
+                .collect(),
+        }
+    }
+
+    fn add(&mut self, name: &str, energy_wh: f64, cost: f64) {
+        self.energy_wh += energy_wh;
+        self.cost += cost;
+        if let Some(period) = self.periods.iter_mut().find(|p| p.name == name) {
+            period.energy_wh += energy_wh;
+            period.cost += cost;
+        }
+    }
+
+    /// The part billed under `name`.
+    pub fn period(&self, name: &str) -> Option<&PeriodCost> {
+        self.periods.iter().find(|p| p.name == name)
+    }
+}
+
+/// A session with what it cost.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionCost {
+    pub session: Session,
+    pub cost: Cost,
+}
+
+#[derive(Debug)]
+struct Previous {
+    timestamp: OffsetDateTime,
+    session_energy_wh: f64,
+    session_s: u64,
+    uptime_s: u64,
+    vehicle_connected: bool,
+}
+
+/// Follows successive vitals samples and adds up their cost.
+#[derive(Debug)]
+pub struct CostTracker {
+    tariff: Tariff,
+    detector: SessionDetector,
+    previous: Option<Previous>,
+    session: Cost,
+    days: BTreeMap<Date, Cost>,
+    /// Keyed by the first day of the month.
+    months: BTreeMap<Date, Cost>,
+}
+
+impl CostTracker {
+    pub fn new(tariff: Tariff) -> Self {
+        CostTracker {
+            session: Cost::new(&tariff),
+            tariff,
+            detector: SessionDetector::new(),
+            previous: None,
+            days: BTreeMap::new(),
+            months: BTreeMap::new(),
+        }
+    }
+
+    pub fn tariff(&self) -> &Tariff {
+        &self.tariff
+    }
+
+    /// Feed the next sample, returning the session it completed with its
+    /// cost, if any.
+    pub fn push(&mut self, timestamp: OffsetDateTime, vitals: &Vitals) -> Option<SessionCost> {
+        // Energy since the previous sample and when it was used
+        let (energy_wh, since) = match &self.previous {
+            None if vitals.vehicle_connected => (
+                vitals.session_energy_wh,
+                timestamp - Duration::seconds(vitals.session_s as i64),
+            ),
+            None => (0.0, timestamp),
+            Some(previous) => {
+                let restarted = restart(previous.uptime_s, previous.session_s, vitals);
+                let energy_wh = if restarted.is_some() {
+                    vitals.session_energy_wh
+                } else if vitals.vehicle_connected || previous.vehicle_connected {
+                    (vitals.session_energy_wh - previous.session_energy_wh).max(0.0)
+                } else {
+                    0.0
+                };
+                (energy_wh, previous.timestamp)
+            }
+        };
+        self.previous = Some(Previous {
+            timestamp,
+            session_energy_wh: vitals.session_energy_wh,
+            session_s: vitals.session_s,
+            uptime_s: vitals.uptime_s,
+            vehicle_connected: vitals.vehicle_connected,
+        });
+
+        let completed = self.detector.push(timestamp, vitals);
+        // The unplug sample still belongs to the session it ends, the first
+        // sample after a restart or reboot to the next one
+        match completed {
+            Some(session) if session.end == SessionEnd::Unplugged => {
+                self.add(since, timestamp, energy_wh);
+                let cost = std::mem::replace(&mut self.session, Cost::new(&self.tariff));
+                Some(SessionCost { session, cost })
+            }
+            Some(session) => {
+                let cost = std::mem::replace(&mut self.session, Cost::new(&self.tariff));
+                self.add(since, timestamp, energy_wh);
+                Some(SessionCost { session, cost })
+            }
+            None => {
+                self.add(since, timestamp, energy_wh);
+                None
+            }
+        }
+    }
+
+    /// Bill `energy_wh` used from `start` to `end`.
+    fn add(&mut self, start: OffsetDateTime, end: OffsetDateTime, energy_wh: f64) {
+        if energy_wh <= 0.0 {
+            return;
+        }
+        let offset = self.tariff.offset();
+        let (start, end) = (start.to_offset(offset), end.to_offset(offset));
+        let total = (end - start).as_seconds_f64();
+        let mut slice_start = start;
+        loop {
+            // Up to the next full hour
+            let next_hour = slice_start
+                .replace_time(time::Time::from_hms(slice_start.hour(), 0, 0).unwrap())
+                + Duration::HOUR;
+            let slice_end = next_hour.min(end);
+            let share = if total > 0.0 {
+                (slice_end - slice_start).as_seconds_f64() / total
+            } else {
+                1.0
+            };
+            self.bill(slice_start, energy_wh * share);
+            if slice_end >= end {
+                break;
+            }
+            slice_start = slice_end;
+        }
+    }
+
+    fn bill(&mut self, time: OffsetDateTime, energy_wh: f64) {
+        let day = time.date();
+        let month = day.replace_day(1).unwrap();
+        let month_kwh = self.months.get(&month).map_or(0.0, |cost| cost.energy_wh) / 1000.0;
+        let rate = self.tariff.rate_at(time, month_kwh);
+        let (name, cost) = (rate.name.to_string(), energy_wh / 1000.0 * rate.per_kwh);
+
+        self.session.add(&name, energy_wh, cost);
+        for bucket in [
+            self.days
+                .entry(day)
+                .or_insert_with(|| Cost::new(&self.tariff)),
+            self.months
+                .entry(month)
+                .or_insert_with(|| Cost::new(&self.tariff)),
+        ] {
+            bucket.add(&name, energy_wh, cost);
+        }
+    }
+
+    /// The session in progress with its cost so far.
+    pub fn current(&self) -> Option<SessionCost> {
+        Some(SessionCost {
+            session: self.detector.current()?,
+            cost: self.session.clone(),
+        })
+    }
+
+    /// Cost by day, in the tariff's offset.
+    pub fn days(&self) -> &BTreeMap<Date, Cost> {
+        &self.days
+    }
+
+    /// Cost by month, keyed by the first day of the month.
+    pub fn months(&self) -> &BTreeMap<Date, Cost> {
+        &self.months
+    }
+
+    /// Close the session in progress, if any.
+    pub fn finish(&mut self) -> Option<SessionCost> {
+        let detector = std::mem::take(&mut self.detector);
+        let session = detector.finish()?;
+        let cost = std::mem::replace(&mut self.session, Cost::new(&self.tariff));
+        Some(SessionCost { session, cost })
+    }
+}
@@ -0,0 +1,508 @@
+//! Headless polling for running as a service.
+//!
+//! Every interval the endpoints of each device are fetched and the records
+//! handed to the sinks of the `[daemon]` config section:
+//!
+//! ```toml
+//! [daemon]
+//! devices = ["home", "garage"]
+//! endpoints = ["vitals", "lifetime"]
+//! interval = 30
+//!
+//! [[daemon.sinks]]
+//! type = "file"
+//! path = "/var/lib/tesla-wallcon-monitor/home.log"
+//! device = "home"
+//!
+//! [[daemon.sinks]]
+//! type = "stdout"
+//!
+//! [[daemon.sinks]]
+//! type = "mqtt"
+//! broker = "localhost:1883"
+//! ```
+//!
+//! File sinks append lines in the format of `--log`, so `replay` and
+//! `sessions` read them, stdout sinks print JSON lines with a timestamp and
+//! the device name, and MQTT sinks publish like the mqtt command with Home
+//! Assistant discovery.
+//!
+//! SIGTERM and SIGINT stop the daemon once the sinks are flushed, SIGHUP
+//! reloads the settings and reopens the sinks, keeping the old ones if the
+//! new settings are invalid. Under systemd readiness, reloads and the
+//! watchdog are reported through [`crate::systemd`].
+
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+use crate::client::WallConnectorClient;
+use crate::config::{Config, SinkConfig};
+use crate::error::Error;
+use crate::fleet::{Device, parse_devices};
+use crate::format::Units;
+use crate::logfile::{LogRecord, Payload, format_line};
+use crate::models::Version;
+use crate::mqtt::{LIFETIME_COUNTERS, MqttConfig, MqttPublisher};
+use crate::output::{OutputFormat, Printer};
+use crate::systemd;
+
+/// Endpoints the daemon can poll.
+pub const ENDPOINTS: &[&str] = &["lifetime", "version", "vitals", "wifi_status"];
+
+/// What the daemon runs with, rebuilt on every reload.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub devices: Vec<Device>,
+    pub endpoints: Vec<String>,
+    pub interval: Duration,
+    pub sinks: Vec<SinkConfig>,
+}
+
+impl Settings {
+    /// Settings of the `[daemon]` section of `config`, polling every
+    /// `default_interval` seconds unless it sets an interval.
+    pub fn from_config(
+        config: &Config,
+        default_interval: u64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let daemon = &config.daemon;
+        let list = if daemon.devices.is_empty() {
+            config.devices.keys().cloned().collect::<Vec<_>>().join(",")
+        } else {
+            daemon.devices.join(",")
+        };
+        if list.is_empty() {
+            return Err("no devices, set [daemon] devices or add [devices]".into());
+        }
+        let devices = parse_devices(&list, &config.devices)?;
+
+        let endpoints = if daemon.endpoints.is_empty() {
+            vec!["vitals".to_string()]
+        } else {
+            daemon.endpoints.clone()
+        };
+        if let Some(endpoint) = endpoints.iter().find(|e| !ENDPOINTS.contains(&e.as_str())) {
+            return Err(format!(
+                "unknown endpoint '{}', expected one of {}",
+                endpoint,
+                ENDPOINTS.join(", ")
+            )
+            .into());
+        }
+
+        if daemon.sinks.is_empty() {
+            return Err("no sinks, add a [[daemon.sinks]] section".into());
+        }
+        for sink in &daemon.sinks {
+            if let Some(device) = sink_device(sink)
+                && !devices.iter().any(|d| d.name == device)
+            {
+                return Err(format!("sink for unknown device '{}'", device).into());
+            }
+        }
+
+        Ok(Settings {
+            devices,
+            endpoints,
+            interval: Duration::from_secs(daemon.interval.unwrap_or(default_interval).max(1)),
+            sinks: daemon.sinks.clone(),
+        })
+    }
+}
+
+fn sink_device(sink: &SinkConfig) -> Option<&str> {
+    match sink {
+        SinkConfig::File { device, .. }
+        | SinkConfig::Stdout { device }
+        | SinkConfig::Mqtt { device, .. } => device.as_deref(),
+    }
+}
+
+/// A response of a device, handed to every sink.
+struct Record<'a> {
+    device: &'a str,
+    version: &'a Version,
+    log: LogRecord,
+}
+
+/// Publisher of one device with the endpoints discovery was sent for.
+struct MqttDevice {
+    publisher: MqttPublisher,
+    discovered: HashSet<&'static str>,
+}
+
+enum Sink {
+    File {
+        config: SinkConfig,
+        writer: BufWriter<File>,
+    },
+    Stdout {
+        config: SinkConfig,
+        printer: Printer,
+    },
+    Mqtt {
+        config: SinkConfig,
+        mqtt: MqttConfig,
+        devices: HashMap<String, MqttDevice>,
+    },
+}
+
+impl Sink {
+    fn open(config: &SinkConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(match config {
+            SinkConfig::File { path, .. } => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                Sink::File {
+                    config: config.clone(),
+                    writer: BufWriter::new(file),
+                }
+            }
+            SinkConfig::Stdout { .. } => Sink::Stdout {
+                config: config.clone(),
+                printer: Printer::new(OutputFormat::Json, Units::Metric),
+            },
+            SinkConfig::Mqtt {
+                broker,
+                user,
+                password,
+                topic,
+                discovery_prefix,
+                ..
+            } => {
+                let mut mqtt = MqttConfig::new(broker)
+                    .map_err(|e| format!("invalid broker '{}': {}", broker, e))?;
+                mqtt.credentials = user.clone().zip(password.clone());
+                if let Some(topic) = topic {
+                    mqtt.base_topic = topic.clone();
+                }
+                if let Some(prefix) = discovery_prefix {
+                    mqtt.discovery_prefix = prefix.clone();
+                }
+                Sink::Mqtt {
+                    config: config.clone(),
+                    mqtt,
+                    devices: HashMap::new(),
+                }
+            }
+        })
+    }
+
+    fn config(&self) -> &SinkConfig {
+        match self {
+            Sink::File { config, .. } | Sink::Stdout { config, .. } | Sink::Mqtt { config, .. } => {
+                config
+            }
+        }
+    }
+
+    fn wants(&self, device: &str) -> bool {
+        sink_device(self.config()).is_none_or(|d| d == device)
+    }
+
+    fn write(&mut self, record: &Record) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Sink::File { writer, .. } => writeln!(writer, "{}", format_line(&record.log))?,
+            Sink::Stdout { printer, .. } => {
+                printer.device = Some(record.device.to_string());
+                println!(
+                    "{}",
+                    printer.render(&record.log.payload, Some(record.log.timestamp))
+                );
+            }
+            Sink::Mqtt { mqtt, devices, .. } => {
+                let serial = &record.version.serial_number;
+                let device = devices.entry(record.device.to_string()).or_insert_with(|| {
+                    // Every device needs its own session with the broker
+                    let mut mqtt = mqtt.clone();
+                    mqtt.client_id = format!("{}-{}", mqtt.client_id, serial);
+                    MqttDevice {
+                        publisher: MqttPublisher::connect(mqtt, serial),
+                        discovered: HashSet::new(),
+                    }
+                });
+                let payload = &record.log.payload;
+                let endpoint = payload.endpoint();
+                let value = payload.to_value();
+                if !device.discovered.contains(endpoint) {
+                    let counters: &[&str] = match endpoint {
+                        // session_energy_wh drops back to 0 on plug-in
+                        "vitals" => &["session_energy_wh"],
+                        "lifetime" => LIFETIME_COUNTERS,
+                        _ => &[],
+                    };
+                    device.publisher.publish_discovery(
+                        record.version,
+                        endpoint,
+                        &value,
+                        counters,
+                    )?;
+                    device.discovered.insert(endpoint);
+                }
+                device.publisher.publish(endpoint, &value)?;
+                device.publisher.publish_availability(true)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Note that `device` couldn't be polled.
+    fn unavailable(&mut self, device: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if let Sink::Mqtt { devices, .. } = self
+            && let Some(device) = devices.get(device)
+        {
+            device.publisher.publish_availability(false)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Sink::File { writer, .. } => writer.flush(),
+            Sink::Stdout { .. } => std::io::stdout().flush(),
+            Sink::Mqtt { .. } => Ok(()),
+        }
+    }
+
+    /// Flush and, for MQTT, mark the devices offline and disconnect.
+    fn close(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.flush()?;
+        if let Sink::Mqtt { devices, .. } = self {
+            for device in devices.values() {
+                device.publisher.disconnect()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.config() {
+            SinkConfig::File { path, .. } => write!(f, "file {}", path.display())?,
+            SinkConfig::Stdout { .. } => f.write_str("stdout")?,
+            SinkConfig::Mqtt { broker, .. } => write!(f, "mqtt {}", broker)?,
+        }
+        match sink_device(self.config()) {
+            Some(device) => write!(f, " ({})", device),
+            None => Ok(()),
+        }
+    }
+}
+
+fn open_sinks(configs: &[SinkConfig]) -> Result<Vec<Sink>, Box<dyn std::error::Error>> {
+    configs.iter().map(Sink::open).collect()
+}
+
+fn close_sinks(sinks: Vec<Sink>) {
+    for sink in sinks {
+        let name = sink.to_string();
+        if let Err(e) = sink.close() {
+            eprintln!("Error closing {}: {}", name, e);
+        }
+    }
+}
+
+fn fetch(client: &WallConnectorClient, endpoint: &str) -> Result<Payload, Error> {
+    match endpoint {
+        "lifetime" => client.lifetime().map(Payload::Lifetime),
+        "version" => client.version().map(Payload::Version),
+        "vitals" => client.vitals().map(Payload::Vitals),
+        "wifi_status" => client.wifi_status().map(Payload::WifiStatus),
+        _ => unreachable!(),
+    }
+}
+
+/// Poll state of a device.
+#[derive(Default)]
+struct DeviceState {
+    /// Fetched once, MQTT needs the serial number.
+    version: Option<Version>,
+    failing: bool,
+}
+
+/// Fetch the version, if not known yet, and the endpoints of `device`.
+fn poll_device(
+    device: &Device,
+    endpoints: &[String],
+    version: Option<Version>,
+) -> Result<(Version, Vec<LogRecord>), Error> {
+    let version = match version {
+        Some(version) => version,
+        None => device.client.version()?,
+    };
+    let mut records = Vec::with_capacity(endpoints.len());
+    for endpoint in endpoints {
+        let payload = fetch(&device.client, endpoint)?;
+        records.push(LogRecord {
+            timestamp: OffsetDateTime::now_utc(),
+            payload,
+        });
+    }
+    Ok((version, records))
+}
+
+/// Poll all devices concurrently and write their records to `sinks`.
+fn poll(settings: &Settings, states: &mut [DeviceState], sinks: &mut [Sink]) {
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = settings
+            .devices
+            .iter()
+            .zip(states.iter())
+            .map(|(device, state)| {
+                let version = state.version.clone();
+                scope.spawn(move || poll_device(device, &settings.endpoints, version))
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    for ((device, state), result) in settings.devices.iter().zip(states).zip(results) {
+        let name = device.name.as_str();
+        let (version, records) = match result {
+            Ok(polled) => polled,
+            Err(e) => {
+                // Report outages once rather than on every poll
+                if !state.failing {
+                    eprintln!("{}: Error polling {}: {}", name, device.client.addr(), e);
+                    state.failing = true;
+                }
+                for sink in sinks.iter_mut().filter(|s| s.wants(name)) {
+                    let _ = sink.unavailable(name);
+                }
+                continue;
+            }
+        };
+        if state.failing {
+            eprintln!("{}: polling again", name);
+            state.failing = false;
+        }
+        for log in records {
+            let record = Record {
+                device: name,
+                version: &version,
+                log,
+            };
+            for sink in sinks.iter_mut().filter(|s| s.wants(name)) {
+                if let Err(e) = sink.write(&record) {
+                    eprintln!("{}: Error writing to {}: {}", name, sink, e);
+                }
+            }
+        }
+        state.version = Some(version);
+    }
+
+    for sink in sinks.iter_mut() {
+        if let Err(e) = sink.flush() {
+            eprintln!("Error flushing {}: {}", sink, e);
+        }
+    }
+}
+
+fn describe(settings: &Settings, sinks: &[Sink]) -> String {
+    let devices: Vec<String> = settings
+        .devices
+        .iter()
+        .map(|d| format!("{} ({})", d.name, d.client.addr()))
+        .collect();
+    let sinks: Vec<String> = sinks.iter().map(Sink::to_string).collect();
+    format!(
+        "Polling {} of {} every {}s into {}",
+        settings.endpoints.join(", "),
+        devices.join(", "),
+        settings.interval.as_secs(),
+        sinks.join(", ")
+    )
+}
+
+fn notify(state: &str) {
+    if let Err(e) = systemd::notify(state) {
+        eprintln!("Error notifying systemd: {}", e);
+    }
+}
+
+/// Run until SIGTERM or SIGINT with the settings of `load`, which is called
+/// again on SIGHUP.
+pub fn run(
+    mut load: impl FnMut() -> Result<Settings, Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut settings = load()?;
+    let mut sinks = open_sinks(&settings.sinks)?;
+    let mut states: Vec<DeviceState> = settings
+        .devices
+        .iter()
+        .map(|_| Default::default())
+        .collect();
+
+    let mut signals = Signals::new([SIGTERM, SIGINT, SIGHUP])?;
+    let (sender, received) = mpsc::channel();
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            if sender.send(signal).is_err() {
+                break;
+            }
+        }
+    });
+
+    eprintln!("{}", describe(&settings, &sinks));
+    notify(systemd::READY);
+    let watchdog = systemd::watchdog_interval();
+    let mut next_poll = Instant::now();
+    let mut next_watchdog = Instant::now();
+
+    loop {
+        if Instant::now() >= next_poll {
+            poll(&settings, &mut states, &mut sinks);
+            // Polls missed while the previous one was slow are skipped
+            next_poll = (next_poll + settings.interval).max(Instant::now());
+        }
+        if let Some(every) = watchdog
+            && Instant::now() >= next_watchdog
+        {
+            notify(systemd::WATCHDOG);
+            next_watchdog = Instant::now() + every;
+        }
+
+        let wake = match watchdog {
+            Some(_) => next_poll.min(next_watchdog),
+            None => next_poll,
+        };
+        match received.recv_timeout(wake.saturating_duration_since(Instant::now())) {
+            Ok(SIGHUP) => {
+                notify(systemd::RELOADING);
+                // The old sinks stay open until the new ones are
+                match load().and_then(|new| Ok((open_sinks(&new.sinks)?, new))) {
+                    Ok((opened, new)) => {
+                        close_sinks(std::mem::replace(&mut sinks, opened));
+                        states = new.devices.iter().map(|_| Default::default()).collect();
+                        settings = new;
+                        eprintln!("Reloaded. {}", describe(&settings, &sinks));
+                        next_poll = Instant::now();
+                    }
+                    Err(e) => eprintln!("Error reloading, keeping the old settings: {}", e),
+                }
+                notify(systemd::READY);
+            }
+            Ok(signal) => {
+                eprintln!("Stopping on signal {}", signal);
+                break;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    notify(systemd::STOPPING);
+    close_sinks(sinks);
+    Ok(())
+}
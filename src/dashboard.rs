@@ -0,0 +1,541 @@
+//! Full-screen dashboard with live charts.
+//!
+//! A background thread polls the connector every `delay` and hands the
+//! samples to the UI thread, which keeps a sliding window of history for the
+//! charts and stays responsive to the keyboard while a request is pending.
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::Marker;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Axis, Block, Chart, Dataset, Gauge, GraphType, Paragraph, Row, Table};
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+use crate::client::WallConnectorClient;
+use crate::format::{Units, decode_ssid, format_duration, format_time_of_day};
+use crate::models::{Lifetime, Vitals, WifiStatus};
+use crate::term::{is_exit_key, read_key};
+
+/// How often the UI redraws and checks for keys while waiting for data.
+const TICK: Duration = Duration::from_millis(250);
+
+/// Name, color and points of one chart line.
+type Series<'a> = (&'a str, Color, Vec<(f64, f64)>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Panel {
+    State,
+    Current,
+    Voltage,
+    Temperature,
+    Wifi,
+    Lifetime,
+}
+
+impl Panel {
+    const ALL: [Panel; 6] = [
+        Panel::State,
+        Panel::Current,
+        Panel::Voltage,
+        Panel::Temperature,
+        Panel::Wifi,
+        Panel::Lifetime,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|p| *p == self).unwrap()
+    }
+
+    fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    fn previous(self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// One poll of the background thread.
+pub struct Sample {
+    pub vitals: Result<Vitals, String>,
+    pub lifetime: Option<Lifetime>,
+    pub wifi: Option<WifiStatus>,
+}
+
+fn poll_connector(client: WallConnectorClient, delay: Duration) -> Receiver<Sample> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        loop {
+            let sample = Sample {
+                vitals: client.vitals().map_err(|e| e.to_string()),
+                lifetime: client.lifetime().ok(),
+                wifi: client.wifi_status().ok(),
+            };
+            // The dashboard is gone once sending fails
+            if tx.send(sample).is_err() {
+                break;
+            }
+            std::thread::sleep(delay);
+        }
+    });
+    rx
+}
+
+/// State of the dashboard, fed with [`Sample`]s and keys by [`run`].
+pub struct Dashboard {
+    addr: String,
+    window: Duration,
+    units: Units,
+    started: Instant,
+    /// Vitals with the seconds since `started` they were received at.
+    history: VecDeque<(f64, Vitals)>,
+    rssi: VecDeque<(f64, f64)>,
+    lifetime: Option<Lifetime>,
+    wifi: Option<WifiStatus>,
+    error: Option<String>,
+    updated: Option<OffsetDateTime>,
+    focus: Panel,
+    zoomed: bool,
+}
+
+impl Dashboard {
+    /// An empty dashboard for the connector at `addr` showing `window` of
+    /// history.
+    pub fn new(addr: &str, window: Duration, units: Units) -> Self {
+        Dashboard {
+            addr: addr.to_string(),
+            window,
+            units,
+            started: Instant::now(),
+            history: VecDeque::new(),
+            rssi: VecDeque::new(),
+            lifetime: None,
+            wifi: None,
+            error: None,
+            updated: None,
+            focus: Panel::State,
+            zoomed: false,
+        }
+    }
+
+    fn now(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+
+    /// Add `sample`, dropping history older than the window. A failed poll
+    /// keeps the last data and marks it stale.
+    pub fn push(&mut self, sample: Sample) {
+        let now = self.now();
+        match sample.vitals {
+            Ok(vitals) => {
+                self.history.push_back((now, vitals));
+                self.error = None;
+                self.updated = Some(OffsetDateTime::now_utc());
+            }
+            Err(e) => self.error = Some(e),
+        }
+        if let Some(wifi) = sample.wifi {
+            self.rssi.push_back((now, wifi.wifi_rssi as f64));
+            self.wifi = Some(wifi);
+        }
+        if sample.lifetime.is_some() {
+            self.lifetime = sample.lifetime;
+        }
+        let oldest = now - self.window.as_secs_f64();
+        while self.history.front().is_some_and(|(t, _)| *t < oldest) {
+            self.history.pop_front();
+        }
+        while self.rssi.front().is_some_and(|(t, _)| *t < oldest) {
+            self.rssi.pop_front();
+        }
+    }
+
+    /// The most recent vitals, kept while polls fail.
+    pub fn latest(&self) -> Option<&Vitals> {
+        self.history.back().map(|(_, vitals)| vitals)
+    }
+
+    /// `value` of the vitals in the window, with the seconds since the
+    /// dashboard started they were received at.
+    pub fn series(&self, value: impl Fn(&Vitals) -> f64) -> Vec<(f64, f64)> {
+        self.history.iter().map(|(t, v)| (*t, value(v))).collect()
+    }
+
+    /// Returns false once the dashboard should exit.
+    pub fn handle_key(&mut self, key_event: KeyEvent) -> bool {
+        if is_exit_key(&key_event) {
+            return false;
+        }
+        match key_event.code {
+            KeyCode::Tab | KeyCode::Right | KeyCode::Down => self.focus = self.focus.next(),
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Up => self.focus = self.focus.previous(),
+            KeyCode::Enter | KeyCode::Char('z') => self.zoomed = !self.zoomed,
+            KeyCode::Char('q') if key_event.modifiers.is_empty() => return false,
+            _ => {}
+        }
+        true
+    }
+
+    fn block(&self, panel: Panel, title: &str) -> Block<'static> {
+        let style = if panel == self.focus {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        Block::bordered()
+            .title(format!(" {} ", title))
+            .border_style(style)
+    }
+
+    /// Render every panel, or only the focused one when zoomed.
+    pub fn draw(&self, frame: &mut Frame) {
+        let [body, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+
+        if self.zoomed {
+            self.draw_panel(frame, self.focus, body);
+        } else {
+            let [top, middle, bottom] = Layout::vertical([
+                Constraint::Percentage(40),
+                Constraint::Percentage(35),
+                Constraint::Percentage(25),
+            ])
+            .areas(body);
+            let [state, current] =
+                Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)])
+                    .areas(top);
+            let [voltage, temperature] =
+                Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .areas(middle);
+            let [wifi, lifetime] =
+                Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                    .areas(bottom);
+            self.draw_panel(frame, Panel::State, state);
+            self.draw_panel(frame, Panel::Current, current);
+            self.draw_panel(frame, Panel::Voltage, voltage);
+            self.draw_panel(frame, Panel::Temperature, temperature);
+            self.draw_panel(frame, Panel::Wifi, wifi);
+            self.draw_panel(frame, Panel::Lifetime, lifetime);
+        }
+
+        let status = match (&self.error, self.updated) {
+            (Some(e), Some(updated)) => Span::styled(
+                format!("Stale since {} UTC, {}", format_time_of_day(updated), e),
+                Style::default().fg(Color::Red),
+            ),
+            (Some(e), None) => {
+                Span::styled(format!("Error: {}", e), Style::default().fg(Color::Red))
+            }
+            (None, Some(updated)) => {
+                Span::raw(format!("Updated {} UTC", format_time_of_day(updated)))
+            }
+            (None, None) => Span::raw("Waiting for data..."),
+        };
+        let help = Span::styled(
+            "  TAB/arrows select, ENTER zoom, ESC or Ctrl+C exit",
+            Style::default().fg(Color::DarkGray),
+        );
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::raw(format!("{}  ", self.addr)),
+                status,
+                help,
+            ])),
+            footer,
+        );
+    }
+
+    fn draw_panel(&self, frame: &mut Frame, panel: Panel, area: Rect) {
+        match panel {
+            Panel::State => self.draw_state(frame, area),
+            Panel::Current => self.draw_chart(
+                frame,
+                area,
+                panel,
+                "Current (A)",
+                &[
+                    (
+                        "vehicle",
+                        Color::Yellow,
+                        self.series(|v| v.vehicle_current_a),
+                    ),
+                    ("A", Color::Red, self.series(|v| v.current_a_a)),
+                    ("B", Color::Green, self.series(|v| v.current_b_a)),
+                    ("C", Color::Blue, self.series(|v| v.current_c_a)),
+                ],
+            ),
+            Panel::Voltage => self.draw_chart(
+                frame,
+                area,
+                panel,
+                "Voltage (V)",
+                &[
+                    ("grid", Color::Yellow, self.series(|v| v.grid_v)),
+                    ("A", Color::Red, self.series(|v| v.voltage_a_v)),
+                    ("B", Color::Green, self.series(|v| v.voltage_b_v)),
+                    ("C", Color::Blue, self.series(|v| v.voltage_c_v)),
+                ],
+            ),
+            Panel::Temperature => {
+                let units = self.units;
+                self.draw_chart(
+                    frame,
+                    area,
+                    panel,
+                    &format!("Temperature ({})", units.temperature_symbol()),
+                    &[
+                        (
+                            "handle",
+                            Color::Yellow,
+                            self.series(|v| units.temperature(v.handle_temp_c)),
+                        ),
+                        (
+                            "pcba",
+                            Color::Green,
+                            self.series(|v| units.temperature(v.pcba_temp_c)),
+                        ),
+                        (
+                            "mcu",
+                            Color::Cyan,
+                            self.series(|v| units.temperature(v.mcu_temp_c)),
+                        ),
+                    ],
+                )
+            }
+            Panel::Wifi => self.draw_wifi(frame, area),
+            Panel::Lifetime => self.draw_lifetime(frame, area),
+        }
+    }
+
+    fn draw_state(&self, frame: &mut Frame, area: Rect) {
+        let block = self.block(Panel::State, "Charging");
+        let Some(vitals) = self.latest() else {
+            frame.render_widget(Paragraph::new("No data yet").block(block), area);
+            return;
+        };
+        let yes_no = |value: bool| {
+            if value {
+                Span::styled("yes", Style::default().fg(Color::Green))
+            } else {
+                Span::raw("no")
+            }
+        };
+        let mut lines = vec![
+            Line::from(vec![
+                Span::raw("State:     "),
+                Span::styled(
+                    vitals.evse_state.to_string(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+            ]),
+            Line::from(vec![
+                Span::raw("Vehicle:   "),
+                yes_no(vitals.vehicle_connected),
+            ]),
+            Line::from(vec![
+                Span::raw("Contactor: "),
+                yes_no(vitals.contactor_closed),
+            ]),
+            Line::from(format!("Current:   {:.1} A", vitals.vehicle_current_a)),
+            Line::from(format!("Session:   {}", format_duration(vitals.session_s))),
+            Line::from(format!(
+                "Energy:    {:.3} kWh",
+                vitals.session_energy_wh / 1000.0
+            )),
+            Line::from(format!(
+                "Grid:      {:.1} V {:.3} Hz",
+                vitals.grid_v, vitals.grid_hz
+            )),
+        ];
+        for alert in &vitals.current_alerts {
+            lines.push(Line::styled(
+                format!("Alert:     {}", alert),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        for reason in &vitals.evse_not_ready_reasons {
+            lines.push(Line::styled(
+                format!("Not ready: {}", reason),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_chart(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        panel: Panel,
+        title: &str,
+        series: &[Series],
+    ) {
+        let now = self.now();
+        let start = now - self.window.as_secs_f64();
+        let (mut min, mut max) = (f64::MAX, f64::MIN);
+        for (_, _, points) in series {
+            for (_, y) in points {
+                min = min.min(*y);
+                max = max.max(*y);
+            }
+        }
+        if min > max {
+            (min, max) = (0.0, 1.0);
+        }
+        // Leave some room so flat lines don't sit on the border
+        let pad = ((max - min) * 0.1).max(1.0);
+        let (low, high) = ((min - pad).floor(), (max + pad).ceil());
+
+        let datasets = series
+            .iter()
+            .map(|(name, color, points)| {
+                Dataset::default()
+                    .name(*name)
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(Style::default().fg(*color))
+                    .data(points)
+            })
+            .collect();
+        let minutes = self.window.as_secs() / 60;
+        let chart = Chart::new(datasets)
+            .block(self.block(panel, title))
+            .x_axis(
+                Axis::default()
+                    .bounds([start, now])
+                    .labels([format!("-{}m", minutes), "now".to_string()]),
+            )
+            .y_axis(
+                Axis::default()
+                    .bounds([low, high])
+                    .labels([format!("{:.0}", low), format!("{:.0}", high)]),
+            );
+        frame.render_widget(chart, area);
+    }
+
+    fn draw_wifi(&self, frame: &mut Frame, area: Rect) {
+        let block = self.block(Panel::Wifi, "Wi-Fi");
+        let Some(wifi) = &self.wifi else {
+            frame.render_widget(Paragraph::new("No data yet").block(block), area);
+            return;
+        };
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let [gauge, text] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+        let strength = wifi.wifi_signal_strength.clamp(0, 100) as u16;
+        let color = match strength {
+            0..=39 => Color::Red,
+            40..=59 => Color::Yellow,
+            _ => Color::Green,
+        };
+        frame.render_widget(
+            Gauge::default()
+                .gauge_style(Style::default().fg(color))
+                .percent(strength),
+            gauge,
+        );
+        let rssi_min = self.rssi.iter().map(|(_, r)| *r).fold(f64::MAX, f64::min);
+        let lines = vec![
+            Line::from(format!("SSID:      {}", decode_ssid(&wifi.wifi_ssid))),
+            Line::from(format!(
+                "RSSI/SNR:  {} dBm / {} dB",
+                wifi.wifi_rssi, wifi.wifi_snr
+            )),
+            Line::from(if self.rssi.len() > 1 {
+                format!("RSSI low:  {:.0} dBm in window", rssi_min)
+            } else {
+                String::new()
+            }),
+            Line::from(format!(
+                "Connected: {}  Internet: {}",
+                wifi.wifi_connected, wifi.internet
+            )),
+        ];
+        frame.render_widget(Paragraph::new(lines), text);
+    }
+
+    fn draw_lifetime(&self, frame: &mut Frame, area: Rect) {
+        let block = self.block(Panel::Lifetime, "Lifetime");
+        let Some(lifetime) = &self.lifetime else {
+            frame.render_widget(Paragraph::new("No data yet").block(block), area);
+            return;
+        };
+        let rows = [
+            (
+                "Energy",
+                format!("{:.2} kWh", lifetime.energy_wh as f64 / 1000.0),
+                "Charge starts",
+                lifetime.charge_starts.to_string(),
+            ),
+            (
+                "Charging time",
+                format_duration(lifetime.charging_time_s),
+                "Uptime",
+                format_duration(lifetime.uptime_s),
+            ),
+            (
+                "Contactor cycles",
+                lifetime.contactor_cycles.to_string(),
+                "Loaded cycles",
+                lifetime.contactor_cycles_loaded.to_string(),
+            ),
+            (
+                "Connector cycles",
+                lifetime.connector_cycles.to_string(),
+                "Thermal foldbacks",
+                lifetime.thermal_foldbacks.to_string(),
+            ),
+            (
+                "Alert count",
+                lifetime.alert_count.to_string(),
+                "Avg startup temp",
+                self.units.format_temperature(lifetime.avg_startup_temp),
+            ),
+        ]
+        .into_iter()
+        .map(|(k1, v1, k2, v2)| Row::new(vec![k1.to_string(), v1, k2.to_string(), v2]));
+        let widths = [
+            Constraint::Length(17),
+            Constraint::Length(14),
+            Constraint::Length(18),
+            Constraint::Min(10),
+        ];
+        frame.render_widget(Table::new(rows, widths).block(block), area);
+    }
+}
+
+/// Run the dashboard for `client` until ESC, Ctrl+C or `q` is pressed.
+/// `window` is how much history the charts show.
+pub fn run(
+    client: WallConnectorClient,
+    delay: Duration,
+    window: Duration,
+    units: Units,
+) -> std::io::Result<()> {
+    let mut dashboard = Dashboard::new(client.addr(), window, units);
+    let samples = poll_connector(client, delay);
+
+    let mut terminal = ratatui::init();
+    let result = (|| {
+        loop {
+            while let Ok(sample) = samples.try_recv() {
+                dashboard.push(sample);
+            }
+            terminal.draw(|frame| dashboard.draw(frame))?;
+            if let Some(key_event) = read_key(Some(TICK))
+                && !dashboard.handle_key(key_event)
+            {
+                break;
+            }
+        }
+        Ok(())
+    })();
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,137 @@
+//! Finding wall connectors on the local network.
+//!
+//! Every address of an IPv4 range is asked for `/api/1/version` and the
+//! hosts answering with a valid [`Version`] are reported.
+
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::client::{DEFAULT_PORT, endpoint_url};
+use crate::models::Version;
+
+/// Largest range we scan, a /16.
+const MAX_HOSTS: u64 = 1 << 16;
+
+/// An IPv4 range in CIDR notation, e.g. `192.168.1.0/24`. A plain address is
+/// a range of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: Ipv4Addr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Addresses of the range, without the network and broadcast addresses
+    /// for ranges that have them.
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let start = u32::from(self.network) as u64;
+        let size = 1u64 << (32 - self.prefix);
+        let (first, last) = if self.prefix <= 30 {
+            (start + 1, start + size - 2)
+        } else {
+            (start, start + size - 1)
+        };
+        (first..=last).map(|ip| Ipv4Addr::from(ip as u32))
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, prefix) = s.split_once('/').unwrap_or((s, "32"));
+        let ip: Ipv4Addr = ip
+            .parse()
+            .map_err(|_| format!("invalid IPv4 address in '{}'", s))?;
+        let prefix: u8 = match prefix.parse() {
+            Ok(prefix) if prefix <= 32 => prefix,
+            _ => return Err(format!("invalid prefix length in '{}'", s)),
+        };
+        if (1u64 << (32 - prefix)) > MAX_HOSTS {
+            return Err(format!("'{}' is too large, scan at most a /16", s));
+        }
+        let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+        Ok(Cidr {
+            network: Ipv4Addr::from(u32::from(ip) & mask),
+            prefix,
+        })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+/// A wall connector that answered.
+#[derive(Debug, Clone)]
+pub struct Found {
+    pub ip: Ipv4Addr,
+    pub port: u16,
+    pub version: Version,
+}
+
+impl Found {
+    /// Address to pass to the client or the CLI, with the port unless it is
+    /// the default one.
+    pub fn addr(&self) -> String {
+        if self.port == DEFAULT_PORT {
+            self.ip.to_string()
+        } else {
+            format!("{}:{}", self.ip, self.port)
+        }
+    }
+}
+
+fn probe(client: &reqwest::blocking::Client, ip: Ipv4Addr, port: u16) -> Option<Version> {
+    client
+        .get(endpoint_url(&ip.to_string(), port, "version"))
+        .send()
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .ok()
+}
+
+/// Ask every host of `cidr` on each of `ports` for its version, at most
+/// `concurrency` at a time, giving each `timeout` to answer. The result is
+/// ordered by address and port.
+pub fn discover(
+    cidr: &Cidr,
+    ports: &[u16],
+    timeout: Duration,
+    concurrency: usize,
+) -> Result<Vec<Found>, Box<dyn std::error::Error>> {
+    let client = reqwest::blocking::Client::builder()
+        .connect_timeout(timeout)
+        .timeout(timeout)
+        .build()?;
+    let targets: Vec<(Ipv4Addr, u16)> = cidr
+        .hosts()
+        .flat_map(|ip| ports.iter().map(move |port| (ip, *port)))
+        .collect();
+    let next = AtomicUsize::new(0);
+    let found = Mutex::new(Vec::new());
+
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, targets.len().max(1)) {
+            scope.spawn(|| {
+                while let Some(&(ip, port)) = targets.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if let Some(version) = probe(&client, ip, port) {
+                        found.lock().unwrap().push(Found { ip, port, version });
+                    }
+                }
+            });
+        }
+    });
+
+    let mut found = found.into_inner().unwrap();
+    found.sort_by_key(|f| (f.ip, f.port));
+    Ok(found)
+}
@@ -0,0 +1,205 @@
+//! Errors of requests to a wall connector.
+//!
+//! Failures are sorted into classes a caller can act on: the connector being
+//! unreachable (DNS, connection refused, timeout, other transport errors),
+//! refusing the request (HTTP status) or answering with something that isn't
+//! the expected payload (not JSON, schema mismatch). Each class has its own
+//! process exit code, see [`Error::exit_code`].
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt;
+use std::net::ToSocketAddrs;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The name of the wall connector didn't resolve.
+    Dns {
+        host: String,
+        source: std::io::Error,
+    },
+    /// Nothing accepts connections on the wall connector's port.
+    ConnectionRefused(reqwest::Error),
+    /// No connection or no complete response within the timeouts.
+    Timeout(reqwest::Error),
+    /// Any other failure to talk to the wall connector, e.g. a reset
+    /// connection or a truncated body.
+    Http(reqwest::Error),
+    /// The wall connector answered with an error status.
+    Status { url: String, status: u16 },
+    /// The body of the response isn't JSON.
+    NotJson {
+        endpoint: String,
+        source: serde_json::Error,
+    },
+    /// The body is JSON but not the expected payload. `field` is the path of
+    /// the field that failed, e.g. `evse_state` or `current_alerts[0]`.
+    Schema {
+        endpoint: String,
+        field: String,
+        source: serde_json::Error,
+    },
+}
+
+impl Error {
+    /// Exit code of the CLI for the class of the error. 1 and 2 are left to
+    /// other failures and usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Dns { .. } => 3,
+            Error::ConnectionRefused(_) => 4,
+            Error::Timeout(_) => 5,
+            Error::Http(_) => 6,
+            Error::Status { .. } => 7,
+            Error::NotJson { .. } => 8,
+            Error::Schema { .. } => 9,
+        }
+    }
+
+    /// Whether the wall connector couldn't be reached at all.
+    pub fn is_unreachable(&self) -> bool {
+        matches!(
+            self,
+            Error::Dns { .. } | Error::ConnectionRefused(_) | Error::Timeout(_) | Error::Http(_)
+        )
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Dns { host, source } => write!(f, "name lookup of {} failed: {}", host, source),
+            Error::ConnectionRefused(e) => write!(f, "connection refused: {}", describe(e)),
+            Error::Timeout(e) => write!(f, "timed out: {}", describe(e)),
+            Error::Http(e) => write!(f, "{}", describe(e)),
+            Error::Status { url, status } => write!(
+                f,
+                "HTTP status {} {} for {}",
+                status,
+                crate::http::reason(*status),
+                url
+            ),
+            Error::NotJson { endpoint, source } => {
+                write!(f, "{} response isn't JSON: {}", endpoint, source)
+            }
+            Error::Schema {
+                endpoint,
+                field,
+                source,
+            } => write!(
+                f,
+                "{} response doesn't match the expected payload at `{}`: {}",
+                endpoint, field, source
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Dns { source, .. } => Some(source),
+            Error::ConnectionRefused(e) | Error::Timeout(e) | Error::Http(e) => Some(e),
+            Error::Status { .. } => None,
+            Error::NotJson { source, .. } | Error::Schema { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if let Some(status) = e.status() {
+            return Error::Status {
+                url: e.url().map(|url| url.to_string()).unwrap_or_default(),
+                status: status.as_u16(),
+            };
+        }
+        if e.is_timeout() {
+            return Error::Timeout(e);
+        }
+        // Neither reqwest nor hyper expose this as a kind, look for the
+        // underlying cause. Names that don't resolve never get here, see
+        // [`resolve`]
+        let mut refused = false;
+        let mut source = std::error::Error::source(&e);
+        while let Some(cause) = source {
+            refused |= cause
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|io| io.kind() == std::io::ErrorKind::ConnectionRefused);
+            source = cause.source();
+        }
+        if refused {
+            Error::ConnectionRefused(e)
+        } else {
+            Error::Http(e)
+        }
+    }
+}
+
+/// `addr` and `port` as `host:port`, `addr` may come with a port of its own.
+pub(crate) fn host_port(addr: &str, port: u16) -> String {
+    if addr.contains(':') && !addr.ends_with(']') {
+        addr.to_string()
+    } else {
+        format!("{}:{}", addr, port)
+    }
+}
+
+/// Resolve the name of the wall connector before connecting, reqwest
+/// doesn't tell a name that didn't resolve apart from other connection
+/// failures.
+pub(crate) fn resolve(addr: &str, port: u16) -> Result<(), Error> {
+    resolved(addr, host_port(addr, port).to_socket_addrs())
+}
+
+/// [`Error::Dns`] unless `lookup` of `addr` found an address.
+pub(crate) fn resolved<I: Iterator>(addr: &str, lookup: std::io::Result<I>) -> Result<(), Error> {
+    let source = match lookup.map(|mut addrs| addrs.next()) {
+        Ok(Some(_)) => return Ok(()),
+        Ok(None) => std::io::Error::new(std::io::ErrorKind::NotFound, "no addresses"),
+        Err(e) => e,
+    };
+    Err(Error::Dns {
+        host: addr.to_string(),
+        source,
+    })
+}
+
+/// `e` with its causes, reqwest's own message rarely says what went wrong.
+fn describe(e: &reqwest::Error) -> String {
+    let mut text = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        text.push_str(": ");
+        text.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    text
+}
+
+/// Parse the body of `endpoint`.
+pub(crate) fn parse<T: DeserializeOwned>(endpoint: &str, text: &str) -> Result<T, Error> {
+    let value: Value = serde_json::from_str(text).map_err(|source| Error::NotJson {
+        endpoint: endpoint.to_string(),
+        source,
+    })?;
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let path = e.path().to_string();
+        let source = e.into_inner();
+        // A missing field is reported at its parent
+        let message = source.to_string();
+        let missing = message
+            .strip_prefix("missing field `")
+            .and_then(|rest| rest.split('`').next());
+        let field = match missing {
+            Some(missing) if path == "." => missing.to_string(),
+            Some(missing) => format!("{}.{}", path, missing),
+            None => path,
+        };
+        Error::Schema {
+            endpoint: endpoint.to_string(),
+            field,
+            source,
+        }
+    })
+}
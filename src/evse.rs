@@ -0,0 +1,111 @@
+//! Typed versions of the numeric status codes in [`crate::Vitals`].
+//!
+//! The firmware doesn't document these codes. The names follow the mapping
+//! used by the community integrations where one exists and otherwise what the
+//! logs in `data/` show the code doing. Codes that aren't known parse as
+//! `Unknown(code)` so new firmware values never break deserialization, and
+//! every value serializes back to its original number.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Declare a `u32` backed enum with an `Unknown(u32)` fallback variant.
+macro_rules! code_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $( $(#[$vmeta:meta])* $variant:ident = $code:literal => $text:literal, )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+        #[serde(from = "u32", into = "u32")]
+        pub enum $name {
+            $( $(#[$vmeta])* $variant, )*
+            Unknown(u32),
+        }
+
+        impl $name {
+            /// Numeric code as reported by the wall connector.
+            pub fn code(self) -> u32 {
+                match self {
+                    $( Self::$variant => $code, )*
+                    Self::Unknown(code) => code,
+                }
+            }
+
+            /// Human readable description of the code.
+            pub fn description(self) -> &'static str {
+                match self {
+                    $( Self::$variant => $text, )*
+                    Self::Unknown(_) => "Unknown",
+                }
+            }
+        }
+
+        impl From<u32> for $name {
+            fn from(code: u32) -> Self {
+                match code {
+                    $( $code => Self::$variant, )*
+                    code => Self::Unknown(code),
+                }
+            }
+        }
+
+        impl From<$name> for u32 {
+            fn from(value: $name) -> u32 {
+                value.code()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{} ({})", self.description(), self.code())
+            }
+        }
+    };
+}
+
+code_enum! {
+    /// `evse_state` of the vitals.
+    pub enum EvseState {
+        Booting = 0 => "Booting",
+        NotConnected = 1 => "Not connected",
+        Connected = 2 => "Connected",
+        /// Not in the community mapping, seen for a single poll between
+        /// plug-in and `WaitingForCar`.
+        Connecting = 3 => "Connecting",
+        Ready = 4 => "Ready",
+        Negotiating = 6 => "Negotiating",
+        /// Also seen briefly around plug-in and unplug on a healthy unit.
+        Error = 7 => "Error",
+        ChargingFinished = 8 => "Charging finished",
+        WaitingForCar = 9 => "Waiting for car",
+        ChargingReduced = 10 => "Charging reduced",
+        Charging = 11 => "Charging",
+    }
+}
+
+code_enum! {
+    /// `config_status` of the vitals, every unit we've seen reports 5.
+    pub enum ConfigStatus {
+        Configured = 5 => "Configured",
+    }
+}
+
+code_enum! {
+    /// Entries of `evse_not_ready_reasons` in the vitals.
+    pub enum NotReadyReason {
+        /// Reported for as long as a vehicle is plugged in.
+        VehicleSession = 1 => "Vehicle session active",
+        /// Seen only after a factory reset, before the unit was set up again.
+        NotCommissioned = 2 => "Not commissioned",
+        /// Seen for a single poll while unplugging.
+        Unplugging = 3 => "Unplugging",
+        /// Reported while idle and right after plug-in until the vehicle
+        /// answers.
+        VehicleNotReady = 4 => "Vehicle not ready",
+        /// Reported whenever no vehicle is plugged in.
+        NoVehicle = 8 => "No vehicle connected",
+    }
+}
@@ -0,0 +1,355 @@
+//! Prometheus exporter serving the wall connector data on `/metrics`.
+
+use std::fmt::Write;
+use std::net::TcpListener;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::client::WallConnectorClient;
+use crate::http::{self, Request, Response};
+use crate::models::{Lifetime, Version, Vitals, WifiStatus};
+
+/// Address the exporter listens on unless told otherwise.
+pub const DEFAULT_LISTEN: &str = "0.0.0.0:9869";
+
+const PREFIX: &str = "tesla_wallcon";
+
+/// Metrics are in base units, the connector reports energy in Wh.
+const JOULES_PER_WH: f64 = 3600.0;
+
+/// Builds a text exposition format document, every sample gets the
+/// `serial` label.
+struct MetricsWriter {
+    out: String,
+    serial: String,
+}
+
+impl MetricsWriter {
+    fn new(serial: &str) -> Self {
+        MetricsWriter {
+            out: String::new(),
+            serial: escape_label(serial),
+        }
+    }
+
+    fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {}_{} {}", PREFIX, name, help);
+        let _ = writeln!(self.out, "# TYPE {}_{} {}", PREFIX, name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let _ = write!(self.out, "{}_{}{{serial=\"{}\"", PREFIX, name, self.serial);
+        for (key, label) in labels {
+            let _ = write!(self.out, ",{}=\"{}\"", key, escape_label(label));
+        }
+        let _ = writeln!(self.out, "}} {}", value);
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.header(name, "gauge", help);
+        self.sample(name, &[], value);
+    }
+
+    fn counter(&mut self, name: &str, help: &str, value: f64) {
+        self.header(name, "counter", help);
+        self.sample(name, &[], value);
+    }
+
+    /// Gauge with one sample per value of the label `key`.
+    fn gauges(&mut self, name: &str, help: &str, key: &str, values: &[(&str, f64)]) {
+        self.header(name, "gauge", help);
+        for (label, value) in values {
+            self.sample(name, &[(key, label)], *value);
+        }
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn flag(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+/// Render all metrics of one scrape. `data` is `None` when fetching failed,
+/// in which case only the `info` and `up` metrics are rendered.
+pub fn render_metrics(
+    version: &Version,
+    data: Option<(&Vitals, &Lifetime, &WifiStatus)>,
+) -> String {
+    let mut w = MetricsWriter::new(&version.serial_number);
+
+    w.header("info", "gauge", "Wall connector device information");
+    w.sample(
+        "info",
+        &[
+            ("part_number", &version.part_number),
+            ("firmware_version", &version.firmware_version),
+        ],
+        1.0,
+    );
+    w.gauge(
+        "up",
+        "Whether the last fetch from the wall connector succeeded",
+        flag(data.is_some()),
+    );
+    let Some((vitals, lifetime, wifi)) = data else {
+        return w.out;
+    };
+
+    w.gauge(
+        "vehicle_connected",
+        "Whether a vehicle is plugged in",
+        flag(vitals.vehicle_connected),
+    );
+    w.gauge(
+        "contactor_closed",
+        "Whether the contactor is closed",
+        flag(vitals.contactor_closed),
+    );
+    w.gauge(
+        "session_seconds",
+        "Duration of the current session",
+        vitals.session_s as f64,
+    );
+    w.gauge(
+        "session_energy_joules",
+        "Energy delivered in the current session",
+        vitals.session_energy_wh * JOULES_PER_WH,
+    );
+    w.gauge(
+        "vehicle_current_amperes",
+        "Current drawn by the vehicle",
+        vitals.vehicle_current_a,
+    );
+    w.gauge("grid_volts", "Grid voltage", vitals.grid_v);
+    w.gauge("grid_hertz", "Grid frequency", vitals.grid_hz);
+    w.gauges(
+        "phase_current_amperes",
+        "Current per phase",
+        "phase",
+        &[
+            ("a", vitals.current_a_a),
+            ("b", vitals.current_b_a),
+            ("c", vitals.current_c_a),
+            ("n", vitals.current_n_a),
+        ],
+    );
+    w.gauges(
+        "phase_voltage_volts",
+        "Voltage per phase",
+        "phase",
+        &[
+            ("a", vitals.voltage_a_v),
+            ("b", vitals.voltage_b_v),
+            ("c", vitals.voltage_c_v),
+        ],
+    );
+    w.gauges(
+        "temperature_celsius",
+        "Temperature per sensor",
+        "sensor",
+        &[
+            ("pcba", vitals.pcba_temp_c),
+            ("handle", vitals.handle_temp_c),
+            ("mcu", vitals.mcu_temp_c),
+        ],
+    );
+    w.gauges(
+        "pilot_volts",
+        "Control pilot voltage",
+        "level",
+        &[("high", vitals.pilot_high_v), ("low", vitals.pilot_low_v)],
+    );
+    w.gauge("proximity_volts", "Proximity pin voltage", vitals.prox_v);
+    w.gauge(
+        "relay_coil_volts",
+        "Relay coil voltage",
+        vitals.relay_coil_v,
+    );
+    w.gauge(
+        "input_thermopile_microvolts",
+        "Input thermopile voltage",
+        vitals.input_thermopile_uv as f64,
+    );
+    w.gauge(
+        "uptime_seconds",
+        "Time since the last boot",
+        vitals.uptime_s as f64,
+    );
+    w.gauge(
+        "evse_state",
+        "Numeric EVSE state",
+        vitals.evse_state.code() as f64,
+    );
+    w.gauge(
+        "config_status",
+        "Numeric configuration status",
+        vitals.config_status.code() as f64,
+    );
+    w.gauge(
+        "current_alerts",
+        "Number of active alerts",
+        vitals.current_alerts.len() as f64,
+    );
+    w.gauge(
+        "not_ready_reasons",
+        "Number of reasons the EVSE is not ready",
+        vitals.evse_not_ready_reasons.len() as f64,
+    );
+
+    w.counter(
+        "contactor_cycles_total",
+        "Contactor cycles",
+        lifetime.contactor_cycles as f64,
+    );
+    w.counter(
+        "contactor_cycles_loaded_total",
+        "Contactor cycles under load",
+        lifetime.contactor_cycles_loaded as f64,
+    );
+    w.counter("alerts_total", "Alerts raised", lifetime.alert_count as f64);
+    w.counter(
+        "thermal_foldbacks_total",
+        "Thermal foldbacks",
+        lifetime.thermal_foldbacks as f64,
+    );
+    w.counter(
+        "charge_starts_total",
+        "Charges started",
+        lifetime.charge_starts as f64,
+    );
+    w.counter(
+        "energy_joules_total",
+        "Energy delivered",
+        lifetime.energy_wh as f64 * JOULES_PER_WH,
+    );
+    w.counter(
+        "connector_cycles_total",
+        "Connector plug cycles",
+        lifetime.connector_cycles as f64,
+    );
+    w.counter(
+        "lifetime_uptime_seconds_total",
+        "Total uptime",
+        lifetime.uptime_s as f64,
+    );
+    w.counter(
+        "charging_seconds_total",
+        "Total time spent charging",
+        lifetime.charging_time_s as f64,
+    );
+    w.gauge(
+        "avg_startup_temperature_celsius",
+        "Average temperature at charge start",
+        lifetime.avg_startup_temp,
+    );
+
+    w.gauge(
+        "wifi_connected",
+        "Whether Wi-Fi is connected",
+        flag(wifi.wifi_connected),
+    );
+    w.gauge(
+        "internet_connected",
+        "Whether the internet is reachable",
+        flag(wifi.internet),
+    );
+    w.gauge(
+        "wifi_signal_strength_percent",
+        "Wi-Fi signal strength",
+        wifi.wifi_signal_strength as f64,
+    );
+    w.gauge(
+        "wifi_rssi_decibel_milliwatts",
+        "Wi-Fi RSSI",
+        wifi.wifi_rssi as f64,
+    );
+    w.gauge(
+        "wifi_snr_decibels",
+        "Wi-Fi signal to noise ratio",
+        wifi.wifi_snr as f64,
+    );
+    w.out
+}
+
+/// Serves `/metrics`, fetching from the wall connector on each scrape or at
+/// most once per `cache` interval.
+pub struct Exporter {
+    client: WallConnectorClient,
+    cache: Duration,
+    version: Mutex<Option<Version>>,
+    cached: Mutex<Option<(Instant, String)>>,
+}
+
+impl Exporter {
+    /// `cache` of zero fetches on every scrape.
+    pub fn new(client: WallConnectorClient, cache: Duration) -> Self {
+        Exporter {
+            client,
+            cache,
+            version: Mutex::new(None),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Fetch everything and render the metrics document.
+    pub fn scrape(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let mut cached = self.cached.lock().unwrap();
+        if let Some((at, metrics)) = cached.as_ref()
+            && at.elapsed() < self.cache
+        {
+            return Ok(metrics.clone());
+        }
+
+        // The version never changes at runtime, fetch it until it succeeds
+        let mut version = self.version.lock().unwrap();
+        if version.is_none() {
+            *version = Some(self.client.version()?);
+        }
+        let version = version.as_ref().unwrap();
+
+        let fetched = (|| -> Result<_, Box<dyn std::error::Error>> {
+            Ok((
+                self.client.vitals()?,
+                self.client.lifetime()?,
+                self.client.wifi_status()?,
+            ))
+        })();
+        let metrics = match &fetched {
+            Ok((vitals, lifetime, wifi)) => render_metrics(version, Some((vitals, lifetime, wifi))),
+            Err(e) => {
+                log::warn!("scrape of {} failed: {}", self.client.addr(), e);
+                render_metrics(version, None)
+            }
+        };
+        if fetched.is_ok() {
+            *cached = Some((Instant::now(), metrics.clone()));
+        }
+        Ok(metrics)
+    }
+
+    fn handle(&self, request: &Request) -> Response {
+        match request.path.as_str() {
+            "/metrics" => match self.scrape() {
+                Ok(metrics) => Response::new(200, "text/plain; version=0.0.4", metrics),
+                Err(e) => Response::error(502, &format!("Error fetching version: {}", e)),
+            },
+            "/" => Response::new(
+                200,
+                "text/html; charset=utf-8",
+                "<html><body><a href=\"/metrics\">Metrics</a></body></html>\n",
+            ),
+            _ => Response::not_found(),
+        }
+    }
+
+    /// Serve the exporter on `listener` until the process exits.
+    pub fn serve(self, listener: TcpListener) -> std::io::Result<()> {
+        http::serve(listener, move |request| self.handle(request))
+    }
+}
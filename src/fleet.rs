@@ -0,0 +1,93 @@
+//! Several wall connectors polled together.
+//!
+//! Devices are given as a comma separated list of `ADDR` or `NAME=ADDR`
+//! entries, e.g. `home=192.168.1.221,garage=192.168.1.222`, or by the names
+//! of devices from the config file. Unnamed devices are named after their
+//! address.
+
+use std::collections::BTreeMap;
+
+use crate::client::WallConnectorClient;
+use crate::models::Vitals;
+
+/// A wall connector with the name it is shown under.
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub name: String,
+    pub client: WallConnectorClient,
+}
+
+impl Device {
+    pub fn new(name: &str, addr: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Device {
+            name: name.to_string(),
+            client: WallConnectorClient::new(addr)?,
+        })
+    }
+
+    /// Parse one `ADDR` or `NAME=ADDR` entry, an `ADDR` that is a key of
+    /// `named` is replaced by its value.
+    pub fn parse(
+        entry: &str,
+        named: &BTreeMap<String, String>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (name, addr) = match entry.split_once('=') {
+            Some((name, addr)) => (name.trim(), addr.trim()),
+            None => {
+                let entry = entry.trim();
+                (entry, named.get(entry).map_or(entry, String::as_str))
+            }
+        };
+        if name.is_empty() || addr.is_empty() {
+            return Err(format!("invalid device '{}', expected ADDR or NAME=ADDR", entry).into());
+        }
+        Self::new(name, addr)
+    }
+}
+
+/// Parse a comma separated list of devices, names must be unique. Entries
+/// naming a device of `named` resolve to its address.
+pub fn parse_devices(
+    list: &str,
+    named: &BTreeMap<String, String>,
+) -> Result<Vec<Device>, Box<dyn std::error::Error>> {
+    let mut devices: Vec<Device> = Vec::new();
+    for entry in list.split(',') {
+        let device = Device::parse(entry, named)?;
+        if devices.iter().any(|d| d.name == device.name) {
+            return Err(format!("device '{}' given more than once", device.name).into());
+        }
+        devices.push(device);
+    }
+    Ok(devices)
+}
+
+/// Latest vitals of a device, or why fetching them failed.
+#[derive(Debug, Clone)]
+pub struct DeviceVitals {
+    pub name: String,
+    pub addr: String,
+    pub vitals: Result<Vitals, String>,
+}
+
+/// Fetch the vitals of all `devices` concurrently, results are in the order
+/// of `devices`.
+pub fn poll_vitals(devices: &[Device]) -> Vec<DeviceVitals> {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = devices
+            .iter()
+            .map(|device| scope.spawn(move || device.client.vitals().map_err(|e| e.to_string())))
+            .collect();
+        devices
+            .iter()
+            .zip(handles)
+            .map(|(device, handle)| DeviceVitals {
+                name: device.name.clone(),
+                addr: device.client.addr().to_string(),
+                vitals: handle
+                    .join()
+                    .unwrap_or_else(|_| Err("polling thread panicked".to_string())),
+            })
+            .collect()
+    })
+}
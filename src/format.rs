@@ -0,0 +1,541 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use time::format_description::BorrowedFormatItem;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime};
+
+use crate::alert::AlertTracker;
+use crate::cost::{Cost, CostTracker, SessionCost};
+use crate::discover::Found;
+use crate::fleet::DeviceVitals;
+use crate::logfile::Payload;
+use crate::models::{Lifetime, Version, Vitals, WifiStatus};
+use crate::session::{Session, SessionEnd};
+use crate::tariff::Tariff;
+
+/// Units values are displayed in, the wall connector itself reports metric.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    #[default]
+    Metric,
+    /// Temperatures in °F.
+    Imperial,
+}
+
+impl Units {
+    /// `celsius` in the temperature scale of the units.
+    pub fn temperature(self, celsius: f64) -> f64 {
+        match self {
+            Units::Metric => celsius,
+            Units::Imperial => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    pub fn temperature_symbol(self) -> &'static str {
+        match self {
+            Units::Metric => "°C",
+            Units::Imperial => "°F",
+        }
+    }
+
+    /// `celsius` converted and formatted with one decimal, e.g. `19.5°C`.
+    pub fn format_temperature(self, celsius: f64) -> String {
+        format!(
+            "{:.1}{}",
+            self.temperature(celsius),
+            self.temperature_symbol()
+        )
+    }
+}
+
+impl FromStr for Units {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "metric" => Ok(Units::Metric),
+            "imperial" => Ok(Units::Imperial),
+            _ => Err(format!(
+                "unknown units '{}', expected metric or imperial",
+                s
+            )),
+        }
+    }
+}
+
+/// Decode the base64 encoded SSID reported by the wall connector,
+/// falling back to the raw value if it isn't valid base64/UTF-8.
+pub fn decode_ssid(encoded: &str) -> String {
+    STANDARD
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .unwrap_or_else(|| encoded.to_string())
+}
+
+const TIMESTAMP: &[BorrowedFormatItem<'_>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+const TIME_OF_DAY: &[BorrowedFormatItem<'_>] = format_description!("[hour]:[minute]:[second]");
+
+/// `YYYY-MM-DD HH:MM:SS` in the timestamp's own offset.
+pub fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp.format(TIMESTAMP).unwrap_or_default()
+}
+
+/// `HH:MM:SS` in the timestamp's own offset.
+pub fn format_time_of_day(timestamp: OffsetDateTime) -> String {
+    timestamp.format(TIME_OF_DAY).unwrap_or_default()
+}
+
+pub fn format_duration(seconds: u64) -> String {
+    let days = seconds / 86400;
+    let hours = (seconds % 86400) / 3600;
+    let mins = (seconds % 3600) / 60;
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, mins)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, mins)
+    } else {
+        format!("{}m", mins)
+    }
+}
+
+pub fn format_version(version: &Version) -> String {
+    [
+        "Tesla Wall Connector Version Info:".to_string(),
+        format!("  Firmware Version: {}", version.firmware_version),
+        format!("  Git Branch:       {}", version.git_branch),
+        format!("  Part Number:      {}", version.part_number),
+        format!("  Serial Number:    {}", version.serial_number),
+        format!(
+            "  Web Service:      {}",
+            version.web_service.as_deref().unwrap_or("none")
+        ),
+    ]
+    .join("\n")
+}
+
+pub fn format_wifi_status(status: &WifiStatus) -> String {
+    [
+        "Tesla Wall Connector WiFi Status:".to_string(),
+        format!("  SSID:            {}", decode_ssid(&status.wifi_ssid)),
+        format!("  Connected:       {}", status.wifi_connected),
+        format!("  Signal Strength: {}%", status.wifi_signal_strength),
+        format!("  RSSI:            {} dBm", status.wifi_rssi),
+        format!("  SNR:             {} dB", status.wifi_snr),
+        format!("  IP Address:      {}", status.wifi_infra_ip),
+        format!("  Internet:        {}", status.internet),
+        format!("  MAC Address:     {}", status.wifi_mac),
+    ]
+    .join("\n")
+}
+
+pub fn format_lifetime(lifetime: &Lifetime, units: Units) -> String {
+    [
+        "Tesla Wall Connector Lifetime Stats:".to_string(),
+        format!("  Charge Starts:      {}", lifetime.charge_starts),
+        format!(
+            "  Energy Delivered:   {:.2} kWh",
+            lifetime.energy_wh as f64 / 1000.0
+        ),
+        format!(
+            "  Charging Time:      {}",
+            format_duration(lifetime.charging_time_s)
+        ),
+        format!(
+            "  Uptime:             {}",
+            format_duration(lifetime.uptime_s)
+        ),
+        format!("  Contactor Cycles:   {}", lifetime.contactor_cycles),
+        format!("  Loaded Cycles:      {}", lifetime.contactor_cycles_loaded),
+        format!("  Connector Cycles:   {}", lifetime.connector_cycles),
+        format!("  Thermal Foldbacks:  {}", lifetime.thermal_foldbacks),
+        format!("  Alert Count:        {}", lifetime.alert_count),
+        format!(
+            "  Avg Startup Temp:   {}",
+            units.format_temperature(lifetime.avg_startup_temp)
+        ),
+    ]
+    .join("\n")
+}
+
+pub fn format_vitals(vitals: &Vitals, units: Units) -> String {
+    let mut lines = vec![
+        "Tesla Wall Connector Vitals:".to_string(),
+        format!("  Vehicle Connected:  {}", vitals.vehicle_connected),
+        format!("  Contactor Closed:   {}", vitals.contactor_closed),
+        format!(
+            "  Session Duration:   {}",
+            format_duration(vitals.session_s)
+        ),
+        format!(
+            "  Session Energy:     {:.3} kWh",
+            vitals.session_energy_wh / 1000.0
+        ),
+        format!("  Vehicle Current:    {:.1} A", vitals.vehicle_current_a),
+        String::new(),
+        format!("  Grid Voltage:       {:.1} V", vitals.grid_v),
+        format!("  Grid Frequency:     {:.3} Hz", vitals.grid_hz),
+        format!(
+            "  Phase Currents:     A={:.1} B={:.1} C={:.1} N={:.1} A",
+            vitals.current_a_a, vitals.current_b_a, vitals.current_c_a, vitals.current_n_a
+        ),
+        format!(
+            "  Phase Voltages:     A={:.1} B={:.1} C={:.1} V",
+            vitals.voltage_a_v, vitals.voltage_b_v, vitals.voltage_c_v
+        ),
+        String::new(),
+        format!(
+            "  PCBA Temp:          {}",
+            units.format_temperature(vitals.pcba_temp_c)
+        ),
+        format!(
+            "  Handle Temp:        {}",
+            units.format_temperature(vitals.handle_temp_c)
+        ),
+        format!(
+            "  MCU Temp:           {}",
+            units.format_temperature(vitals.mcu_temp_c)
+        ),
+        String::new(),
+        format!(
+            "  Pilot High/Low:     {:.1} / {:.1} V",
+            vitals.pilot_high_v, vitals.pilot_low_v
+        ),
+        format!("  Proximity:          {:.1} V", vitals.prox_v),
+        format!("  Relay Coil:         {:.1} V", vitals.relay_coil_v),
+        format!("  Thermopile:         {} uV", vitals.input_thermopile_uv),
+        String::new(),
+        format!("  Uptime:             {}", format_duration(vitals.uptime_s)),
+        format!("  EVSE State:         {}", vitals.evse_state),
+        format!("  Config Status:      {}", vitals.config_status),
+    ];
+    for (i, alert) in vitals.current_alerts.iter().enumerate() {
+        let label = if i == 0 { "Current Alerts:" } else { "" };
+        lines.push(format!("  {:<20}{}", label, alert));
+    }
+    if !vitals.evse_not_ready_reasons.is_empty() {
+        let reasons: Vec<String> = vitals
+            .evse_not_ready_reasons
+            .iter()
+            .map(|reason| reason.to_string())
+            .collect();
+        lines.push(format!("  Not Ready Reasons:  {}", reasons.join(", ")));
+    }
+    lines.join("\n")
+}
+
+pub fn format_payload(payload: &Payload, units: Units) -> String {
+    match payload {
+        Payload::Vitals(vitals) => format_vitals(vitals, units),
+        Payload::Lifetime(lifetime) => format_lifetime(lifetime, units),
+        Payload::Version(version) => format_version(version),
+        Payload::WifiStatus(status) => format_wifi_status(status),
+    }
+}
+
+/// Report of the alerts followed by `tracker`, `vitals` being the latest
+/// sample.
+pub fn format_alerts(tracker: &AlertTracker, vitals: &Vitals, alert_count: u32) -> String {
+    let mut lines = vec![
+        "Tesla Wall Connector Alerts:".to_string(),
+        format!(
+            "  Lifetime Count:     {} (+{} since start)",
+            alert_count,
+            tracker.count_delta()
+        ),
+    ];
+    if vitals.current_alerts.is_empty() {
+        lines.push("  Current Alerts:     none".to_string());
+    }
+    for (i, alert) in vitals.current_alerts.iter().enumerate() {
+        let label = if i == 0 { "Current Alerts:" } else { "" };
+        lines.push(format!("  {:<20}{}", label, alert));
+    }
+    let raised: Vec<_> = tracker
+        .alerts()
+        .iter()
+        .filter(|t| t.occurrences > 0)
+        .collect();
+    if !raised.is_empty() {
+        lines.push(String::new());
+        lines.push("  Raised Since Start:".to_string());
+        for tracked in raised {
+            lines.push(format!(
+                "    {}x {}{}",
+                tracked.occurrences,
+                tracked.alert,
+                if tracked.active { " (active)" } else { "" }
+            ));
+        }
+    }
+    if tracker.unobserved() > 0 {
+        lines.push(format!(
+            "  Unobserved:         {} (raised and cleared between polls)",
+            tracker.unobserved()
+        ));
+    }
+    lines.join("\n")
+}
+
+fn format_hms(seconds: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Table of sessions, one per line.
+pub fn format_sessions(sessions: &[Session], units: Units) -> String {
+    let time_of_day = |t: Option<OffsetDateTime>| {
+        t.and_then(|t| t.format(TIME_OF_DAY).ok())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let mut lines = vec![format!(
+        "{:<19}  {:<8}  {:<8}  {:<8}  {:>8}  {:>10}  {:>6}  {:>6}  {:>7}  {}",
+        "Plugged In",
+        "Closed",
+        "Opened",
+        "Ended",
+        "Charging",
+        "Energy",
+        "Peak",
+        "Avg",
+        "Handle",
+        "End"
+    )];
+    for session in sessions {
+        let end = match session.end {
+            SessionEnd::Unplugged => "unplugged",
+            SessionEnd::Restarted => "restarted",
+            SessionEnd::Reboot => "reboot",
+            SessionEnd::InProgress => "in progress",
+        };
+        lines.push(format!(
+            "{:<19}  {:<8}  {:<8}  {:<8}  {:>8}  {:>6.3} kWh  {:>4.1} A  {:>4.1} A  {:>7}  {}",
+            format_timestamp(session.plugged_in),
+            time_of_day(session.contactor_closed),
+            time_of_day(session.contactor_opened),
+            time_of_day(Some(session.ended)),
+            format_hms(session.charging_s),
+            session.energy_wh / 1000.0,
+            session.peak_current_a,
+            session.avg_current_a,
+            units.format_temperature(session.peak_handle_temp_c),
+            end
+        ));
+    }
+    lines.join("\n")
+}
+
+/// One summary row per device, numbered for drill-down.
+pub fn format_overview(devices: &[DeviceVitals], units: Units) -> String {
+    let mut lines = vec![format!(
+        "{:>2}  {:<12}  {:<16}  {:<9}  {:<18}  {:>7}  {:>10}  {:>7}  {}",
+        "#", "Name", "Address", "Connected", "State", "Current", "Energy", "Handle", "Errors"
+    )];
+    for (i, device) in devices.iter().enumerate() {
+        let row = match &device.vitals {
+            Ok(vitals) => {
+                let alerts: Vec<String> = vitals
+                    .current_alerts
+                    .iter()
+                    .map(|alert| alert.to_string())
+                    .collect();
+                format!(
+                    "{:<9}  {:<18}  {:>5.1} A  {:>6.3} kWh  {:>7}  {}",
+                    if vitals.vehicle_connected {
+                        "yes"
+                    } else {
+                        "no"
+                    },
+                    vitals.evse_state.description(),
+                    vitals.vehicle_current_a,
+                    vitals.session_energy_wh / 1000.0,
+                    units.format_temperature(vitals.handle_temp_c),
+                    if alerts.is_empty() {
+                        "-".to_string()
+                    } else {
+                        alerts.join(", ")
+                    }
+                )
+            }
+            Err(e) => format!(
+                "{:<9}  {:<18}  {:>7}  {:>10}  {:>7}  {}",
+                "-", "-", "-", "-", "-", e
+            ),
+        };
+        lines.push(format!(
+            "{:>2}  {:<12}  {:<16}  {}",
+            i + 1,
+            device.name,
+            device.addr,
+            row
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Table of the wall connectors found by a scan.
+pub fn format_discovered(found: &[Found]) -> String {
+    let mut lines = vec![format!(
+        "{:<21}  {:<14}  {:<14}  {}",
+        "Address", "Serial Number", "Part Number", "Firmware"
+    )];
+    for unit in found {
+        lines.push(format!(
+            "{:<21}  {:<14}  {:<14}  {}",
+            unit.addr(),
+            unit.version.serial_number,
+            unit.version.part_number,
+            unit.version.firmware_version
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Energy and cost columns of `cost`, a column per name of the tariff when
+/// it has periods.
+fn cost_columns(cost: &Cost, tariff: &Tariff) -> String {
+    let mut columns = format!("{:>10.3}", cost.energy_wh / 1000.0);
+    if !tariff.periods.is_empty() {
+        for name in tariff.names() {
+            let energy_wh = cost.period(name).map_or(0.0, |period| period.energy_wh);
+            columns.push_str(&format!(
+                "  {:>w$.3}",
+                energy_wh / 1000.0,
+                w = name.len().max(8)
+            ));
+        }
+    }
+    columns.push_str(&format!("  {:>10}", tariff.format_amount(cost.cost)));
+    columns
+}
+
+fn cost_headers(tariff: &Tariff) -> String {
+    let mut headers = format!("{:>10}", "kWh");
+    if !tariff.periods.is_empty() {
+        for name in tariff.names() {
+            headers.push_str(&format!("  {:>w$}", name, w = name.len().max(8)));
+        }
+    }
+    headers.push_str(&format!("  {:>10}", "Cost"));
+    headers
+}
+
+/// Table of sessions with their cost, one per line, and the total. Times are
+/// in the offset of the tariff.
+pub fn format_session_costs(sessions: &[SessionCost], tariff: &Tariff) -> String {
+    let mut lines = vec![format!(
+        "{:<19}  {:<8}  {:>8}  {}",
+        "Plugged In",
+        "Ended",
+        "Charging",
+        cost_headers(tariff)
+    )];
+    let offset = tariff.offset();
+    let mut total = Cost::default();
+    for SessionCost { session, cost } in sessions {
+        lines.push(format!(
+            "{:<19}  {:<8}  {:>8}  {}",
+            format_timestamp(session.plugged_in.to_offset(offset)),
+            format_time_of_day(session.ended.to_offset(offset)),
+            format_hms(session.charging_s),
+            cost_columns(cost, tariff)
+        ));
+        add_cost(&mut total, cost);
+    }
+    lines.push(format!(
+        "{:<19}  {:<8}  {:>8}  {}",
+        "Total",
+        "",
+        "",
+        cost_columns(&total, tariff)
+    ));
+    lines.join("\n")
+}
+
+/// Table of the cost by day or, with `monthly`, by month and the total.
+pub fn format_cost_summary(costs: &BTreeMap<Date, Cost>, tariff: &Tariff, monthly: bool) -> String {
+    let label = if monthly { "Month" } else { "Day" };
+    let mut lines = vec![format!("{:<10}  {}", label, cost_headers(tariff))];
+    let mut total = Cost::default();
+    for (date, cost) in costs {
+        let date = if monthly {
+            format!("{}-{:02}", date.year(), u8::from(date.month()))
+        } else {
+            date.to_string()
+        };
+        lines.push(format!("{:<10}  {}", date, cost_columns(cost, tariff)));
+        add_cost(&mut total, cost);
+    }
+    lines.push(format!("{:<10}  {}", "Total", cost_columns(&total, tariff)));
+    lines.join("\n")
+}
+
+fn add_cost(total: &mut Cost, cost: &Cost) {
+    total.energy_wh += cost.energy_wh;
+    total.cost += cost.cost;
+    for period in &cost.periods {
+        match total.periods.iter_mut().find(|p| p.name == period.name) {
+            Some(sum) => {
+                sum.energy_wh += period.energy_wh;
+                sum.cost += period.cost;
+            }
+            None => total.periods.push(period.clone()),
+        }
+    }
+}
+
+/// Running cost of the live `cost` command at `now`.
+pub fn format_live_cost(
+    tracker: &CostTracker,
+    completed: &[SessionCost],
+    now: OffsetDateTime,
+) -> String {
+    let tariff = tracker.tariff();
+    let today = now.to_offset(tariff.offset()).date();
+    let month = today.replace_day(1).unwrap();
+    let line = |label: &str, cost: Option<&Cost>| {
+        let Some(cost) = cost else {
+            return format!("  {:<17}-", label);
+        };
+        let mut text = format!(
+            "  {:<17}{:.3} kWh  {}",
+            label,
+            cost.energy_wh / 1000.0,
+            tariff.format_amount(cost.cost)
+        );
+        if !tariff.periods.is_empty() {
+            let split: Vec<String> = cost
+                .periods
+                .iter()
+                .map(|p| format!("{} {:.3} kWh", p.name, p.energy_wh / 1000.0))
+                .collect();
+            text.push_str(&format!("  ({})", split.join(", ")));
+        }
+        text
+    };
+    let current = tracker.current();
+    let mut sessions = Cost::default();
+    for session in completed {
+        add_cost(&mut sessions, &session.cost);
+    }
+    [
+        "Tesla Wall Connector Charging Cost:".to_string(),
+        format!("  {:<17}{}", "Tariff:", tariff),
+        line("Current Session:", current.as_ref().map(|c| &c.cost)),
+        line("Today:", tracker.days().get(&today)),
+        line("This Month:", tracker.months().get(&month)),
+        line(
+            &format!("Completed ({}):", completed.len()),
+            (!completed.is_empty()).then_some(&sessions),
+        ),
+    ]
+    .join("\n")
+}
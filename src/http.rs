@@ -0,0 +1,200 @@
+//! Minimal HTTP/1.1 server for the modes that serve data themselves.
+//!
+//! Each connection is handled on its own thread and answers a single
+//! request, which is all a scraper or a browser polling a JSON endpoint
+//! needs. Event streams keep their connection for as long as the browser
+//! listens.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+/// Largest request head we accept, requests are only ever a GET line and a
+/// few headers.
+const MAX_HEAD: usize = 16 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// Path without the query string.
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Value of the header `name`, compared case insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Response {
+            status,
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    pub fn text(body: impl Into<Vec<u8>>) -> Self {
+        Self::new(200, "text/plain; charset=utf-8", body)
+    }
+
+    pub fn json(body: impl Into<Vec<u8>>) -> Self {
+        Self::new(200, "application/json", body)
+    }
+
+    pub fn not_found() -> Self {
+        Self::new(404, "text/plain; charset=utf-8", "Not Found\n")
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::new(
+            status,
+            "text/plain; charset=utf-8",
+            format!("{}\n", message),
+        )
+    }
+
+    /// Write the response to `stream`, closing the connection afterwards.
+    pub fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+/// Standard reason phrase of the status codes we use.
+pub fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+/// Read the request line and headers from `stream`.
+pub fn read_request(stream: &TcpStream) -> io::Result<Request> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let mut reader = BufReader::new(stream).take(MAX_HEAD as u64);
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or_else(|| invalid("empty request"))?;
+    let target = parts
+        .next()
+        .ok_or_else(|| invalid("missing request target"))?;
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+    let method = method.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    Ok(Request {
+        method,
+        path,
+        query,
+        headers,
+    })
+}
+
+/// Accept connections on `listener` forever, answering each request with
+/// `handler` on a thread of its own.
+pub fn serve<H>(listener: TcpListener, handler: H) -> io::Result<()>
+where
+    H: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    serve_connections(listener, move |request, mut stream| {
+        // The client may already be gone, nothing to do about it
+        let _ = handler(&request).write_to(&mut stream);
+    })
+}
+
+/// Like [`serve`], but `handler` gets the connection to answer on itself,
+/// e.g. to keep it open for an event stream.
+pub fn serve_connections<H>(listener: TcpListener, handler: H) -> io::Result<()>
+where
+    H: Fn(Request, TcpStream) + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("accept failed: {}", e);
+                continue;
+            }
+        };
+        let handler = Arc::clone(&handler);
+        std::thread::spawn(move || {
+            let response = match read_request(&stream) {
+                Ok(request) if request.method == "GET" => return handler(request, stream),
+                Ok(_) => Response::error(405, "Method Not Allowed"),
+                Err(_) => Response::error(400, "Bad Request"),
+            };
+            let _ = response.write_to(&mut stream);
+        });
+    }
+    Ok(())
+}
+
+/// Start a Server-Sent Events stream on `stream`, the events follow with
+/// [`write_event`] until the connection is closed.
+pub fn write_event_stream_head(stream: &mut impl Write) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )?;
+    stream.flush()
+}
+
+/// Send the event `event` with `data`, which must not contain newlines, or
+/// a comment line to keep the connection alive if `event` is empty.
+pub fn write_event(stream: &mut impl Write, event: &str, data: &str) -> io::Result<()> {
+    if event.is_empty() {
+        write!(stream, ": {}\n\n", data)?;
+    } else {
+        write!(stream, "event: {}\ndata: {}\n\n", event, data)?;
+    }
+    stream.flush()
+}
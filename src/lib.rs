@@ -0,0 +1,48 @@
+//! Client library for the local HTTP API of a Tesla Wall Connector (Gen 3).
+//!
+//! ```no_run
+//! use tesla_wallcon_monitor::WallConnectorClient;
+//!
+//! let client = WallConnectorClient::new("192.168.1.221")?;
+//! let vitals = client.vitals()?;
+//! let units = tesla_wallcon_monitor::format::Units::Metric;
+//! println!("{}", tesla_wallcon_monitor::format::format_vitals(&vitals, units));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod alert;
+#[cfg(feature = "async")]
+pub mod async_client;
+pub mod client;
+pub mod config;
+pub mod cost;
+#[cfg(unix)]
+pub mod daemon;
+pub mod dashboard;
+pub mod discover;
+pub mod error;
+pub mod evse;
+pub mod exporter;
+pub mod fleet;
+pub mod format;
+pub mod http;
+pub mod logfile;
+pub mod models;
+pub mod mqtt;
+pub mod output;
+pub mod retry;
+pub mod rules;
+pub mod session;
+pub mod simulator;
+pub mod store;
+pub mod systemd;
+pub mod tariff;
+pub mod term;
+pub mod web;
+pub mod webhook;
+
+#[cfg(feature = "async")]
+pub use async_client::AsyncWallConnectorClient;
+pub use client::WallConnectorClient;
+pub use error::Error;
+pub use models::{Lifetime, Version, Vitals, WifiStatus};
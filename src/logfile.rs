@@ -0,0 +1,398 @@
+//! The files written with `--log` and reading them back.
+//!
+//! Each response is logged on one line, by default as text
+//!
+//! ```text
+//! 2025-12-21T04:39:18.123456789Z [INFO] vitals: {"contactor_closed":false,...}
+//! ```
+//!
+//! or with `--log-format jsonl` as a JSON object, see [`JsonLine`]:
+//!
+//! ```text
+//! {"timestamp":"2025-12-21T04:39:18.123456789Z","addr":"192.168.1.221","endpoint":"vitals","status":200,"latency_ms":42.137,"payload":{"contactor_closed":false,...}}
+//! ```
+//!
+//! Responses with an error status are logged too, as a `[WARN]` line with
+//! the status or with their `status` in JSON Lines, and skipped when reading
+//! back. Other messages, e.g. warnings, are
+//! `{"timestamp":...,"level":"WARN","message":...}` in JSON Lines. Files in
+//! either format, or a mix of both, read back the same.
+
+use log::kv::Key;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::models::{Lifetime, Version, Vitals, WifiStatus};
+
+/// Parsed response of one of the `/api/1` endpoints.
+#[derive(Debug, Clone)]
+pub enum Payload {
+    Vitals(Vitals),
+    Lifetime(Lifetime),
+    Version(Version),
+    WifiStatus(WifiStatus),
+}
+
+impl Payload {
+    /// Parse the payload of `endpoint` from a JSON value.
+    pub fn from_value(endpoint: &str, value: Value) -> Result<Self, String> {
+        let payload = match endpoint {
+            "vitals" => serde_json::from_value(value).map(Payload::Vitals),
+            "lifetime" => serde_json::from_value(value).map(Payload::Lifetime),
+            "version" => serde_json::from_value(value).map(Payload::Version),
+            "wifi_status" => serde_json::from_value(value).map(Payload::WifiStatus),
+            _ => return Err(format!("unknown endpoint '{}'", endpoint)),
+        };
+        payload.map_err(|e| format!("invalid {} payload: {}", endpoint, e))
+    }
+
+    /// Parse the JSON body returned by `endpoint`.
+    pub fn parse(endpoint: &str, json: &str) -> Result<Self, String> {
+        let value = serde_json::from_str(json)
+            .map_err(|e| format!("invalid {} payload: {}", endpoint, e))?;
+        Self::from_value(endpoint, value)
+    }
+
+    /// Name of the endpoint the payload came from.
+    pub fn endpoint(&self) -> &'static str {
+        match self {
+            Payload::Vitals(_) => "vitals",
+            Payload::Lifetime(_) => "lifetime",
+            Payload::Version(_) => "version",
+            Payload::WifiStatus(_) => "wifi_status",
+        }
+    }
+
+    /// The payload as a JSON object in API field order.
+    pub fn to_value(&self) -> Value {
+        let value = match self {
+            Payload::Vitals(vitals) => serde_json::to_value(vitals),
+            Payload::Lifetime(lifetime) => serde_json::to_value(lifetime),
+            Payload::Version(version) => serde_json::to_value(version),
+            Payload::WifiStatus(status) => serde_json::to_value(status),
+        };
+        value.expect("payloads serialize to JSON")
+    }
+
+    /// The payload as the wall connector sends it, alerts in their raw form
+    /// rather than decoded.
+    pub fn to_raw_value(&self) -> Value {
+        let mut value = self.to_value();
+        if let Payload::Vitals(vitals) = self {
+            value["current_alerts"] = vitals
+                .current_alerts
+                .iter()
+                .map(|alert| alert.raw.clone())
+                .collect();
+        }
+        value
+    }
+}
+
+impl From<Vitals> for Payload {
+    fn from(vitals: Vitals) -> Self {
+        Payload::Vitals(vitals)
+    }
+}
+
+impl From<Lifetime> for Payload {
+    fn from(lifetime: Lifetime) -> Self {
+        Payload::Lifetime(lifetime)
+    }
+}
+
+impl From<Version> for Payload {
+    fn from(version: Version) -> Self {
+        Payload::Version(version)
+    }
+}
+
+impl From<WifiStatus> for Payload {
+    fn from(status: WifiStatus) -> Self {
+        Payload::WifiStatus(status)
+    }
+}
+
+/// One line of a log file.
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub timestamp: OffsetDateTime,
+    pub payload: Payload,
+}
+
+/// A line that couldn't be parsed, `line` is 1 based.
+#[derive(Debug, Clone)]
+pub struct SkippedLine {
+    pub line: usize,
+    pub reason: String,
+}
+
+/// Result of reading a whole log file.
+#[derive(Debug, Default)]
+pub struct LogFile {
+    pub records: Vec<LogRecord>,
+    pub skipped: Vec<SkippedLine>,
+}
+
+impl LogFile {
+    /// Parse every line of `text`, collecting the ones that don't parse in
+    /// [`LogFile::skipped`]. Blank lines are ignored.
+    pub fn parse(text: &str) -> Self {
+        let mut log = LogFile::default();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_line(line) {
+                Ok(record) => log.records.push(record),
+                Err(reason) => log.skipped.push(SkippedLine {
+                    line: i + 1,
+                    reason,
+                }),
+            }
+        }
+        log
+    }
+
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    /// The vitals records only, in file order.
+    pub fn vitals(&self) -> impl Iterator<Item = (OffsetDateTime, &Vitals)> {
+        self.records.iter().filter_map(|r| match &r.payload {
+            Payload::Vitals(vitals) => Some((r.timestamp, vitals)),
+            _ => None,
+        })
+    }
+}
+
+/// Parse a single line of either format.
+pub fn parse_line(line: &str) -> Result<LogRecord, String> {
+    if line.trim_start().starts_with('{') {
+        return parse_json_line(line);
+    }
+    parse_text_line(line)
+}
+
+/// Parse a single `<rfc3339> [LEVEL] <endpoint>: <json>` line.
+fn parse_text_line(line: &str) -> Result<LogRecord, String> {
+    let (timestamp, rest) = line
+        .split_once(' ')
+        .ok_or_else(|| "missing timestamp".to_string())?;
+    let timestamp = OffsetDateTime::parse(timestamp, &Rfc3339)
+        .map_err(|e| format!("invalid timestamp '{}': {}", timestamp, e))?;
+    let rest = rest.trim_start();
+    let rest = match rest.strip_prefix('[') {
+        Some(level) => {
+            let (level, rest) = level
+                .split_once(']')
+                .ok_or_else(|| "unterminated log level".to_string())?;
+            // Responses are logged at info level, failed ones and other
+            // messages at higher levels
+            if level.trim() != "INFO" {
+                return Err(format!("{} message", level.trim()));
+            }
+            rest.trim_start()
+        }
+        None => rest,
+    };
+    let (endpoint, json) = rest
+        .split_once(": ")
+        .ok_or_else(|| "missing endpoint".to_string())?;
+    let payload = Payload::parse(endpoint, json)?;
+    Ok(LogRecord { timestamp, payload })
+}
+
+/// Write `record` as a line [`parse_line`] reads back, without the newline.
+/// The payload is written as the wall connector sent it, like `--log` does.
+pub fn format_line(record: &LogRecord) -> String {
+    format!(
+        "{} [INFO] {}: {}",
+        record.timestamp.format(&Rfc3339).unwrap_or_default(),
+        record.payload.endpoint(),
+        record.payload.to_raw_value()
+    )
+}
+
+/// Parse a single [`JsonLine`].
+fn parse_json_line(line: &str) -> Result<LogRecord, String> {
+    let value: Value = serde_json::from_str(line).map_err(|e| format!("invalid JSON: {}", e))?;
+    // Messages other than responses, e.g. warnings
+    if value.get("endpoint").is_none() {
+        return Err("not a response".to_string());
+    }
+    let line: JsonLine =
+        serde_json::from_value(value).map_err(|e| format!("invalid JSON line: {}", e))?;
+    if !is_success(line.status) {
+        return Err(format!("HTTP status {}", line.status));
+    }
+    Ok(LogRecord {
+        timestamp: line.timestamp,
+        payload: Payload::from_value(&line.endpoint, line.payload)?,
+    })
+}
+
+/// Format of the file written with `--log`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// `<rfc3339> [INFO] <endpoint>: <json>` lines, as written by earlier
+    /// versions.
+    #[default]
+    Text,
+    /// One [`JsonLine`] per response.
+    Jsonl,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "jsonl" => Ok(LogFormat::Jsonl),
+            _ => Err(format!(
+                "unknown log format '{}', expected text or jsonl",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogFormat::Text => "text",
+            LogFormat::Jsonl => "jsonl",
+        })
+    }
+}
+
+/// A response as logged with `--log-format jsonl`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonLine {
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    /// Address of the wall connector, as given to the client.
+    #[serde(default)]
+    pub addr: String,
+    pub endpoint: String,
+    /// HTTP status of the response.
+    #[serde(default)]
+    pub status: u16,
+    /// Time from sending the request to the end of the body.
+    #[serde(default)]
+    pub latency_ms: f64,
+    /// The body, a string if it isn't JSON.
+    pub payload: Value,
+}
+
+/// Whether `status` is a 2xx success, 0 stands for unknown in old lines.
+fn is_success(status: u16) -> bool {
+    status == 0 || (200..300).contains(&status)
+}
+
+/// Log the `body` of a response of `endpoint` with the details
+/// [`JsonLogger`] writes as key-values. Successful responses are logged at
+/// info level as `<endpoint>: <body>`, others at warn level with the status.
+pub fn log_response(addr: &str, endpoint: &str, status: u16, latency: Duration, body: &str) {
+    let (level, message) = if is_success(status) {
+        (Level::Info, format!("{}: {}", endpoint, body))
+    } else {
+        (
+            Level::Warn,
+            format!("{}: HTTP {}: {}", endpoint, status, body),
+        )
+    };
+    log::log!(
+        level,
+        addr = addr,
+        endpoint = endpoint,
+        status = status,
+        latency_ms = latency.as_micros() as f64 / 1000.0,
+        body = body;
+        "{}",
+        message
+    );
+}
+
+/// `log` backend writing one JSON object per line, responses logged with
+/// [`log_response`] as [`JsonLine`]s.
+pub struct JsonLogger {
+    level: LevelFilter,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLogger {
+    pub fn new(level: LevelFilter, out: impl Write + Send + 'static) -> Self {
+        JsonLogger {
+            level,
+            out: Mutex::new(Box::new(out)),
+        }
+    }
+
+    /// Install as the global logger.
+    pub fn init(self) -> Result<(), log::SetLoggerError> {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+
+    fn to_line(record: &Record) -> Value {
+        let timestamp = OffsetDateTime::now_utc();
+        let kv = record.key_values();
+        let value = |key| kv.get(Key::from(key));
+        if let (Some(endpoint), Some(body)) = (value("endpoint"), value("body")) {
+            let body = body.to_string();
+            let line = JsonLine {
+                timestamp,
+                addr: value("addr")
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_default(),
+                endpoint: endpoint.to_string(),
+                status: value("status")
+                    .and_then(|status| status.to_u64())
+                    .unwrap_or_default() as u16,
+                latency_ms: value("latency_ms")
+                    .and_then(|latency| latency.to_f64())
+                    .unwrap_or_default(),
+                payload: serde_json::from_str(&body).unwrap_or(Value::String(body)),
+            };
+            return serde_json::to_value(line).expect("log lines serialize to JSON");
+        }
+        serde_json::json!({
+            "timestamp": timestamp.format(&Rfc3339).unwrap_or_default(),
+            "level": record.level().as_str(),
+            "message": record.args().to_string(),
+        })
+    }
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = Self::to_line(record);
+        let mut out = self.out.lock().unwrap();
+        let _ = writeln!(out, "{}", line);
+        let _ = out.flush();
+    }
+
+    fn flush(&self) {
+        let _ = self.out.lock().unwrap().flush();
+    }
+}
@@ -1,29 +1,92 @@
-use base64::{Engine, engine::general_purpose::STANDARD};
-use clap::{CommandFactory, FromArgMatches, Parser};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 use crossterm::{
     cursor::MoveTo,
-    event::{self, Event, KeyCode, KeyModifiers},
+    event::{KeyCode, KeyEvent},
     execute,
+    style::Stylize,
     terminal::{self, Clear, ClearType},
 };
-use log::info;
-use serde::{Deserialize, Serialize};
 use simplelog::{ConfigBuilder, LevelFilter, WriteLogger};
+use std::cell::Cell;
 use std::fs::OpenOptions;
 use std::io::{Write, stdout};
-use std::path::PathBuf;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
+use tesla_wallcon_monitor::alert::AlertTracker;
+use tesla_wallcon_monitor::config::Config;
+use tesla_wallcon_monitor::cost::{CostTracker, SessionCost};
+#[cfg(unix)]
+use tesla_wallcon_monitor::daemon;
+use tesla_wallcon_monitor::dashboard;
+use tesla_wallcon_monitor::discover::{Cidr, discover};
+use tesla_wallcon_monitor::exporter::{self, Exporter};
+use tesla_wallcon_monitor::fleet::{Device, parse_devices, poll_vitals};
+use tesla_wallcon_monitor::format::{
+    Units, format_alerts, format_cost_summary, format_discovered, format_live_cost,
+    format_overview, format_payload, format_session_costs, format_sessions, format_time_of_day,
+    format_timestamp, format_vitals,
+};
+use tesla_wallcon_monitor::logfile::{JsonLogger, LogFile, LogFormat, LogRecord, Payload};
+use tesla_wallcon_monitor::models::Version;
+use tesla_wallcon_monitor::mqtt::{self, LIFETIME_COUNTERS, MqttConfig, MqttPublisher};
+use tesla_wallcon_monitor::output::{OutputFormat, Printer};
+use tesla_wallcon_monitor::retry::RetryPolicy;
+use tesla_wallcon_monitor::rules::{Rule, RuleEngine};
+use tesla_wallcon_monitor::session::detect_sessions;
+use tesla_wallcon_monitor::simulator::{self, Simulator};
+use tesla_wallcon_monitor::store::{ImportStats, Store};
+use tesla_wallcon_monitor::tariff::Tariff;
+use tesla_wallcon_monitor::term::{highlight_changes, is_exit_key, read_key};
+use tesla_wallcon_monitor::web::{self, WebServer};
+use tesla_wallcon_monitor::webhook::Webhook;
+use tesla_wallcon_monitor::{Error, WallConnectorClient};
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+
+const COMMANDS: &[&str] = &[
+    "alerts",
+    "cost",
+    "dashboard",
+    "exporter",
+    "lifetime",
+    "mqtt",
+    "overview",
+    "serve",
+    "version",
+    "vitals",
+    "watch",
+    "wifi_status",
+];
+
+/// The original commands, their abbreviations win over ones of commands
+/// added later, e.g. `w` stays `wifi_status` rather than clashing with `watch`.
+const ORIGINAL_COMMANDS: &[&str] = &["lifetime", "version", "vitals", "wifi_status"];
 
-const COMMANDS: &[&str] = &["lifetime", "version", "vitals", "wifi_status"];
+/// Commands starting with `prefix`, only the original one if there is one.
+fn commands_matching(prefix: &str) -> Vec<&'static str> {
+    let matches: Vec<&str> = COMMANDS
+        .iter()
+        .filter(|cmd| cmd.starts_with(prefix))
+        .copied()
+        .collect();
+    let original: Vec<&str> = matches
+        .iter()
+        .filter(|cmd| ORIGINAL_COMMANDS.contains(cmd))
+        .copied()
+        .collect();
+    if original.len() == 1 {
+        original
+    } else {
+        matches
+    }
+}
 
-fn min_abbreviation(cmd: &str, all_commands: &[&str]) -> usize {
+fn min_abbreviation(cmd: &str) -> usize {
     for len in 1..=cmd.len() {
-        let prefix = &cmd[..len];
-        let matches: Vec<_> = all_commands
-            .iter()
-            .filter(|c| c.starts_with(prefix))
-            .collect();
-        if matches.len() == 1 {
+        if commands_matching(&cmd[..len]) == [cmd] {
             return len;
         }
     }
@@ -34,60 +97,223 @@ fn format_commands_help() -> String {
     let cmds: Vec<String> = COMMANDS
         .iter()
         .map(|cmd| {
-            let min_len = min_abbreviation(cmd, COMMANDS);
+            let min_len = min_abbreviation(cmd);
             format!("({}){}", &cmd[..min_len], &cmd[min_len..])
         })
         .collect();
     format!("Command: {}", cmds.join(", "))
 }
 
-fn init_logging(log_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+fn init_logging(log_path: &PathBuf, format: LogFormat) -> Result<(), Box<dyn std::error::Error>> {
     let file = OpenOptions::new()
         .create(true)
         .append(true)
         .open(log_path)?;
 
-    let config = ConfigBuilder::new().set_time_format_rfc3339().build();
-
-    WriteLogger::init(LevelFilter::Info, config, file)?;
+    match format {
+        LogFormat::Text => {
+            let config = ConfigBuilder::new().set_time_format_rfc3339().build();
+            WriteLogger::init(LevelFilter::Info, config, file)?;
+        }
+        LogFormat::Jsonl => JsonLogger::new(LevelFilter::Info, file).init()?,
+    }
     Ok(())
 }
 
-fn log_json(endpoint: &str, json: &str) {
-    info!("{}: {}", endpoint, json);
-}
-
 #[derive(Parser)]
 #[command(name = "tesla-wallcon-monitor")]
 #[command(version)]
 #[command(before_help = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")))]
 #[command(about = "Monitor a Tesla Wall Connector")]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
+#[command(subcommand_value_name = "TOOL", subcommand_help_heading = "Tools")]
 struct Args {
-    /// Name or IP address of the wall connector
-    addr: String,
+    /// Name or IP address of the wall connector or a device of the config
+    /// file, several as a comma separated list of ADDR or NAME=ADDR
+    #[arg(required = true)]
+    addr: Option<String>,
 
     /// Command to execute
-    command: String,
+    command: Option<String>,
 
-    /// Loop mode: continuously update display (vitals only)
+    /// Loop mode: continuously update the output, highlighting changed values
     #[arg(short, long)]
     loop_mode: bool,
 
-    /// Delay in seconds between updates in loop mode
+    /// Delay in seconds between updates in loop mode, the dashboard and serve, and between mqtt and watch polls
     #[arg(short, long, default_value = "5")]
     delay: u64,
 
     /// Log file for debug output (JSON data with timestamps)
     #[arg(long)]
     log: Option<PathBuf>,
+
+    /// Format of the --log file: text or jsonl, one JSON object per response
+    #[arg(long, default_value = "text")]
+    log_format: LogFormat,
+
+    /// SQLite database to store every response in, with the sessions derived from them
+    #[arg(long, global = true)]
+    db: Option<PathBuf>,
+
+    /// Config file [default: tesla-wallcon-monitor/config.toml in the user's config directory]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Display units, metric or imperial
+    #[arg(long, default_value = "metric")]
+    units: Units,
+
+    /// Output of lifetime, version, vitals and wifi_status: text, json, yaml, csv or key=value
+    #[arg(long, default_value = "text")]
+    format: OutputFormat,
+
+    /// Milliseconds to wait for a connection to the wall connector
+    #[arg(long, default_value = "5000", help_heading = "HTTP")]
+    connect_timeout: u64,
+
+    /// Milliseconds a request may take in total
+    #[arg(long, default_value = "10000", help_heading = "HTTP")]
+    read_timeout: u64,
+
+    /// Retries of a failed request, with jittered exponential backoff
+    #[arg(long, default_value = "2", help_heading = "HTTP")]
+    retries: u32,
+
+    /// Milliseconds before the first retry, doubled for each further one
+    #[arg(long, default_value = "250", help_heading = "HTTP")]
+    backoff: u64,
+
+    /// Minutes of history shown in the dashboard and serve charts
+    #[arg(long, default_value = "10", help_heading = "Dashboard")]
+    window: u64,
+
+    /// Address to serve on [default: 0.0.0.0:9869 for exporter, 0.0.0.0:8088 for serve]
+    #[arg(long, help_heading = "Exporter")]
+    listen: Option<String>,
+
+    /// Seconds to reuse fetched data between scrapes, 0 fetches on every scrape
+    #[arg(long, default_value = "0", help_heading = "Exporter")]
+    cache: u64,
+
+    /// MQTT broker as HOST or HOST:PORT
+    #[arg(long, default_value = "localhost", help_heading = "MQTT")]
+    broker: String,
+
+    /// MQTT user name
+    #[arg(long, help_heading = "MQTT", requires = "mqtt_password")]
+    mqtt_user: Option<String>,
+
+    /// MQTT password
+    #[arg(long, help_heading = "MQTT", requires = "mqtt_user")]
+    mqtt_password: Option<String>,
+
+    /// Base of the topics data is published to
+    #[arg(long, default_value = mqtt::DEFAULT_BASE_TOPIC, help_heading = "MQTT")]
+    mqtt_topic: String,
+
+    /// Home Assistant discovery prefix
+    #[arg(long, default_value = mqtt::DEFAULT_DISCOVERY_PREFIX, help_heading = "MQTT")]
+    discovery_prefix: String,
+
+    /// Rule to watch, e.g. "handle_temp_c > 60 for 2m", may be repeated
+    #[arg(long = "rule", value_name = "RULE", help_heading = "Watch")]
+    rules: Vec<Rule>,
+
+    /// URL notifications are POSTed to as JSON
+    #[arg(long, help_heading = "Watch")]
+    webhook: Option<String>,
+
+    #[command(subcommand)]
+    tool: Option<Tool>,
+}
+
+/// Tools that work without a wall connector
+#[derive(Subcommand)]
+enum Tool {
+    /// Replay a file written with --log
+    Replay {
+        /// Log file to replay
+        file: PathBuf,
+
+        /// Playback speed, e.g. 10 for ten times real time
+        #[arg(short, long, default_value = "1", value_parser = parse_speed)]
+        speed: f64,
+
+        /// Start paused and advance one record per key press
+        #[arg(long)]
+        step: bool,
+    },
+
+    /// List the charging sessions in files written with --log
+    Sessions {
+        /// Log files to read
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// With a tariff in the config file, list the cost by session, day or month
+        #[arg(long, value_parser = ["session", "day", "month"])]
+        by: Option<String>,
+    },
+
+    /// Import files written with --log into the --db history database
+    Import {
+        /// Log files or glob patterns, e.g. 'logs/*.txt'
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// Device the files were logged from, defaults to the only one of [devices]
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Scan an IPv4 range for wall connectors
+    Discover {
+        /// Range to scan, e.g. 192.168.1.0/24
+        cidr: Cidr,
+
+        /// Ports to try on each host, comma separated
+        #[arg(short, long, value_delimiter = ',', default_value = "80")]
+        port: Vec<u16>,
+
+        /// Milliseconds to wait for each host to answer
+        #[arg(long, default_value = "500")]
+        timeout: u64,
+
+        /// Hosts to ask at the same time
+        #[arg(long, default_value = "64")]
+        concurrency: usize,
+    },
+
+    /// Poll the devices of the [daemon] config section into its sinks until stopped
+    #[cfg(unix)]
+    Daemon,
+
+    /// Serve a simulated wall connector
+    Simulate {
+        /// Log file to replay instead of the scripted charging scenario
+        file: Option<PathBuf>,
+
+        /// Address to serve /api/1 on
+        #[arg(long, default_value = simulator::DEFAULT_LISTEN)]
+        listen: String,
+
+        /// Simulation speed, e.g. 10 for ten times real time
+        #[arg(short, long, default_value = "1", value_parser = parse_speed)]
+        speed: f64,
+    },
+}
+
+/// Parse a `--speed` factor, a finite number greater than 0.
+fn parse_speed(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        _ => Err(format!("'{}' isn't a number greater than 0", text)),
+    }
 }
 
 fn match_command(input: &str) -> Result<&'static str, String> {
-    let matches: Vec<&str> = COMMANDS
-        .iter()
-        .filter(|cmd| cmd.starts_with(input))
-        .copied()
-        .collect();
+    let matches = commands_matching(input);
 
     match matches.len() {
         0 => Err(format!(
@@ -104,337 +330,859 @@ fn match_command(input: &str) -> Result<&'static str, String> {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Version {
-    firmware_version: String,
-    git_branch: String,
-    part_number: String,
-    serial_number: String,
-    web_service: Option<String>,
-}
-
-fn get_version(addr: &str) -> Result<Version, Box<dyn std::error::Error>> {
-    let url = format!("http://{}/api/1/version", addr);
-    let response = reqwest::blocking::get(&url)?;
-    let text = response.text()?;
-    log_json("version", &text);
-    let version: Version = serde_json::from_str(&text)?;
-    Ok(version)
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-struct WifiStatus {
-    wifi_ssid: String,
-    wifi_signal_strength: i32,
-    wifi_rssi: i32,
-    wifi_snr: i32,
-    wifi_connected: bool,
-    wifi_infra_ip: String,
-    internet: bool,
-    wifi_mac: String,
-}
-
-fn get_wifi_status(addr: &str) -> Result<WifiStatus, Box<dyn std::error::Error>> {
-    let url = format!("http://{}/api/1/wifi_status", addr);
-    let response = reqwest::blocking::get(&url)?;
-    let text = response.text()?;
-    log_json("wifi_status", &text);
-    let status: WifiStatus = serde_json::from_str(&text)?;
-    Ok(status)
-}
-
-fn decode_ssid(encoded: &str) -> String {
-    STANDARD
-        .decode(encoded)
-        .ok()
-        .and_then(|bytes| String::from_utf8(bytes).ok())
-        .unwrap_or_else(|| encoded.to_string())
-}
-
-fn run_wifi_status(addr: &str) {
-    match get_wifi_status(addr) {
-        Ok(status) => {
-            println!("Tesla Wall Connector WiFi Status:");
-            println!("  SSID:            {}", decode_ssid(&status.wifi_ssid));
-            println!("  Connected:       {}", status.wifi_connected);
-            println!("  Signal Strength: {}%", status.wifi_signal_strength);
-            println!("  RSSI:            {} dBm", status.wifi_rssi);
-            println!("  SNR:             {} dB", status.wifi_snr);
-            println!("  IP Address:      {}", status.wifi_infra_ip);
-            println!("  Internet:        {}", status.internet);
-            println!("  MAC Address:     {}", status.wifi_mac);
+/// Report a failed request and exit with the code of its class.
+fn exit_with_error(context: &str, e: &Error) -> ! {
+    eprintln!("{}: {}", context, e);
+    std::process::exit(e.exit_code());
+}
+
+/// Fetch the response of `command`, one of lifetime, version, vitals and
+/// wifi_status.
+fn fetch_payload(client: &WallConnectorClient, command: &str) -> Result<Payload, Error> {
+    match command {
+        "lifetime" => client.lifetime().map(Payload::Lifetime),
+        "version" => client.version().map(Payload::Version),
+        "vitals" => client.vitals().map(Payload::Vitals),
+        "wifi_status" => client.wifi_status().map(Payload::WifiStatus),
+        _ => unreachable!(),
+    }
+}
+
+/// Context of the errors of `command`.
+fn fetch_context(command: &str) -> &'static str {
+    match command {
+        "lifetime" => "Error fetching lifetime stats",
+        "version" => "Error fetching version",
+        "vitals" => "Error fetching vitals",
+        "wifi_status" => "Error fetching wifi status",
+        _ => unreachable!(),
+    }
+}
+
+/// Print the response of `command`, in loop mode again every `delay` seconds.
+fn run_endpoint(
+    client: &WallConnectorClient,
+    command: &str,
+    loop_mode: bool,
+    delay: u64,
+    printer: &mut Printer,
+) {
+    if !loop_mode {
+        match fetch_payload(client, command) {
+            Ok(payload) => println!("{}", printer.render(&payload, None)),
+            Err(e) => exit_with_error(fetch_context(command), &e),
         }
-        Err(e) => {
-            eprintln!("Error fetching wifi status: {}", e);
-            std::process::exit(1);
+    } else if printer.format() != OutputFormat::Text {
+        // Machine readable records are appended rather than redrawn
+        loop {
+            match fetch_payload(client, command) {
+                Ok(payload) => {
+                    let now = OffsetDateTime::now_utc().replace_nanosecond(0).unwrap();
+                    println!("{}", printer.render(&payload, Some(now)));
+                }
+                Err(e) => eprintln!("{}: {}", fetch_context(command), e),
+            }
+            std::thread::sleep(Duration::from_secs(delay));
         }
+    } else {
+        run_display_loop(delay, || match fetch_payload(client, command) {
+            Ok(payload) => Ok(printer.render(&payload, None)),
+            Err(e) => Err(format!("{}: {}", fetch_context(command), e)),
+        });
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Lifetime {
-    contactor_cycles: u32,
-    contactor_cycles_loaded: u32,
-    alert_count: u32,
-    thermal_foldbacks: u32,
-    avg_startup_temp: f64,
-    charge_starts: u32,
-    energy_wh: u64,
-    connector_cycles: u32,
-    uptime_s: u64,
-    charging_time_s: u64,
+fn fetch_alerts(client: &WallConnectorClient, tracker: &mut AlertTracker) -> Result<String, Error> {
+    let lifetime = client.lifetime()?;
+    let vitals = client.vitals()?;
+    tracker.update_lifetime(&lifetime);
+    tracker.update_vitals(&vitals);
+    Ok(format_alerts(tracker, &vitals, lifetime.alert_count))
+}
+
+fn run_alerts(client: &WallConnectorClient, loop_mode: bool, delay: u64) {
+    let mut tracker = AlertTracker::new();
+    if loop_mode {
+        run_display_loop(delay, || {
+            fetch_alerts(client, &mut tracker).map_err(|e| format!("Error fetching alerts: {}", e))
+        });
+    } else {
+        match fetch_alerts(client, &mut tracker) {
+            Ok(report) => println!("{}", report),
+            Err(e) => exit_with_error("Error fetching alerts", &e),
+        }
+    }
 }
 
-fn get_lifetime(addr: &str) -> Result<Lifetime, Box<dyn std::error::Error>> {
-    let url = format!("http://{}/api/1/lifetime", addr);
-    let response = reqwest::blocking::get(&url)?;
-    let text = response.text()?;
-    log_json("lifetime", &text);
-    let lifetime: Lifetime = serde_json::from_str(&text)?;
-    Ok(lifetime)
+/// Redraw the output of `render` every `delay` seconds until ESC or Ctrl+C,
+/// highlighting what changed since the previous refresh. When `render`
+/// fails the last output stays up, marked stale.
+fn run_display_loop(delay: u64, mut render: impl FnMut() -> Result<String, String>) {
+    let mut previous: Option<(String, OffsetDateTime)> = None;
+    run_interactive_loop(
+        delay,
+        || match (render(), &previous) {
+            (Ok(text), _) => {
+                let shown = match &previous {
+                    Some((previous, _)) => highlight_changes(previous, &text),
+                    None => text.clone(),
+                };
+                previous = Some((text, OffsetDateTime::now_utc()));
+                format!(
+                    "{}\n\n  Press ESC or Ctrl+C to exit (updates every {}s)",
+                    shown, delay
+                )
+            }
+            (Err(e), Some((text, updated))) => format!(
+                "{}\n\n  {}\n  Press ESC or Ctrl+C to exit (updates every {}s)",
+                text,
+                format!("Stale since {} UTC, {}", format_time_of_day(*updated), e).red(),
+                delay
+            ),
+            (Err(e), None) => format!("{}\n\n  Press ESC or Ctrl+C to exit", e),
+        },
+        |key_event| !is_exit_key(key_event),
+    );
 }
 
-fn format_duration(seconds: u64) -> String {
-    let days = seconds / 86400;
-    let hours = (seconds % 86400) / 3600;
-    let mins = (seconds % 3600) / 60;
-    if days > 0 {
-        format!("{}d {}h {}m", days, hours, mins)
-    } else if hours > 0 {
-        format!("{}h {}m", hours, mins)
+/// Redraw the output of `render` every `delay` seconds and after every key
+/// press until `handle_key` returns false.
+fn run_interactive_loop(
+    delay: u64,
+    mut render: impl FnMut() -> String,
+    mut handle_key: impl FnMut(&KeyEvent) -> bool,
+) {
+    terminal::enable_raw_mode().expect("Failed to enable raw mode");
+    let mut stdout = stdout();
+
+    loop {
+        // Clear screen and move cursor to top
+        execute!(stdout, Clear(ClearType::All), MoveTo(0, 0)).unwrap();
+        print!("{}\r\n", render().replace('\n', "\r\n"));
+        stdout.flush().unwrap();
+
+        // Check for key press with configured delay timeout
+        if let Some(key_event) = read_key(Some(Duration::from_secs(delay)))
+            && !handle_key(&key_event)
+        {
+            break;
+        }
+    }
+
+    terminal::disable_raw_mode().expect("Failed to disable raw mode");
+    execute!(stdout, Clear(ClearType::All), MoveTo(0, 0)).unwrap();
+}
+
+/// Summary of all `devices`, in loop mode 1-9 drill down into the vitals of
+/// a device.
+fn run_overview(devices: &[Device], loop_mode: bool, delay: u64, units: Units) {
+    if !loop_mode {
+        println!("{}", format_overview(&poll_vitals(devices), units));
+        return;
+    }
+
+    let selected: Cell<Option<usize>> = Cell::new(None);
+    run_interactive_loop(
+        delay,
+        || match selected.get() {
+            None => format!(
+                "{}\n\n  Press 1-9 for the vitals of a device, ESC or Ctrl+C to exit (updates every {}s)",
+                format_overview(&poll_vitals(devices), units),
+                delay
+            ),
+            Some(i) => {
+                let device = &devices[i];
+                let vitals = match device.client.vitals() {
+                    Ok(vitals) => format_vitals(&vitals, units),
+                    Err(e) => format!("Error fetching vitals: {}", e),
+                };
+                format!(
+                    "{} ({})\n\n{}\n\n  Press ESC to go back, LEFT/RIGHT for other devices, Ctrl+C to exit (updates every {}s)",
+                    device.name,
+                    device.client.addr(),
+                    vitals,
+                    delay
+                )
+            }
+        },
+        |key_event| {
+            let count = devices.len();
+            match (key_event.code, selected.get()) {
+                (KeyCode::Char('c'), _) if is_exit_key(key_event) => return false,
+                (KeyCode::Esc, None) => return false,
+                (KeyCode::Esc | KeyCode::Backspace, Some(_)) => selected.set(None),
+                (KeyCode::Char(c @ '1'..='9'), _) => {
+                    let i = c as usize - '1' as usize;
+                    if i < count {
+                        selected.set(Some(i));
+                    }
+                }
+                (KeyCode::Left, Some(i)) => selected.set(Some((i + count - 1) % count)),
+                (KeyCode::Right, Some(i)) => selected.set(Some((i + 1) % count)),
+                _ => {}
+            }
+            true
+        },
+    );
+}
+
+/// Cost of the charging seen since the start, refreshed every `delay`
+/// seconds in loop mode.
+fn run_cost(client: &WallConnectorClient, tariff: Tariff, loop_mode: bool, delay: u64) {
+    let mut tracker = CostTracker::new(tariff);
+    let mut completed = Vec::new();
+    let mut update = || -> Result<String, Error> {
+        let vitals = client.vitals()?;
+        let now = OffsetDateTime::now_utc();
+        completed.extend(tracker.push(now, &vitals));
+        Ok(format_live_cost(&tracker, &completed, now))
+    };
+    if loop_mode {
+        run_display_loop(delay, || {
+            update().map_err(|e| format!("Error fetching vitals: {}", e))
+        });
     } else {
-        format!("{}m", mins)
+        match update() {
+            Ok(report) => println!("{}", report),
+            Err(e) => exit_with_error("Error fetching vitals", &e),
+        }
     }
 }
 
-fn run_lifetime(addr: &str) {
-    match get_lifetime(addr) {
-        Ok(lifetime) => {
-            println!("Tesla Wall Connector Lifetime Stats:");
-            println!("  Charge Starts:      {}", lifetime.charge_starts);
-            println!(
-                "  Energy Delivered:   {:.2} kWh",
-                lifetime.energy_wh as f64 / 1000.0
-            );
-            println!(
-                "  Charging Time:      {}",
-                format_duration(lifetime.charging_time_s)
-            );
-            println!(
-                "  Uptime:             {}",
-                format_duration(lifetime.uptime_s)
-            );
-            println!("  Contactor Cycles:   {}", lifetime.contactor_cycles);
-            println!("  Loaded Cycles:      {}", lifetime.contactor_cycles_loaded);
-            println!("  Connector Cycles:   {}", lifetime.connector_cycles);
-            println!("  Thermal Foldbacks:  {}", lifetime.thermal_foldbacks);
-            println!("  Alert Count:        {}", lifetime.alert_count);
-            println!("  Avg Startup Temp:   {:.1}°C", lifetime.avg_startup_temp);
+fn run_dashboard(client: WallConnectorClient, delay: u64, window: u64, units: Units) {
+    let delay = Duration::from_secs(delay);
+    let window = Duration::from_secs(window.max(1) * 60);
+    if let Err(e) = dashboard::run(client, delay, window, units) {
+        eprintln!("Dashboard failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run_discover(cidr: &Cidr, ports: &[u16], timeout: u64, concurrency: usize) {
+    eprintln!("Scanning {} ...", cidr);
+    match discover(cidr, ports, Duration::from_millis(timeout), concurrency) {
+        Ok(found) if found.is_empty() => {
+            eprintln!("No wall connectors found in {}", cidr);
+            std::process::exit(1);
         }
+        Ok(found) => println!("{}", format_discovered(&found)),
         Err(e) => {
-            eprintln!("Error fetching lifetime stats: {}", e);
+            eprintln!("Error scanning {}: {}", cidr, e);
             std::process::exit(1);
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Vitals {
-    contactor_closed: bool,
-    vehicle_connected: bool,
-    session_s: u64,
-    grid_v: f64,
-    grid_hz: f64,
-    vehicle_current_a: f64,
-    #[serde(rename = "currentA_a")]
-    current_a_a: f64,
-    #[serde(rename = "currentB_a")]
-    current_b_a: f64,
-    #[serde(rename = "currentC_a")]
-    current_c_a: f64,
-    #[serde(rename = "currentN_a")]
-    current_n_a: f64,
-    #[serde(rename = "voltageA_v")]
-    voltage_a_v: f64,
-    #[serde(rename = "voltageB_v")]
-    voltage_b_v: f64,
-    #[serde(rename = "voltageC_v")]
-    voltage_c_v: f64,
-    relay_coil_v: f64,
-    pcba_temp_c: f64,
-    handle_temp_c: f64,
-    mcu_temp_c: f64,
-    uptime_s: u64,
-    input_thermopile_uv: i32,
-    prox_v: f64,
-    pilot_high_v: f64,
-    pilot_low_v: f64,
-    session_energy_wh: f64,
-    config_status: u32,
-    evse_state: u32,
-    current_alerts: Vec<serde_json::Value>,
-    evse_not_ready_reasons: Vec<u32>,
-}
-
-fn get_vitals(addr: &str) -> Result<Vitals, Box<dyn std::error::Error>> {
-    let url = format!("http://{}/api/1/vitals", addr);
-    let response = reqwest::blocking::get(&url)?;
-    let text = response.text()?;
-    log_json("vitals", &text);
-    let vitals: Vitals = serde_json::from_str(&text)?;
-    Ok(vitals)
-}
-
-fn format_vitals(vitals: &Vitals) -> String {
-    let mut lines = vec![
-        "Tesla Wall Connector Vitals:".to_string(),
-        format!("  Vehicle Connected:  {}", vitals.vehicle_connected),
-        format!("  Contactor Closed:   {}", vitals.contactor_closed),
-        format!(
-            "  Session Duration:   {}",
-            format_duration(vitals.session_s)
-        ),
-        format!(
-            "  Session Energy:     {:.3} kWh",
-            vitals.session_energy_wh / 1000.0
-        ),
-        format!("  Vehicle Current:    {:.1} A", vitals.vehicle_current_a),
-        String::new(),
-        format!("  Grid Voltage:       {:.1} V", vitals.grid_v),
-        format!("  Grid Frequency:     {:.3} Hz", vitals.grid_hz),
-        format!(
-            "  Phase Currents:     A={:.1} B={:.1} C={:.1} N={:.1} A",
-            vitals.current_a_a, vitals.current_b_a, vitals.current_c_a, vitals.current_n_a
-        ),
-        format!(
-            "  Phase Voltages:     A={:.1} B={:.1} C={:.1} V",
-            vitals.voltage_a_v, vitals.voltage_b_v, vitals.voltage_c_v
-        ),
-        String::new(),
-        format!("  PCBA Temp:          {:.1}°C", vitals.pcba_temp_c),
-        format!("  Handle Temp:        {:.1}°C", vitals.handle_temp_c),
-        format!("  MCU Temp:           {:.1}°C", vitals.mcu_temp_c),
-        String::new(),
-        format!(
-            "  Pilot High/Low:     {:.1} / {:.1} V",
-            vitals.pilot_high_v, vitals.pilot_low_v
-        ),
-        format!("  Proximity:          {:.1} V", vitals.prox_v),
-        format!("  Relay Coil:         {:.1} V", vitals.relay_coil_v),
-        format!("  Thermopile:         {} uV", vitals.input_thermopile_uv),
-        String::new(),
-        format!("  Uptime:             {}", format_duration(vitals.uptime_s)),
-        format!("  EVSE State:         {}", vitals.evse_state),
-        format!("  Config Status:      {}", vitals.config_status),
-    ];
-    if !vitals.current_alerts.is_empty() {
-        lines.push(format!("  Current Alerts:     {:?}", vitals.current_alerts));
+fn run_simulate(file: Option<&Path>, listen: &str, speed: f64) {
+    let simulator = match file {
+        Some(path) => {
+            let records = read_logs(&[path.to_path_buf()]);
+            match Simulator::replay(records, speed) {
+                Ok(simulator) => simulator,
+                Err(e) => {
+                    eprintln!("Error replaying {}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => Simulator::scenario(speed),
+    };
+    let listener = match TcpListener::bind(listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen on {}: {}", listen, e);
+            std::process::exit(1);
+        }
+    };
+    let source = match file {
+        Some(path) => format!("replaying {}", path.display()),
+        None => "scripted charging scenario".to_string(),
+    };
+    let addr = listener
+        .local_addr()
+        .map_or(listen.to_string(), |a| a.to_string());
+    println!(
+        "Simulating a wall connector on http://{}/api/1 ({})",
+        addr, source
+    );
+    println!("Try: tesla-wallcon-monitor {} vitals", addr);
+    if let Err(e) = simulator.serve(listener) {
+        eprintln!("Simulator failed: {}", e);
+        std::process::exit(1);
     }
-    if !vitals.evse_not_ready_reasons.is_empty() {
-        lines.push(format!(
-            "  Not Ready Reasons:  {:?}",
-            vitals.evse_not_ready_reasons
-        ));
+}
+
+/// Run the daemon, re-reading the config file on SIGHUP.
+#[cfg(unix)]
+fn run_daemon(matches: &ArgMatches, config: Config) {
+    let mut config = Some(config);
+    let load = || -> Result<daemon::Settings, Box<dyn std::error::Error>> {
+        let config = match config.take() {
+            Some(config) => config,
+            None => read_config(matches)?,
+        };
+        let mut args = Args::from_arg_matches(matches)?;
+        apply_config(&mut args, &config, matches);
+        let mut settings = daemon::Settings::from_config(&config, args.delay)?;
+        let store = open_store(&args)?;
+        for device in &mut settings.devices {
+            configure_device(device, &args, store.as_ref())?;
+        }
+        Ok(settings)
+    };
+    if let Err(e) = daemon::run(load) {
+        eprintln!("Daemon failed: {}", e);
+        std::process::exit(1);
     }
-    lines.join("\n")
 }
 
-fn print_vitals(vitals: &Vitals) {
-    println!("{}", format_vitals(vitals));
+fn run_exporter(client: WallConnectorClient, listen: &str, cache: u64) {
+    let listener = match TcpListener::bind(listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen on {}: {}", listen, e);
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "Serving metrics of {} on http://{}/metrics",
+        client.addr(),
+        listen
+    );
+    let exporter = Exporter::new(client, Duration::from_secs(cache));
+    if let Err(e) = exporter.serve(listener) {
+        eprintln!("Exporter failed: {}", e);
+        std::process::exit(1);
+    }
 }
 
-fn print_vitals_raw(vitals: &Vitals) {
-    print!("{}\r\n", format_vitals(vitals).replace('\n', "\r\n"));
+fn run_serve(client: WallConnectorClient, listen: &str, delay: u64, window: u64) {
+    let listener = match TcpListener::bind(listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen on {}: {}", listen, e);
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "Serving the dashboard of {} on http://{}/ (updates every {}s)",
+        client.addr(),
+        listen,
+        delay
+    );
+    let server = WebServer::new(
+        client,
+        Duration::from_secs(delay.max(1)),
+        Duration::from_secs(window.max(1) * 60),
+    );
+    if let Err(e) = server.serve(listener) {
+        eprintln!("Web server failed: {}", e);
+        std::process::exit(1);
+    }
 }
 
-fn run_vitals(addr: &str, loop_mode: bool, delay: u64) {
-    if loop_mode {
-        run_vitals_loop(addr, delay);
-    } else {
-        match get_vitals(addr) {
-            Ok(vitals) => print_vitals(&vitals),
-            Err(e) => {
-                eprintln!("Error fetching vitals: {}", e);
-                std::process::exit(1);
+fn publish_mqtt(
+    client: &WallConnectorClient,
+    publisher: &MqttPublisher,
+    version: &Version,
+    discovery_sent: &mut bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let vitals = client.vitals()?;
+    let lifetime = client.lifetime()?;
+    let wifi = client.wifi_status()?;
+    if !*discovery_sent {
+        // session_energy_wh drops back to 0 on plug-in, which Home Assistant
+        // treats as a meter reset
+        publisher.publish_discovery(version, "vitals", &vitals, &["session_energy_wh"])?;
+        publisher.publish_discovery(version, "lifetime", &lifetime, LIFETIME_COUNTERS)?;
+        publisher.publish_discovery(version, "wifi_status", &wifi, &[])?;
+        *discovery_sent = true;
+    }
+    publisher.publish("vitals", &vitals)?;
+    publisher.publish("lifetime", &lifetime)?;
+    publisher.publish("wifi_status", &wifi)?;
+    publisher.publish_availability(true)
+}
+
+fn run_mqtt(client: &WallConnectorClient, config: MqttConfig, delay: u64) {
+    let version = match client.version() {
+        Ok(version) => version,
+        Err(e) => exit_with_error("Error fetching version", &e),
+    };
+    println!(
+        "Publishing {} ({}) to {}:{} every {}s",
+        client.addr(),
+        version.serial_number,
+        config.host,
+        config.port,
+        delay
+    );
+    let publisher = MqttPublisher::connect(config, &version.serial_number);
+    let mut discovery_sent = false;
+    loop {
+        if let Err(e) = publish_mqtt(client, &publisher, &version, &mut discovery_sent) {
+            eprintln!("Error publishing: {}", e);
+            let _ = publisher.publish_availability(false);
+        }
+        std::thread::sleep(Duration::from_secs(delay));
+    }
+}
+
+fn run_watch(devices: &[Device], rules: Vec<Rule>, webhook: Option<Webhook>, delay: u64) {
+    if rules.is_empty() {
+        eprintln!("watch needs at least one --rule");
+        std::process::exit(1);
+    }
+    let mut engines: Vec<RuleEngine> = devices
+        .iter()
+        .map(|device| RuleEngine::new(&device.name, rules.clone()))
+        .collect();
+    let mut failing = vec![false; devices.len()];
+    let mut validated = false;
+    println!(
+        "Watching {} rule{} on {} wall connector{}{}",
+        rules.len(),
+        if rules.len() == 1 { "" } else { "s" },
+        devices.len(),
+        if devices.len() == 1 { "" } else { "s" },
+        webhook
+            .as_ref()
+            .map(|w| format!(", notifying {}", w.url()))
+            .unwrap_or_default()
+    );
+
+    loop {
+        let now = OffsetDateTime::now_utc().replace_nanosecond(0).unwrap();
+        for (i, polled) in poll_vitals(devices).into_iter().enumerate() {
+            let vitals = match polled.vitals {
+                Ok(vitals) => vitals,
+                Err(e) => {
+                    // Report outages once rather than on every poll
+                    if !failing[i] {
+                        eprintln!("{}: Error fetching vitals: {}", polled.name, e);
+                        failing[i] = true;
+                    }
+                    continue;
+                }
+            };
+            if failing[i] {
+                eprintln!("{}: fetching vitals again", polled.name);
+                failing[i] = false;
+            }
+            if !validated {
+                if let Err(e) = engines[i].validate(&vitals) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+                validated = true;
+            }
+            for notification in engines[i].update(now, &vitals) {
+                println!("{} {}", format_timestamp(now), notification);
+                if let Some(webhook) = &webhook
+                    && let Err(e) = webhook.send(&notification)
+                {
+                    eprintln!("Error sending to {}: {}", webhook.url(), e);
+                }
             }
         }
+        std::thread::sleep(Duration::from_secs(delay));
     }
 }
 
-fn run_vitals_loop(addr: &str, delay: u64) {
+fn run_replay(path: &Path, speed: f64, step: bool, units: Units) {
+    let log = match LogFile::read(path) {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    if log.records.is_empty() {
+        eprintln!("No records found in {}", path.display());
+        std::process::exit(1);
+    }
     terminal::enable_raw_mode().expect("Failed to enable raw mode");
     let mut stdout = stdout();
+    let records = &log.records;
+    let mut index = 0;
+    let mut paused = step;
 
     loop {
-        // Clear screen and move cursor to top
         execute!(stdout, Clear(ClearType::All), MoveTo(0, 0)).unwrap();
 
-        match get_vitals(addr) {
-            Ok(vitals) => {
-                print_vitals_raw(&vitals);
-                print!(
-                    "\r\n  Press ESC or Ctrl+C to exit (updates every {}s)\r\n",
-                    delay
-                );
+        let record = &records[index];
+        let timestamp = record.timestamp.format(&Rfc3339).unwrap();
+        print!(
+            "Replay {} [{}/{}] {}\r\n\r\n",
+            path.display(),
+            index + 1,
+            records.len(),
+            timestamp
+        );
+        let text = format_payload(&record.payload, units);
+        print!("{}\r\n", text.replace('\n', "\r\n"));
+        let at_end = index + 1 == records.len();
+        let status = if at_end {
+            "end of log"
+        } else if paused {
+            "paused"
+        } else {
+            "playing"
+        };
+        print!(
+            "\r\n  {} at {}x: SPACE pause/play, LEFT/RIGHT step, ESC or Ctrl+C exit\r\n",
+            status, speed
+        );
+        stdout.flush().unwrap();
+
+        let wait = if paused || at_end {
+            None
+        } else {
+            let gap = records[index + 1].timestamp - record.timestamp;
+            Some(Duration::from_secs_f64(
+                gap.as_seconds_f64().max(0.0) / speed,
+            ))
+        };
+        match read_key(wait) {
+            None => index += 1,
+            Some(key_event) if is_exit_key(&key_event) => break,
+            Some(key_event) => match key_event.code {
+                KeyCode::Char(' ') => paused = !paused,
+                KeyCode::Right | KeyCode::Enter if !at_end => index += 1,
+                KeyCode::Left => index = index.saturating_sub(1),
+                KeyCode::Home => index = 0,
+                _ => {}
+            },
+        }
+    }
+
+    terminal::disable_raw_mode().expect("Failed to disable raw mode");
+    execute!(stdout, Clear(ClearType::All), MoveTo(0, 0)).unwrap();
+    if !log.skipped.is_empty() {
+        println!("Skipped {} unparsable lines", log.skipped.len());
+    }
+}
+
+/// Read all `files`, merging their records in timestamp order.
+fn read_logs(files: &[PathBuf]) -> Vec<LogRecord> {
+    let mut records = Vec::new();
+    for path in files {
+        match LogFile::read(path) {
+            Ok(log) => {
+                if !log.skipped.is_empty() {
+                    eprintln!(
+                        "Skipped {} unparsable lines in {}",
+                        log.skipped.len(),
+                        path.display()
+                    );
+                }
+                records.extend(log.records);
             }
             Err(e) => {
-                print!("Error fetching vitals: {}\r\n", e);
-                print!("\r\n  Press ESC or Ctrl+C to exit\r\n");
+                eprintln!("Error reading {}: {}", path.display(), e);
+                std::process::exit(1);
             }
         }
-        stdout.flush().unwrap();
+    }
+    records.sort_by_key(|record| record.timestamp);
+    records
+}
 
-        // Check for key press with configured delay timeout
-        if event::poll(Duration::from_secs(delay)).unwrap()
-            && let Event::Key(key_event) = event::read().unwrap()
-        {
-            match key_event.code {
-                KeyCode::Esc => break,
-                KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                    break;
-                }
-                _ => {}
+/// Expand the glob `patterns` to the files they match, in order.
+fn expand_globs(patterns: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let paths =
+            glob::glob(pattern).map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+        let mut matched: Vec<PathBuf> = paths
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Error reading {}: {}", pattern, e))?;
+        if matched.is_empty() {
+            return Err(format!("No files match '{}'", pattern));
+        }
+        matched.sort();
+        for path in matched {
+            if !files.contains(&path) {
+                files.push(path);
             }
         }
     }
+    Ok(files)
+}
 
-    terminal::disable_raw_mode().expect("Failed to disable raw mode");
-    execute!(stdout, Clear(ClearType::All), MoveTo(0, 0)).unwrap();
+fn run_import(patterns: &[String], device: Option<String>, args: &Args, config: &Config) {
+    if let Err(e) = import_logs(patterns, device, args, config) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
 
-fn run_version(addr: &str) {
-    match get_version(addr) {
-        Ok(version) => {
-            println!("Tesla Wall Connector Version Info:");
-            println!("  Firmware Version: {}", version.firmware_version);
-            println!("  Git Branch:       {}", version.git_branch);
-            println!("  Part Number:      {}", version.part_number);
-            println!("  Serial Number:    {}", version.serial_number);
-            println!(
-                "  Web Service:      {}",
-                version.web_service.as_deref().unwrap_or("none")
-            );
+/// Import the files matching `patterns` into the `--db` of `args`, reporting
+/// what was imported and skipped per file.
+fn import_logs(
+    patterns: &[String],
+    device: Option<String>,
+    args: &Args,
+    config: &Config,
+) -> Result<(), String> {
+    /// Skipped lines listed per file, the rest are only counted.
+    const SHOWN: usize = 10;
+
+    let files = expand_globs(patterns)?;
+    let device = match device {
+        Some(device) => device,
+        None if config.devices.len() == 1 => config.devices.keys().next().unwrap().clone(),
+        None => return Err("--device is required unless [devices] has a single entry".into()),
+    };
+    let store = open_store(args)?.ok_or("No database given, use --db or db in the config file")?;
+    let mut total = ImportStats::default();
+    let mut skipped = 0;
+    for path in &files {
+        let log =
+            LogFile::read(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        let stats = store
+            .import(&device, &path.display().to_string(), &log.records)
+            .map_err(|e| format!("Error importing {}: {}", path.display(), e))?;
+        println!(
+            "{}: {} records, {} imported, {} duplicates, {} lines skipped",
+            path.display(),
+            log.records.len(),
+            stats.imported,
+            stats.duplicates,
+            log.skipped.len()
+        );
+        for line in log.skipped.iter().take(SHOWN) {
+            println!("  line {}: {}", line.line, line.reason);
         }
-        Err(e) => {
-            eprintln!("Error fetching version: {}", e);
-            std::process::exit(1);
+        if log.skipped.len() > SHOWN {
+            println!("  ... and {} more", log.skipped.len() - SHOWN);
         }
+        total.imported += stats.imported;
+        total.duplicates += stats.duplicates;
+        skipped += log.skipped.len();
+    }
+    if files.len() > 1 {
+        println!(
+            "Total: {} files, {} imported, {} duplicates, {} lines skipped",
+            files.len(),
+            total.imported,
+            total.duplicates,
+            skipped
+        );
+    }
+    Ok(())
+}
+
+fn run_sessions(files: &[PathBuf], units: Units, tariff: Option<&Tariff>, by: Option<&str>) {
+    let records = read_logs(files);
+    let samples = records.iter().filter_map(|record| match &record.payload {
+        Payload::Vitals(vitals) => Some((record.timestamp, vitals)),
+        _ => None,
+    });
+    if let Some(tariff) = tariff {
+        let mut tracker = CostTracker::new(tariff.clone());
+        let mut sessions: Vec<SessionCost> = samples
+            .filter_map(|(timestamp, vitals)| tracker.push(timestamp, vitals))
+            .collect();
+        sessions.extend(tracker.finish());
+        if sessions.is_empty() {
+            println!("No sessions found");
+            return;
+        }
+        match by {
+            Some("day") => println!("{}", format_cost_summary(tracker.days(), tariff, false)),
+            Some("month") => println!("{}", format_cost_summary(tracker.months(), tariff, true)),
+            _ => println!("{}", format_session_costs(&sessions, tariff)),
+        }
+        return;
+    }
+    if by.is_some() {
+        eprintln!("--by needs a [tariff] in the config file");
+        std::process::exit(1);
+    }
+
+    let sessions = detect_sessions(samples);
+    if sessions.is_empty() {
+        println!("No sessions found");
+        return;
+    }
+    println!("{}", format_sessions(&sessions, units));
+    let energy_wh: f64 = sessions.iter().map(|s| s.energy_wh).sum();
+    println!(
+        "\n{} session{}, {:.3} kWh delivered",
+        sessions.len(),
+        if sessions.len() == 1 { "" } else { "s" },
+        energy_wh / 1000.0
+    );
+}
+
+/// Fill in the settings of `config` that weren't given on the command line.
+fn apply_config(args: &mut Args, config: &Config, matches: &ArgMatches) {
+    let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    if args.command.is_none() {
+        args.command = config.command.clone();
+    }
+    if args.log.is_none() {
+        args.log = config.log.clone();
+    }
+    if args.db.is_none() {
+        args.db = config.db.clone();
+    }
+    if let Some(log_format) = config.log_format
+        && !from_cli("log_format")
+    {
+        args.log_format = log_format;
+    }
+    if let Some(delay) = config.delay
+        && !from_cli("delay")
+    {
+        args.delay = delay;
+    }
+    if let Some(units) = config.units
+        && !from_cli("units")
+    {
+        args.units = units;
+    }
+    if let Some(format) = config.format
+        && !from_cli("format")
+    {
+        args.format = format;
+    }
+    if let Some(connect_timeout) = config.http.connect_timeout
+        && !from_cli("connect_timeout")
+    {
+        args.connect_timeout = connect_timeout;
+    }
+    if let Some(read_timeout) = config.http.read_timeout
+        && !from_cli("read_timeout")
+    {
+        args.read_timeout = read_timeout;
+    }
+    if let Some(retries) = config.http.retries
+        && !from_cli("retries")
+    {
+        args.retries = retries;
+    }
+    if let Some(backoff) = config.http.backoff
+        && !from_cli("backoff")
+    {
+        args.backoff = backoff;
+    }
+    if let Some(cache) = config.exporter.cache
+        && !from_cli("cache")
+    {
+        args.cache = cache;
+    }
+}
+
+/// Read the file of --config or the default one.
+fn read_config(matches: &ArgMatches) -> Result<Config, String> {
+    let path = matches.get_one::<PathBuf>("config");
+    match path {
+        Some(path) => Config::read(path),
+        None => Config::read_default(),
+    }
+    .map_err(|e| {
+        let path = path.cloned().or_else(Config::default_path);
+        format!(
+            "Error reading config {}: {}",
+            path.unwrap_or_default().display(),
+            e
+        )
+    })
+}
+
+/// Apply the HTTP options to the client of `device` and store its
+/// responses in `store`.
+fn configure_device(
+    device: &mut Device,
+    args: &Args,
+    store: Option<&Arc<Store>>,
+) -> Result<(), Error> {
+    let retry = RetryPolicy::new(args.retries).with_backoff(Duration::from_millis(args.backoff));
+    let mut client = device
+        .client
+        .clone()
+        .with_connect_timeout(Duration::from_millis(args.connect_timeout))?
+        .with_timeout(Duration::from_millis(args.read_timeout))
+        .with_retry(retry);
+    if let Some(store) = store {
+        client = client.with_recorder(Arc::new(store.recorder(&device.name)));
+    }
+    device.client = client;
+    Ok(())
+}
+
+/// The database of --db, if given.
+fn open_store(args: &Args) -> Result<Option<Arc<Store>>, String> {
+    let Some(path) = &args.db else {
+        return Ok(None);
+    };
+    match Store::open(path) {
+        Ok(store) => Ok(Some(Arc::new(store))),
+        Err(e) => Err(format!("Error opening database {}: {}", path.display(), e)),
     }
 }
 
 fn main() {
+    // Only reliable while the process has a single thread
+    let local_offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
     let cmd = Args::command().mut_arg("command", |a| a.help(format_commands_help()));
-    let args = Args::from_arg_matches(&cmd.get_matches()).expect("Failed to parse arguments");
+    let matches = cmd.get_matches();
+    let mut args = Args::from_arg_matches(&matches).expect("Failed to parse arguments");
+
+    let config = match read_config(&matches) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    apply_config(&mut args, &config, &matches);
+    let tariff = config
+        .tariff
+        .clone()
+        .map(|tariff| tariff.with_local_offset(local_offset));
 
     // Initialize logging if log file specified
     if let Some(ref log_path) = args.log
-        && let Err(e) = init_logging(log_path)
+        && let Err(e) = init_logging(log_path, args.log_format)
     {
         eprintln!("Failed to initialize logging: {}", e);
         std::process::exit(1);
     }
 
-    let command = match match_command(&args.command) {
+    if let Some(tool) = args.tool.take() {
+        match tool {
+            #[cfg(unix)]
+            Tool::Daemon => run_daemon(&matches, config),
+            Tool::Replay { file, speed, step } => run_replay(&file, speed, step, args.units),
+            Tool::Sessions { files, by } => {
+                run_sessions(&files, args.units, tariff.as_ref(), by.as_deref())
+            }
+            Tool::Import { files, device } => run_import(&files, device, &args, &config),
+            Tool::Discover {
+                cidr,
+                port,
+                timeout,
+                concurrency,
+            } => run_discover(&cidr, &port, timeout, concurrency),
+            Tool::Simulate {
+                file,
+                listen,
+                speed,
+            } => run_simulate(file.as_deref(), &listen, speed),
+        }
+        return;
+    }
+    // Required unless a tool is given
+    let addr = args.addr.take().expect("addr is required");
+    let Some(command) = args.command.take() else {
+        eprintln!("No command given and no default command in the config file");
+        std::process::exit(1);
+    };
+
+    let command = match match_command(&command) {
         Ok(cmd) => cmd,
         Err(e) => {
             eprintln!("{}", e);
@@ -442,11 +1190,111 @@ fn main() {
         }
     };
 
+    let store = match open_store(&args) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let devices = parse_devices(&addr, &config.devices).and_then(|mut devices| {
+        for device in &mut devices {
+            configure_device(device, &args, store.as_ref())?;
+        }
+        Ok(devices)
+    });
+    let devices = match devices {
+        Ok(devices) => devices,
+        Err(e) => {
+            eprintln!("Invalid wall connector address: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if command == "overview" {
+        run_overview(&devices, args.loop_mode, args.delay, args.units);
+        return;
+    }
+    if command == "watch" {
+        let webhook = args.webhook.map(|url| match Webhook::new(&url) {
+            Ok(webhook) => webhook,
+            Err(e) => {
+                eprintln!("Invalid webhook '{}': {}", url, e);
+                std::process::exit(1);
+            }
+        });
+        run_watch(&devices, args.rules, webhook, args.delay);
+        return;
+    }
+    let mut printer = Printer::new(args.format, args.units);
+    if devices.len() > 1 {
+        if args.loop_mode
+            || matches!(
+                command,
+                "cost" | "dashboard" | "exporter" | "mqtt" | "serve"
+            )
+        {
+            eprintln!(
+                "{}{} works with a single wall connector, use overview for several",
+                command,
+                if args.loop_mode { " in loop mode" } else { "" }
+            );
+            std::process::exit(1);
+        }
+        for (i, device) in devices.iter().enumerate() {
+            if printer.format() == OutputFormat::Text || command == "alerts" {
+                if i > 0 {
+                    println!();
+                }
+                println!("{} ({})", device.name, device.client.addr());
+            } else {
+                printer.device = Some(device.name.clone());
+            }
+            match command {
+                "alerts" => run_alerts(&device.client, false, args.delay),
+                _ => run_endpoint(&device.client, command, false, args.delay, &mut printer),
+            }
+        }
+        return;
+    }
+    let client = devices.into_iter().next().unwrap().client;
+
     match command {
-        "lifetime" => run_lifetime(&args.addr),
-        "version" => run_version(&args.addr),
-        "vitals" => run_vitals(&args.addr, args.loop_mode, args.delay),
-        "wifi_status" => run_wifi_status(&args.addr),
+        "alerts" => run_alerts(&client, args.loop_mode, args.delay),
+        "cost" => match tariff {
+            Some(tariff) => run_cost(&client, tariff, args.loop_mode, args.delay),
+            None => {
+                eprintln!("cost needs a [tariff] in the config file");
+                std::process::exit(1);
+            }
+        },
+        "dashboard" => run_dashboard(client, args.delay, args.window, args.units),
+        "exporter" => {
+            let listen = args.listen.or(config.exporter.listen);
+            let listen = listen.as_deref().unwrap_or(exporter::DEFAULT_LISTEN);
+            run_exporter(client, listen, args.cache)
+        }
+        "serve" => {
+            let listen = args.listen.or(config.serve.listen);
+            let listen = listen.as_deref().unwrap_or(web::DEFAULT_LISTEN);
+            run_serve(client, listen, args.delay, args.window)
+        }
+        "mqtt" => {
+            let mut config = match MqttConfig::new(&args.broker) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Invalid broker '{}': {}", args.broker, e);
+                    std::process::exit(1);
+                }
+            };
+            config.credentials = args.mqtt_user.zip(args.mqtt_password);
+            config.base_topic = args.mqtt_topic;
+            config.discovery_prefix = args.discovery_prefix;
+            run_mqtt(&client, config, args.delay);
+        }
+        "lifetime" | "version" | "vitals" | "wifi_status" => {
+            run_endpoint(&client, command, args.loop_mode, args.delay, &mut printer)
+        }
         _ => unreachable!(),
     }
 }
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+use crate::alert::Alert;
+use crate::evse::{ConfigStatus, EvseState, NotReadyReason};
+
+/// Response of `/api/1/version`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Version {
+    pub firmware_version: String,
+    pub git_branch: String,
+    pub part_number: String,
+    pub serial_number: String,
+    pub web_service: Option<String>,
+}
+
+/// Response of `/api/1/wifi_status`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WifiStatus {
+    /// Base64 encoded SSID, see [`crate::format::decode_ssid`]
+    pub wifi_ssid: String,
+    pub wifi_signal_strength: i32,
+    pub wifi_rssi: i32,
+    pub wifi_snr: i32,
+    pub wifi_connected: bool,
+    pub wifi_infra_ip: String,
+    pub internet: bool,
+    pub wifi_mac: String,
+}
+
+/// Response of `/api/1/lifetime`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Lifetime {
+    pub contactor_cycles: u32,
+    pub contactor_cycles_loaded: u32,
+    pub alert_count: u32,
+    pub thermal_foldbacks: u32,
+    pub avg_startup_temp: f64,
+    pub charge_starts: u32,
+    pub energy_wh: u64,
+    pub connector_cycles: u32,
+    pub uptime_s: u64,
+    pub charging_time_s: u64,
+}
+
+/// Response of `/api/1/vitals`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Vitals {
+    pub contactor_closed: bool,
+    pub vehicle_connected: bool,
+    pub session_s: u64,
+    pub grid_v: f64,
+    pub grid_hz: f64,
+    pub vehicle_current_a: f64,
+    #[serde(rename = "currentA_a")]
+    pub current_a_a: f64,
+    #[serde(rename = "currentB_a")]
+    pub current_b_a: f64,
+    #[serde(rename = "currentC_a")]
+    pub current_c_a: f64,
+    #[serde(rename = "currentN_a")]
+    pub current_n_a: f64,
+    #[serde(rename = "voltageA_v")]
+    pub voltage_a_v: f64,
+    #[serde(rename = "voltageB_v")]
+    pub voltage_b_v: f64,
+    #[serde(rename = "voltageC_v")]
+    pub voltage_c_v: f64,
+    pub relay_coil_v: f64,
+    pub pcba_temp_c: f64,
+    pub handle_temp_c: f64,
+    pub mcu_temp_c: f64,
+    pub uptime_s: u64,
+    pub input_thermopile_uv: i32,
+    pub prox_v: f64,
+    pub pilot_high_v: f64,
+    pub pilot_low_v: f64,
+    pub session_energy_wh: f64,
+    pub config_status: ConfigStatus,
+    pub evse_state: EvseState,
+    pub current_alerts: Vec<Alert>,
+    pub evse_not_ready_reasons: Vec<NotReadyReason>,
+}
@@ -0,0 +1,325 @@
+//! MQTT publisher with Home Assistant discovery.
+//!
+//! Every field of the vitals, lifetime and wifi_status endpoints is published
+//! to its own topic, `<base>/<serial>/<endpoint>/<field>`, e.g.
+//! `tesla_wallcon/TWC123456789/vitals/grid_v`. Discovery config payloads are
+//! published retained under `<discovery prefix>/<component>/<serial>/...` so
+//! Home Assistant creates one device with a sensor per field.
+//!
+//! Messages are queued for a background connection thread. While the broker
+//! is unreachable that queue fills up, after which new messages are dropped
+//! and counted in [`MqttPublisher::dropped`] rather than stalling the poll
+//! loop.
+
+use rumqttc::{Client, ClientError, Event, LastWill, MqttOptions, Outgoing, QoS};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::models::Version;
+
+pub const DEFAULT_BROKER_PORT: u16 = 1883;
+pub const DEFAULT_BASE_TOPIC: &str = "tesla_wallcon";
+pub const DEFAULT_DISCOVERY_PREFIX: &str = "homeassistant";
+/// Messages queued for the connection thread before new ones are dropped.
+pub const QUEUE_CAPACITY: usize = 256;
+
+/// Connection and topic settings of an [`MqttPublisher`].
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub credentials: Option<(String, String)>,
+    pub base_topic: String,
+    pub discovery_prefix: String,
+}
+
+impl MqttConfig {
+    /// Settings for the broker at `broker`, given as `host` or `host:port`.
+    pub fn new(broker: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (host, port) = match broker.rsplit_once(':') {
+            Some((host, port)) => (host.to_string(), port.parse()?),
+            None => (broker.to_string(), DEFAULT_BROKER_PORT),
+        };
+        Ok(MqttConfig {
+            host,
+            port,
+            client_id: format!("tesla-wallcon-monitor-{}", std::process::id()),
+            credentials: None,
+            base_topic: DEFAULT_BASE_TOPIC.to_string(),
+            discovery_prefix: DEFAULT_DISCOVERY_PREFIX.to_string(),
+        })
+    }
+
+    /// State topic of `field` of the device `serial`.
+    pub fn topic(&self, serial: &str, endpoint: &str, field: &str) -> String {
+        format!("{}/{}/{}/{}", self.base_topic, serial, endpoint, field)
+    }
+
+    /// Topic holding `online` or `offline` for the device `serial`.
+    pub fn availability_topic(&self, serial: &str) -> String {
+        format!("{}/{}/availability", self.base_topic, serial)
+    }
+
+    /// Home Assistant discovery configs for every field of `sample`, a
+    /// response of `endpoint`, as `(topic, config)` pairs. `counters` are
+    /// fields that only ever increase.
+    pub fn discovery_configs<T: Serialize>(
+        &self,
+        version: &Version,
+        endpoint: &str,
+        sample: &T,
+        counters: &[&str],
+    ) -> Result<Vec<(String, Value)>, Box<dyn std::error::Error>> {
+        let Value::Object(fields) = serde_json::to_value(sample)? else {
+            return Err(format!("{} doesn't serialize to an object", endpoint).into());
+        };
+        let serial = &version.serial_number;
+        let device = json!({
+            "identifiers": [serial],
+            "name": format!("Tesla Wall Connector {}", serial),
+            "manufacturer": "Tesla",
+            "model": version.part_number,
+            "serial_number": serial,
+            "sw_version": version.firmware_version,
+        });
+        let mut configs = Vec::new();
+        for (field, value) in &fields {
+            let component = match value {
+                Value::Bool(_) => "binary_sensor",
+                Value::Number(_) | Value::String(_) => "sensor",
+                // Lists are published as JSON but don't make useful sensors
+                _ => continue,
+            };
+            let object_id = format!("{}_{}", endpoint, field);
+            let mut config = json!({
+                "name": field.replace('_', " "),
+                "unique_id": format!("{}_{}", serial, object_id),
+                "state_topic": self.topic(serial, endpoint, field),
+                "availability_topic": self.availability_topic(serial),
+                "device": device,
+            });
+            if let Value::Number(_) = value {
+                let meta = field_meta(field);
+                let state_class = if counters.contains(&field.as_str()) {
+                    "total_increasing"
+                } else {
+                    "measurement"
+                };
+                config["state_class"] = json!(state_class);
+                if let Some(unit) = meta.unit {
+                    config["unit_of_measurement"] = json!(unit);
+                }
+                if let Some(device_class) = meta.device_class {
+                    config["device_class"] = json!(device_class);
+                }
+            }
+            let topic = format!(
+                "{}/{}/{}/{}/config",
+                self.discovery_prefix, component, serial, object_id
+            );
+            configs.push((topic, config));
+        }
+        Ok(configs)
+    }
+}
+
+/// Home Assistant metadata of a field, derived from its name.
+struct FieldMeta {
+    unit: Option<&'static str>,
+    device_class: Option<&'static str>,
+}
+
+fn field_meta(field: &str) -> FieldMeta {
+    let (unit, device_class) = match field {
+        "wifi_rssi" => (Some("dBm"), Some("signal_strength")),
+        "wifi_snr" => (Some("dB"), None),
+        "wifi_signal_strength" => (Some("%"), None),
+        "avg_startup_temp" => (Some("°C"), Some("temperature")),
+        "energy_wh" | "session_energy_wh" => (Some("Wh"), Some("energy")),
+        "input_thermopile_uv" => (Some("µV"), None),
+        f if f.ends_with("_temp_c") => (Some("°C"), Some("temperature")),
+        f if f.ends_with("_hz") => (Some("Hz"), Some("frequency")),
+        f if f.ends_with("_v") => (Some("V"), Some("voltage")),
+        f if f.ends_with("_a") => (Some("A"), Some("current")),
+        f if f.ends_with("_s") => (Some("s"), Some("duration")),
+        _ => (None, None),
+    };
+    FieldMeta { unit, device_class }
+}
+
+/// Text published for a field value, arrays and objects as JSON.
+fn payload(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Bool(b) => if *b { "ON" } else { "OFF" }.to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Publishes wall connector data to an MQTT broker.
+pub struct MqttPublisher {
+    client: Client,
+    config: MqttConfig,
+    serial: String,
+    connection: Mutex<Option<JoinHandle<()>>>,
+    closing: Arc<AtomicBool>,
+    dropped: AtomicU64,
+    dropping: AtomicBool,
+}
+
+impl MqttPublisher {
+    /// Connect to the broker, the connection is driven by a background
+    /// thread that reconnects after errors. `serial` names the device in the
+    /// topics, the availability topic is set to `offline` as last will.
+    pub fn connect(config: MqttConfig, serial: &str) -> Self {
+        let availability = config.availability_topic(serial);
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_last_will(LastWill::new(
+            &availability,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some((user, password)) = &config.credentials {
+            options.set_credentials(user, password);
+        }
+
+        let (client, mut connection) = Client::new(options, QUEUE_CAPACITY);
+        let closing = Arc::new(AtomicBool::new(false));
+        let stop = closing.clone();
+        let connection = std::thread::spawn(move || {
+            for event in connection.iter() {
+                match event {
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                    Ok(_) => {}
+                    // The queued disconnect is never sent without a broker
+                    Err(_) if stop.load(Ordering::Relaxed) => break,
+                    Err(e) => {
+                        log::warn!("mqtt connection error: {}", e);
+                        std::thread::sleep(Duration::from_secs(1));
+                    }
+                }
+            }
+        });
+
+        MqttPublisher {
+            client,
+            config,
+            serial: serial.to_string(),
+            connection: Mutex::new(Some(connection)),
+            closing,
+            dropped: AtomicU64::new(0),
+            dropping: AtomicBool::new(false),
+        }
+    }
+
+    /// Number of messages dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Queue a message without waiting, dropping it when the queue is full.
+    fn send(
+        &self,
+        topic: String,
+        retain: bool,
+        payload: impl Into<Vec<u8>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self
+            .client
+            .try_publish(topic, QoS::AtLeastOnce, retain, payload)
+        {
+            Ok(()) => {
+                if self.dropping.swap(false, Ordering::Relaxed) {
+                    log::info!("mqtt queue drained, publishing again");
+                }
+                Ok(())
+            }
+            Err(ClientError::TryRequest(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                if !self.dropping.swap(true, Ordering::Relaxed) {
+                    log::warn!("mqtt queue full, dropping messages until the broker is back");
+                }
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Mark the device online or offline.
+    pub fn publish_availability(&self, online: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let state = if online { "online" } else { "offline" };
+        self.send(self.config.availability_topic(&self.serial), true, state)
+    }
+
+    /// Publish every top level field of `data`, the response of `endpoint`.
+    pub fn publish<T: Serialize>(
+        &self,
+        endpoint: &str,
+        data: &T,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Value::Object(fields) = serde_json::to_value(data)? else {
+            return Err(format!("{} doesn't serialize to an object", endpoint).into());
+        };
+        for (field, value) in &fields {
+            self.send(
+                self.config.topic(&self.serial, endpoint, field),
+                false,
+                payload(value),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Publish the retained [`MqttConfig::discovery_configs`] of `sample`.
+    pub fn publish_discovery<T: Serialize>(
+        &self,
+        version: &Version,
+        endpoint: &str,
+        sample: &T,
+        counters: &[&str],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let configs = self
+            .config
+            .discovery_configs(version, endpoint, sample, counters)?;
+        for (topic, config) in configs {
+            self.send(topic, true, config.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Mark the device offline and close the connection once everything
+    /// queued has been sent, or right away when the broker is unreachable.
+    pub fn disconnect(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.publish_availability(false)?;
+        self.closing.store(true, Ordering::Relaxed);
+        if self.client.try_disconnect().is_err() {
+            // Full queue, leave the thread to end at its next connection error
+            log::warn!("mqtt queue full, closing without sending it");
+            return Ok(());
+        }
+        if let Some(connection) = self.connection.lock().unwrap().take() {
+            let _ = connection.join();
+        }
+        Ok(())
+    }
+}
+
+/// Lifetime fields that only ever increase.
+pub const LIFETIME_COUNTERS: &[&str] = &[
+    "contactor_cycles",
+    "contactor_cycles_loaded",
+    "alert_count",
+    "thermal_foldbacks",
+    "charge_starts",
+    "energy_wh",
+    "connector_cycles",
+    "uptime_s",
+    "charging_time_s",
+];
@@ -0,0 +1,199 @@
+//! Output of the lifetime, version, vitals and wifi_status commands in the
+//! formats of `--format`.
+//!
+//! Besides the aligned text, records can be printed as JSON, YAML, CSV or
+//! `key=value` lines. These always use the field names and units of the
+//! wall connector's API (`grid_v`, `handle_temp_c`, `energy_wh`, ...) in the
+//! order the API returns them, whatever `--units` says, so scripts don't
+//! break when the display settings change. `wifi_status` gains a
+//! `wifi_ssid_decoded` field next to the base64 encoded `wifi_ssid`.
+//!
+//! Several records, from several devices or loop mode, are printed as one
+//! JSON object per line, YAML documents separated by `---`, CSV rows under a
+//! single header and `key=value` blocks separated by a blank line.
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::str::FromStr;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::format::{Units, decode_ssid, format_payload};
+use crate::logfile::Payload;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Yaml,
+    Csv,
+    #[serde(rename = "key=value")]
+    KeyValue,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "csv" => Ok(OutputFormat::Csv),
+            "key=value" | "kv" => Ok(OutputFormat::KeyValue),
+            _ => Err(format!(
+                "unknown format '{}', expected text, json, yaml, csv or key=value",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Csv => "csv",
+            OutputFormat::KeyValue => "key=value",
+        })
+    }
+}
+
+/// Fields of `payload` in API order, with the decoded SSID added.
+pub fn payload_fields(payload: &Payload) -> Vec<(String, Value)> {
+    let Value::Object(map) = payload.to_value() else {
+        unreachable!("payloads serialize to objects");
+    };
+    let mut fields = Vec::with_capacity(map.len() + 1);
+    for (name, value) in map {
+        let decoded = match (&*name, &value, payload) {
+            ("wifi_ssid", Value::String(ssid), Payload::WifiStatus(_)) => Some(decode_ssid(ssid)),
+            _ => None,
+        };
+        fields.push((name, value));
+        if let Some(decoded) = decoded {
+            fields.push(("wifi_ssid_decoded".to_string(), Value::String(decoded)));
+        }
+    }
+    fields
+}
+
+/// Renders payloads one after the other in an [`OutputFormat`].
+#[derive(Debug, Clone)]
+pub struct Printer {
+    format: OutputFormat,
+    units: Units,
+    /// Name of the device the next records come from, added as a leading
+    /// `device` field in the machine readable formats.
+    pub device: Option<String>,
+    records: usize,
+}
+
+impl Printer {
+    pub fn new(format: OutputFormat, units: Units) -> Self {
+        Printer {
+            format,
+            units,
+            device: None,
+            records: 0,
+        }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Render `payload`, a `timestamp` leads the record in the machine
+    /// readable formats.
+    pub fn render(&mut self, payload: &Payload, timestamp: Option<OffsetDateTime>) -> String {
+        if self.format == OutputFormat::Text {
+            return format_payload(payload, self.units);
+        }
+
+        let mut fields = Vec::new();
+        if let Some(timestamp) = timestamp {
+            let timestamp = timestamp.format(&Rfc3339).unwrap_or_default();
+            fields.push(("timestamp".to_string(), Value::String(timestamp)));
+        }
+        if let Some(device) = &self.device {
+            fields.push(("device".to_string(), Value::String(device.clone())));
+        }
+        fields.extend(payload_fields(payload));
+
+        let first = self.records == 0;
+        self.records += 1;
+        match self.format {
+            OutputFormat::Text => unreachable!(),
+            OutputFormat::Json => {
+                Value::Object(fields.into_iter().collect::<Map<_, _>>()).to_string()
+            }
+            OutputFormat::Yaml => {
+                let lines = fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value));
+                let document = lines.collect::<Vec<_>>().join("\n");
+                if first {
+                    document
+                } else {
+                    format!("---\n{}", document)
+                }
+            }
+            OutputFormat::Csv => {
+                let row = |cells: Vec<String>| {
+                    cells
+                        .iter()
+                        .map(|cell| csv_quote(cell))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                };
+                let values = row(fields.iter().map(|(_, value)| flat(value)).collect());
+                if first {
+                    let names = row(fields.into_iter().map(|(name, _)| name).collect());
+                    format!("{}\n{}", names, values)
+                } else {
+                    values
+                }
+            }
+            OutputFormat::KeyValue => {
+                let lines = fields
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, kv_quote(&flat(value))));
+                let block = lines.collect::<Vec<_>>().join("\n");
+                if first { block } else { format!("\n{}", block) }
+            }
+        }
+    }
+}
+
+/// `value` as a single cell, arrays are joined with `;` and objects are
+/// written as JSON.
+fn flat(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(flat).collect::<Vec<_>>().join(";"),
+        value => value.to_string(),
+    }
+}
+
+fn csv_quote(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Values with spaces, quotes or nothing at all are written as JSON strings.
+fn kv_quote(value: &str) -> String {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"') {
+        Value::String(value.to_string()).to_string()
+    } else {
+        value.to_string()
+    }
+}
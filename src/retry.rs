@@ -0,0 +1,134 @@
+//! Retrying failed requests with jittered exponential backoff.
+//!
+//! The wall connector sits on Wi-Fi and drops the odd request. Failures
+//! that may go away on their own (connection errors, timeouts, truncated
+//! bodies and 5xx responses) are retried, the wait doubling after each
+//! attempt up to a maximum. Each wait is drawn from the upper half of the
+//! current backoff so clients polling several connectors don't retry in
+//! lockstep.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Default wait before the first retry.
+pub const DEFAULT_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Default longest wait between two attempts.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts after the first one, 0 never retries.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every further one.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl RetryPolicy {
+    /// Retry up to `retries` times with the default backoff.
+    pub fn new(retries: u32) -> Self {
+        RetryPolicy {
+            retries,
+            backoff: DEFAULT_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+
+    /// Fail on the first error.
+    pub fn none() -> Self {
+        Self::new(0)
+    }
+
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Backoff before retry number `retry` (0 for the first one) without
+    /// jitter.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(1 << retry.min(16))
+            .min(self.max_backoff)
+    }
+
+    /// How long to wait before retry number `retry`, between half and all of
+    /// [`RetryPolicy::backoff`].
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+        let jitter = random_fraction();
+        backoff / 2 + backoff.mul_f64(jitter / 2.0)
+    }
+
+    /// Run `attempt` until it succeeds, fails for good or runs out of
+    /// retries. `retryable` decides which errors are worth another attempt.
+    pub fn run<T, E>(
+        &self,
+        mut attempt: impl FnMut() -> Result<T, E>,
+        retryable: impl Fn(&E) -> bool,
+    ) -> Result<T, E> {
+        let mut retry = 0;
+        loop {
+            match attempt() {
+                Err(e) if retry < self.retries && retryable(&e) => {
+                    std::thread::sleep(self.delay(retry));
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Async counterpart of [`RetryPolicy::run`], waiting on the tokio
+    /// timer instead of blocking the thread.
+    #[cfg(feature = "async")]
+    pub async fn run_async<T, E, F: Future<Output = Result<T, E>>>(
+        &self,
+        mut attempt: impl FnMut() -> F,
+        retryable: impl Fn(&E) -> bool,
+    ) -> Result<T, E> {
+        let mut retry = 0;
+        loop {
+            match attempt().await {
+                Err(e) if retry < self.retries && retryable(&e) => {
+                    tokio::time::sleep(self.delay(retry)).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether a failed request may succeed when repeated.
+pub fn is_transient(error: &reqwest::Error) -> bool {
+    match error.status() {
+        Some(status) => status.is_server_error(),
+        None => {
+            error.is_connect()
+                || error.is_timeout()
+                || error.is_request()
+                || error.is_body()
+                || error.is_decode()
+        }
+    }
+}
+
+/// A number in `[0, 1)`, random enough to spread retries.
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
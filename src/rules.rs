@@ -0,0 +1,406 @@
+//! Threshold and change rules over [`Vitals`] fields.
+//!
+//! Rules are written as text, one of:
+//!
+//! - `FIELD OP VALUE [for DURATION] [hysteresis DELTA]` with `OP` one of
+//!   `>`, `>=`, `<`, `<=`, `==`, `!=`, e.g. `handle_temp_c > 60 for 2m`.
+//!   The rule fires once the condition held for `DURATION` and resolves when
+//!   it no longer holds, for the ordering operators only after the value
+//!   moved `DELTA` back past the threshold so a value hovering around it
+//!   doesn't flap.
+//! - `FIELD new [cooldown DURATION]` fires for each element of a list field,
+//!   e.g. `current_alerts`, that wasn't there in the previous sample.
+//! - `FIELD changed [cooldown DURATION]` fires when the value changes.
+//!
+//! `new` and `changed` notify at most once per cooldown (default 5 minutes)
+//! for the same element or rule; changes within the cooldown are coalesced
+//! into one notification once it expired. Values present in the first sample
+//! aren't reported as new or changed.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+use crate::models::Vitals;
+
+/// Cooldown of `new` and `changed` rules unless given.
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+/// Parse a duration like `90`, `90s`, `2m` or `1h`, plain numbers are
+/// seconds.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(i) => text.split_at(i),
+        None => (text, "s"),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", text))?;
+    let seconds = match unit {
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => {
+            return Err(format!(
+                "invalid duration unit in '{}', use s, m or h",
+                text
+            ));
+        }
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("invalid duration '{}': {}", text, e))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+}
+
+impl Op {
+    fn parse(text: &str) -> Option<Self> {
+        Some(match text {
+            ">" => Op::Gt,
+            ">=" => Op::Ge,
+            "<" => Op::Lt,
+            "<=" => Op::Le,
+            "==" => Op::Eq,
+            "!=" => Op::Ne,
+            _ => return None,
+        })
+    }
+
+    /// Whether `value OP threshold` holds, with the threshold moved by
+    /// `slack` towards clearing for the ordering operators.
+    fn holds(self, value: &Value, threshold: &Value, slack: f64) -> bool {
+        let numbers = value.as_f64().zip(threshold.as_f64());
+        match (self, numbers) {
+            (Op::Gt, Some((v, t))) => v > t - slack,
+            (Op::Ge, Some((v, t))) => v >= t - slack,
+            (Op::Lt, Some((v, t))) => v < t + slack,
+            (Op::Le, Some((v, t))) => v <= t + slack,
+            (Op::Eq, Some((v, t))) => v == t,
+            (Op::Ne, Some((v, t))) => v != t,
+            (Op::Eq, None) => value == threshold,
+            (Op::Ne, None) => value != threshold,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Threshold {
+        op: Op,
+        value: Value,
+        hold: Duration,
+        hysteresis: f64,
+    },
+    New {
+        cooldown: Duration,
+    },
+    Changed {
+        cooldown: Duration,
+    },
+}
+
+/// A parsed rule, displayed as the text it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub field: String,
+    pub condition: Condition,
+    text: String,
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let invalid = |why: &str| format!("invalid rule '{}': {}", text, why);
+        let (field, rest) = words.split_first().ok_or_else(|| invalid("empty rule"))?;
+        let (kind, rest) = rest
+            .split_first()
+            .ok_or_else(|| invalid("missing condition"))?;
+
+        let mut options: HashMap<&str, &str> = HashMap::new();
+        let (threshold, mut rest) = match Op::parse(kind) {
+            Some(_) => {
+                let (value, rest) = rest.split_first().ok_or_else(|| invalid("missing value"))?;
+                (Some(*value), rest)
+            }
+            None => (None, rest),
+        };
+        while let [name, value, tail @ ..] = rest {
+            options.insert(name, value);
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            return Err(invalid(&format!("missing value after '{}'", rest[0])));
+        }
+        let allowed: &[&str] = match threshold {
+            Some(_) => &["for", "hysteresis"],
+            None => &["cooldown"],
+        };
+        if let Some(name) = options.keys().find(|name| !allowed.contains(name)) {
+            return Err(invalid(&format!("unexpected '{}'", name)));
+        }
+        let duration = |name: &str, default: Duration| {
+            options
+                .get(name)
+                .map(|d| parse_duration(d).map_err(|e| invalid(&e)))
+                .unwrap_or(Ok(default))
+        };
+
+        let condition = match (threshold, *kind) {
+            (Some(value), op) => Condition::Threshold {
+                op: Op::parse(op).unwrap(),
+                // Numbers and booleans compare as such, anything else as text
+                value: serde_json::from_str(value)
+                    .unwrap_or_else(|_| Value::String(value.to_string())),
+                hold: duration("for", Duration::ZERO)?,
+                hysteresis: match options.get("hysteresis") {
+                    Some(delta) => delta
+                        .parse()
+                        .map_err(|_| invalid(&format!("invalid hysteresis '{}'", delta)))?,
+                    None => 0.0,
+                },
+            },
+            (None, "new") => Condition::New {
+                cooldown: duration("cooldown", DEFAULT_COOLDOWN)?,
+            },
+            (None, "changed") => Condition::Changed {
+                cooldown: duration("cooldown", DEFAULT_COOLDOWN)?,
+            },
+            (None, other) => {
+                return Err(invalid(&format!(
+                    "unknown condition '{}', expected an operator, new or changed",
+                    other
+                )));
+            }
+        };
+        Ok(Rule {
+            field: field.to_string(),
+            condition,
+            text: words.join(" "),
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationState {
+    /// A threshold rule started to hold.
+    Firing,
+    /// A threshold rule stopped holding.
+    Resolved,
+    /// A `new` or `changed` rule saw a change.
+    Changed,
+}
+
+impl fmt::Display for NotificationState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NotificationState::Firing => "firing",
+            NotificationState::Resolved => "resolved",
+            NotificationState::Changed => "changed",
+        })
+    }
+}
+
+/// What is sent to the webhook, one per rule event.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub device: String,
+    pub rule: String,
+    pub state: NotificationState,
+    pub field: String,
+    pub value: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Value>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+}
+
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {}: {} ({} = {})",
+            self.state, self.device, self.rule, self.field, self.value
+        )?;
+        if let Some(previous) = &self.previous {
+            write!(f, " was {}", previous)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+enum RuleState {
+    #[default]
+    Idle,
+    Pending(OffsetDateTime),
+    Firing,
+}
+
+#[derive(Debug, Default)]
+struct Tracked {
+    state: RuleState,
+    /// Last value of `new` rules, last reported value of `changed` rules.
+    baseline: Option<Value>,
+    /// When each element of a `new` rule was last reported, or the rule
+    /// itself under the empty key.
+    notified: HashMap<String, OffsetDateTime>,
+    /// Elements of a `new` rule that were new again within their cooldown,
+    /// reported once it expired.
+    pending: Vec<Value>,
+}
+
+fn cooled_down(last: Option<&OffsetDateTime>, now: OffsetDateTime, cooldown: Duration) -> bool {
+    last.is_none_or(|last| now - *last >= cooldown)
+}
+
+/// Evaluates rules against successive samples of one wall connector.
+#[derive(Debug)]
+pub struct RuleEngine {
+    device: String,
+    rules: Vec<(Rule, Tracked)>,
+}
+
+impl RuleEngine {
+    /// `device` names the wall connector in the notifications.
+    pub fn new(device: &str, rules: Vec<Rule>) -> Self {
+        RuleEngine {
+            device: device.to_string(),
+            rules: rules
+                .into_iter()
+                .map(|rule| (rule, Tracked::default()))
+                .collect(),
+        }
+    }
+
+    /// Check that every rule names a field of `vitals`.
+    pub fn validate(&self, vitals: &Vitals) -> Result<(), String> {
+        let sample = serde_json::to_value(vitals).map_err(|e| e.to_string())?;
+        for (rule, _) in &self.rules {
+            let Some(value) = sample.get(&rule.field) else {
+                return Err(format!(
+                    "unknown vitals field '{}' in '{}'",
+                    rule.field, rule
+                ));
+            };
+            if matches!(rule.condition, Condition::New { .. }) && !value.is_array() {
+                return Err(format!("'{}' is not a list, in '{}'", rule.field, rule));
+            }
+        }
+        Ok(())
+    }
+
+    /// Feed the sample taken at `timestamp`, returning the notifications it
+    /// triggered.
+    pub fn update(&mut self, timestamp: OffsetDateTime, vitals: &Vitals) -> Vec<Notification> {
+        let Ok(sample) = serde_json::to_value(vitals) else {
+            return Vec::new();
+        };
+        let mut notifications = Vec::new();
+        for (rule, tracked) in &mut self.rules {
+            let Some(value) = sample.get(&rule.field) else {
+                continue;
+            };
+            let mut notify = |state, value: &Value, previous: Option<Value>| {
+                notifications.push(Notification {
+                    device: self.device.clone(),
+                    rule: rule.to_string(),
+                    state,
+                    field: rule.field.clone(),
+                    value: value.clone(),
+                    previous,
+                    timestamp,
+                })
+            };
+            match &rule.condition {
+                Condition::Threshold {
+                    op,
+                    value: threshold,
+                    hold,
+                    hysteresis,
+                } => {
+                    let firing = matches!(tracked.state, RuleState::Firing);
+                    let slack = if firing { *hysteresis } else { 0.0 };
+                    let holds = op.holds(value, threshold, slack);
+                    let since = match tracked.state {
+                        RuleState::Pending(since) => since,
+                        _ => timestamp,
+                    };
+                    tracked.state = match (firing, holds) {
+                        (true, true) => RuleState::Firing,
+                        (true, false) => {
+                            notify(NotificationState::Resolved, value, None);
+                            RuleState::Idle
+                        }
+                        (false, false) => RuleState::Idle,
+                        (false, true) if timestamp - since >= *hold => {
+                            notify(NotificationState::Firing, value, None);
+                            RuleState::Firing
+                        }
+                        (false, true) => RuleState::Pending(since),
+                    };
+                }
+                Condition::New { cooldown } => {
+                    if let (Some(Value::Array(previous)), Value::Array(current)) =
+                        (&tracked.baseline, value)
+                    {
+                        for element in current.iter().filter(|e| !previous.contains(e)) {
+                            let key = element.to_string();
+                            if cooled_down(tracked.notified.get(&key), timestamp, *cooldown) {
+                                notify(NotificationState::Changed, element, None);
+                                tracked.notified.insert(key, timestamp);
+                                tracked.pending.retain(|pending| pending != element);
+                            } else if !tracked.pending.contains(element) {
+                                tracked.pending.push(element.clone());
+                            }
+                        }
+                    }
+                    tracked.baseline = Some(value.clone());
+                    let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut tracked.pending)
+                        .into_iter()
+                        .partition(|element| {
+                            let last = tracked.notified.get(&element.to_string());
+                            cooled_down(last, timestamp, *cooldown)
+                        });
+                    tracked.pending = waiting;
+                    for element in due {
+                        notify(NotificationState::Changed, &element, None);
+                        tracked.notified.insert(element.to_string(), timestamp);
+                    }
+                }
+                Condition::Changed { cooldown } => match &tracked.baseline {
+                    None => tracked.baseline = Some(value.clone()),
+                    Some(baseline) if baseline != value => {
+                        if cooled_down(tracked.notified.get(""), timestamp, *cooldown) {
+                            notify(NotificationState::Changed, value, Some(baseline.clone()));
+                            tracked.baseline = Some(value.clone());
+                            tracked.notified.insert(String::new(), timestamp);
+                        }
+                    }
+                    Some(_) => {}
+                },
+            }
+        }
+        notifications
+    }
+}
@@ -0,0 +1,214 @@
+//! Charging session detection from a stream of [`Vitals`].
+//!
+//! A session starts when `vehicle_connected` turns true and ends when it
+//! turns false. The connector also restarts a session without an unplug,
+//! e.g. when it retries a failed charge, which shows up as `session_s`
+//! going back to 0 while still connected; that ends the current session and
+//! starts a new one. `uptime_s` going backwards means the connector rebooted
+//! and ends the session as well.
+//!
+//! The charging time only counts the time between samples at most
+//! [`MAX_SAMPLE_GAP`] apart. Longer gaps, e.g. between two merged log files
+//! or while the monitor was down, say nothing about how long the contactor
+//! stayed closed.
+
+use serde::Serialize;
+use time::{Duration, OffsetDateTime};
+
+use crate::models::Vitals;
+
+/// Longest time between two samples that counts towards `charging_s`.
+pub const MAX_SAMPLE_GAP: Duration = Duration::minutes(10);
+
+/// Why a [`Session`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionEnd {
+    /// The vehicle was unplugged.
+    Unplugged,
+    /// `session_s` was reset while the vehicle stayed connected.
+    Restarted,
+    /// The connector rebooted.
+    Reboot,
+    /// Still connected at the end of the data.
+    InProgress,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    #[serde(with = "time::serde::rfc3339")]
+    pub plugged_in: OffsetDateTime,
+    /// First time the contactor closed.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub contactor_closed: Option<OffsetDateTime>,
+    /// Last time the contactor opened.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub contactor_opened: Option<OffsetDateTime>,
+    /// Time of the last sample of the session, the unplug time if `end` is
+    /// [`SessionEnd::Unplugged`].
+    #[serde(with = "time::serde::rfc3339")]
+    pub ended: OffsetDateTime,
+    pub end: SessionEnd,
+    pub energy_wh: f64,
+    /// Seconds the contactor was closed, see [`MAX_SAMPLE_GAP`].
+    pub charging_s: u64,
+    pub peak_current_a: f64,
+    /// Average `vehicle_current_a` over the samples with the contactor closed.
+    pub avg_current_a: f64,
+    pub peak_handle_temp_c: f64,
+    pub samples: usize,
+}
+
+impl Session {
+    fn start(timestamp: OffsetDateTime, vitals: &Vitals) -> Self {
+        Session {
+            plugged_in: timestamp,
+            contactor_closed: None,
+            contactor_opened: None,
+            ended: timestamp,
+            end: SessionEnd::InProgress,
+            energy_wh: 0.0,
+            charging_s: 0,
+            peak_current_a: 0.0,
+            avg_current_a: 0.0,
+            peak_handle_temp_c: vitals.handle_temp_c,
+            samples: 0,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.ended - self.plugged_in
+    }
+}
+
+/// Whether the connector started counting over since a sample with
+/// `uptime_s` and `session_s`: [`SessionEnd::Reboot`] after a reboot,
+/// [`SessionEnd::Restarted`] for a new session while the vehicle stayed
+/// connected.
+pub fn restart(uptime_s: u64, session_s: u64, vitals: &Vitals) -> Option<SessionEnd> {
+    if vitals.uptime_s < uptime_s {
+        Some(SessionEnd::Reboot)
+    } else if vitals.vehicle_connected && vitals.session_s < session_s {
+        Some(SessionEnd::Restarted)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug)]
+struct Previous {
+    timestamp: OffsetDateTime,
+    session_s: u64,
+    uptime_s: u64,
+    contactor_closed: bool,
+}
+
+/// Turns successive vitals samples into [`Session`]s.
+#[derive(Debug, Default)]
+pub struct SessionDetector {
+    current: Option<Session>,
+    closed_samples: usize,
+    current_sum: f64,
+    previous: Option<Previous>,
+}
+
+impl SessionDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next sample, returning the session it completed, if any.
+    pub fn push(&mut self, timestamp: OffsetDateTime, vitals: &Vitals) -> Option<Session> {
+        let mut completed = None;
+        if let Some(previous) = &self.previous
+            && let Some(end) = restart(previous.uptime_s, previous.session_s, vitals)
+        {
+            completed = self.end(end);
+        }
+
+        if self.current.is_none() && vitals.vehicle_connected {
+            self.current = Some(Session::start(timestamp, vitals));
+        }
+        if let Some(session) = &mut self.current {
+            // The connector keeps counting session_energy_wh until the next
+            // plug-in, so the unplug sample has the final value.
+            session.energy_wh = session.energy_wh.max(vitals.session_energy_wh);
+            session.peak_handle_temp_c = session.peak_handle_temp_c.max(vitals.handle_temp_c);
+            session.ended = timestamp;
+            session.samples += 1;
+            // Only the time between samples of this session counts, not the
+            // one before a restart or reboot started it
+            if let Some(previous) = &self.previous
+                && previous.contactor_closed
+                && session.plugged_in < timestamp
+            {
+                let closed = timestamp - previous.timestamp;
+                if closed <= MAX_SAMPLE_GAP {
+                    session.charging_s += closed.whole_seconds().max(0) as u64;
+                }
+            }
+            if vitals.contactor_closed {
+                session.contactor_closed.get_or_insert(timestamp);
+                session.peak_current_a = session.peak_current_a.max(vitals.vehicle_current_a);
+                self.closed_samples += 1;
+                self.current_sum += vitals.vehicle_current_a;
+            } else if self.previous.as_ref().is_some_and(|p| p.contactor_closed) {
+                session.contactor_opened = Some(timestamp);
+            }
+        }
+        if !vitals.vehicle_connected {
+            completed = completed.or_else(|| self.end(SessionEnd::Unplugged));
+        }
+
+        self.previous = Some(Previous {
+            timestamp,
+            session_s: vitals.session_s,
+            uptime_s: vitals.uptime_s,
+            contactor_closed: vitals.contactor_closed,
+        });
+        completed
+    }
+
+    /// Snapshot of the session in progress.
+    pub fn current(&self) -> Option<Session> {
+        let mut session = self.current.clone()?;
+        session.avg_current_a = self.avg_current();
+        Some(session)
+    }
+
+    /// Close the session in progress, if any, as [`SessionEnd::InProgress`].
+    pub fn finish(mut self) -> Option<Session> {
+        self.end(SessionEnd::InProgress)
+    }
+
+    fn avg_current(&self) -> f64 {
+        if self.closed_samples == 0 {
+            0.0
+        } else {
+            self.current_sum / self.closed_samples as f64
+        }
+    }
+
+    fn end(&mut self, end: SessionEnd) -> Option<Session> {
+        let mut session = self.current.take()?;
+        session.end = end;
+        session.avg_current_a = self.avg_current();
+        self.closed_samples = 0;
+        self.current_sum = 0.0;
+        Some(session)
+    }
+}
+
+/// Detect all sessions in a sequence of timestamped vitals, including the
+/// one still in progress at the end.
+pub fn detect_sessions<'a>(
+    samples: impl IntoIterator<Item = (OffsetDateTime, &'a Vitals)>,
+) -> Vec<Session> {
+    let mut detector = SessionDetector::new();
+    let mut sessions: Vec<Session> = samples
+        .into_iter()
+        .filter_map(|(timestamp, vitals)| detector.push(timestamp, vitals))
+        .collect();
+    sessions.extend(detector.finish());
+    sessions
+}
@@ -0,0 +1,294 @@
+//! Simulated wall connector serving the `/api/1` endpoints.
+//!
+//! The data either comes from a scripted charging scenario or from replaying
+//! a file written with `--log`. Both repeat forever, so clients can poll the
+//! simulator for as long as they like.
+//!
+//! The scenario cycles through [`SCENARIO_CYCLE_S`] seconds: the vehicle is
+//! unplugged for a minute, plugs in, charges at up to 32 A for five minutes,
+//! waits half a minute with the contactor open and unplugs again. The
+//! lifetime counters follow the completed cycles.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+use crate::evse::{ConfigStatus, EvseState, NotReadyReason};
+use crate::http::{self, Request, Response};
+use crate::logfile::{LogRecord, Payload};
+use crate::models::{Lifetime, Version, Vitals, WifiStatus};
+
+/// Address the simulator listens on unless told otherwise.
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+/// Length of one plug-in, charge, unplug cycle of the scenario in seconds.
+pub const SCENARIO_CYCLE_S: u64 = 400;
+
+const PLUG_IN_S: f64 = 60.0;
+const CHARGE_START_S: f64 = 70.0;
+const CHARGE_END_S: f64 = 370.0;
+const MAX_CURRENT_A: f64 = 32.0;
+const RAMP_S: f64 = 10.0;
+const START_UPTIME_S: u64 = 3_428_000;
+/// Nominal grid voltage of the scenario, energy is computed with it.
+const GRID_V: f64 = 245.0;
+
+const ENDPOINTS: [&str; 4] = ["vitals", "lifetime", "version", "wifi_status"];
+
+/// Seconds of charging and current integrated over them (A·s) after
+/// charging for `t` seconds.
+fn charge_progress(t: f64) -> (f64, f64) {
+    let t = t.clamp(0.0, CHARGE_END_S - CHARGE_START_S);
+    let amp_seconds = if t <= RAMP_S {
+        MAX_CURRENT_A * t * t / (2.0 * RAMP_S)
+    } else {
+        MAX_CURRENT_A * (RAMP_S / 2.0 + t - RAMP_S)
+    };
+    (t, amp_seconds)
+}
+
+/// Energy of `amp_seconds` at `volts` in Wh.
+fn energy_wh(amp_seconds: f64, volts: f64) -> f64 {
+    amp_seconds * volts / 3600.0
+}
+
+/// The scripted scenario, `elapsed` is simulated seconds since start.
+pub fn scenario_vitals(elapsed: f64) -> Vitals {
+    let cycle = SCENARIO_CYCLE_S as f64;
+    let t = elapsed % cycle;
+    let connected = t >= PLUG_IN_S;
+    let charging = (CHARGE_START_S..CHARGE_END_S).contains(&t);
+    let current = if charging {
+        MAX_CURRENT_A * ((t - CHARGE_START_S) / RAMP_S).min(1.0)
+    } else {
+        0.0
+    };
+    // The connector keeps reporting the energy of the last session until
+    // the next plug-in
+    let session_energy_wh = if connected {
+        energy_wh(charge_progress(t - CHARGE_START_S).1, GRID_V)
+    } else if elapsed >= cycle {
+        energy_wh(charge_progress(cycle).1, GRID_V)
+    } else {
+        0.0
+    };
+    let since_charge = (t - CHARGE_START_S).max(0.0);
+    let handle_temp_c = if connected {
+        // Warms while charging and cools down afterwards
+        let heat = 15.0 * (1.0 - (-since_charge.min(CHARGE_END_S - CHARGE_START_S) / 120.0).exp());
+        let cool = (-(t - CHARGE_END_S).max(0.0) / 60.0).exp();
+        20.0 + heat * cool
+    } else {
+        20.0
+    };
+    let grid_v = GRID_V + 1.5 * (elapsed / 37.0).sin() - current * 0.05;
+    let (evse_state, reasons, pilot) = if !connected {
+        (
+            EvseState::NotConnected,
+            vec![NotReadyReason::VehicleNotReady, NotReadyReason::NoVehicle],
+            (11.8, 11.8),
+        )
+    } else if charging {
+        (
+            EvseState::Charging,
+            vec![NotReadyReason::VehicleSession],
+            (4.4, 4.4),
+        )
+    } else {
+        (
+            EvseState::WaitingForCar,
+            vec![NotReadyReason::VehicleSession],
+            (4.4, 4.4),
+        )
+    };
+
+    Vitals {
+        contactor_closed: charging,
+        vehicle_connected: connected,
+        session_s: if connected { (t - PLUG_IN_S) as u64 } else { 0 },
+        grid_v: round(grid_v, 1),
+        grid_hz: round(60.0 + 0.02 * (elapsed / 11.0).sin(), 3),
+        vehicle_current_a: round(current, 1),
+        current_a_a: 0.0,
+        current_b_a: round(current, 1),
+        current_c_a: 0.0,
+        current_n_a: round(current, 1),
+        voltage_a_v: if connected {
+            round(grid_v / 2.0, 1)
+        } else {
+            0.2
+        },
+        voltage_b_v: if connected { round(grid_v, 1) } else { 0.0 },
+        voltage_c_v: if connected {
+            round(grid_v / 2.0, 1)
+        } else {
+            0.0
+        },
+        relay_coil_v: if charging { 5.7 } else { 0.0 },
+        pcba_temp_c: round(handle_temp_c + 2.4, 1),
+        handle_temp_c: round(handle_temp_c, 1),
+        mcu_temp_c: round(handle_temp_c + 9.8, 1),
+        uptime_s: START_UPTIME_S + elapsed as u64,
+        input_thermopile_uv: -67,
+        prox_v: if connected { 1.5 } else { 0.5 },
+        pilot_high_v: pilot.0,
+        pilot_low_v: pilot.1,
+        session_energy_wh: round(session_energy_wh, 3),
+        config_status: ConfigStatus::Configured,
+        evse_state,
+        current_alerts: Vec::new(),
+        evse_not_ready_reasons: reasons,
+    }
+}
+
+/// Lifetime counters of the scripted scenario.
+pub fn scenario_lifetime(elapsed: f64) -> Lifetime {
+    let cycle = SCENARIO_CYCLE_S as f64;
+    let cycles = (elapsed / cycle).floor();
+    let t = elapsed % cycle;
+    let (full_s, full_as) = charge_progress(cycle);
+    let (now_s, now_as) = charge_progress(t - CHARGE_START_S);
+    let plugs = cycles as u32 + u32::from(t >= PLUG_IN_S);
+    let starts = cycles as u32 + u32::from(t >= CHARGE_START_S);
+    Lifetime {
+        contactor_cycles: 1054 + starts,
+        contactor_cycles_loaded: 66,
+        alert_count: 2243,
+        thermal_foldbacks: 0,
+        avg_startup_temp: 20.0,
+        charge_starts: 1054 + starts,
+        energy_wh: 5_276_620 + energy_wh(cycles * full_as + now_as, GRID_V) as u64,
+        connector_cycles: 483 + plugs,
+        uptime_s: 50_803_200 + elapsed as u64,
+        charging_time_s: 3_937_000 + (cycles * full_s + now_s) as u64,
+    }
+}
+
+pub fn scenario_version() -> Version {
+    Version {
+        firmware_version: "25.34.1+simulated".to_string(),
+        git_branch: "HEAD".to_string(),
+        part_number: "1529455-02-D".to_string(),
+        serial_number: "SIM000000001".to_string(),
+        web_service: Some("0.1.0".to_string()),
+    }
+}
+
+pub fn scenario_wifi_status(elapsed: f64) -> WifiStatus {
+    let rssi = -57 + (3.0 * (elapsed / 53.0).sin()).round() as i32;
+    WifiStatus {
+        wifi_ssid: STANDARD.encode("Simulator"),
+        wifi_signal_strength: (2 * (rssi + 90)).clamp(0, 100),
+        wifi_rssi: rssi,
+        wifi_snr: rssi + 93,
+        wifi_connected: true,
+        wifi_infra_ip: "127.0.0.1".to_string(),
+        internet: true,
+        wifi_mac: "02:00:00:00:00:01".to_string(),
+    }
+}
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+/// Records of one endpoint of a replayed log, offset in seconds from the
+/// first record of the log.
+type Track = Vec<(f64, Payload)>;
+
+/// Serves the scripted scenario or a replayed log.
+pub struct Simulator {
+    /// Per endpoint in the order of [`ENDPOINTS`], empty for the scenario.
+    tracks: Vec<Track>,
+    /// Length of the replayed log in seconds.
+    span: f64,
+    speed: f64,
+    started: Instant,
+}
+
+impl Simulator {
+    /// Simulator running the scripted scenario, `speed` of 2 runs it twice
+    /// as fast as real time.
+    pub fn scenario(speed: f64) -> Self {
+        Simulator {
+            tracks: Vec::new(),
+            span: 0.0,
+            speed,
+            started: Instant::now(),
+        }
+    }
+
+    /// Simulator replaying `records`. Endpoints missing in the log are served
+    /// from the scenario.
+    pub fn replay(mut records: Vec<LogRecord>, speed: f64) -> Result<Self, String> {
+        records.sort_by_key(|record| record.timestamp);
+        let first = records.first().ok_or("no records to replay")?.timestamp;
+        let last = records.last().unwrap().timestamp;
+        let mut tracks = vec![Track::new(); ENDPOINTS.len()];
+        for record in records {
+            let offset = (record.timestamp - first).as_seconds_f64();
+            let index = ENDPOINTS
+                .iter()
+                .position(|e| *e == record.payload.endpoint())
+                .unwrap();
+            tracks[index].push((offset, record.payload));
+        }
+        Ok(Simulator {
+            tracks,
+            span: (last - first).as_seconds_f64(),
+            speed,
+            started: Instant::now(),
+        })
+    }
+
+    /// Simulated time since start.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed().mul_f64(self.speed)
+    }
+
+    /// Response of `endpoint` at `elapsed` simulated time, `None` for unknown
+    /// endpoints.
+    pub fn payload(&self, endpoint: &str, elapsed: Duration) -> Option<Payload> {
+        let index = ENDPOINTS.iter().position(|e| *e == endpoint)?;
+        let elapsed = elapsed.as_secs_f64();
+        if let Some(track) = self.tracks.get(index)
+            && !track.is_empty()
+        {
+            // Start over one second after the last record
+            let position = elapsed % (self.span + 1.0);
+            let next = track.partition_point(|(offset, _)| *offset <= position);
+            return Some(track[next.saturating_sub(1)].1.clone());
+        }
+        Some(match endpoint {
+            "vitals" => Payload::Vitals(scenario_vitals(elapsed)),
+            "lifetime" => Payload::Lifetime(scenario_lifetime(elapsed)),
+            "version" => Payload::Version(scenario_version()),
+            _ => Payload::WifiStatus(scenario_wifi_status(elapsed)),
+        })
+    }
+
+    fn handle(&self, request: &Request) -> Response {
+        let Some(endpoint) = request.path.strip_prefix("/api/1/") else {
+            return Response::not_found();
+        };
+        let Some(payload) = self.payload(endpoint, self.elapsed()) else {
+            return Response::not_found();
+        };
+        match payload_json(&payload) {
+            Ok(json) => Response::json(json),
+            Err(e) => Response::error(500, &e.to_string()),
+        }
+    }
+
+    /// Serve the simulator on `listener` until the process exits.
+    pub fn serve(self, listener: TcpListener) -> std::io::Result<()> {
+        http::serve(listener, move |request| self.handle(request))
+    }
+}
+
+/// JSON body of `payload` as the wall connector sends it, alerts in their
+/// raw form.
+pub fn payload_json(payload: &Payload) -> serde_json::Result<String> {
+    serde_json::to_string(&payload.to_raw_value())
+}
@@ -0,0 +1,618 @@
+//! SQLite history of everything polled, written with `--db`.
+//!
+//! Each endpoint has a table with a column per field, plus `device` (the
+//! name the device was given, or its address) and `timestamp`. Timestamps
+//! are UTC text such as `2025-12-23T02:54:31.123Z`, which sorts in time
+//! order and works with SQLite's date functions. Lists (`current_alerts`,
+//! `evse_not_ready_reasons`) are stored as JSON, status codes as numbers.
+//!
+//! Samples imported from `--log` files record the file in `source`, which
+//! is `NULL` for polled ones.
+//!
+//! Sessions are derived from the vitals as they arrive: the session in
+//! progress is kept up to date in `sessions` with `end_reason` set to
+//! `in_progress`, and gets its final row when it ends. Reopening the
+//! database picks the session in progress up again from its stored vitals,
+//! and importing rebuilds the sessions of the device from all of its vitals.
+//!
+//! The schema is versioned with `PRAGMA user_version`; opening a database
+//! applies the migrations it is missing.
+//!
+//! ```sql
+//! SELECT date(plugged_in) AS day, sum(energy_wh) / 1000 AS kwh
+//! FROM sessions GROUP BY day ORDER BY day;
+//! ```
+
+use rusqlite::types::{Type, ValueRef};
+use rusqlite::{Connection, params};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use time::format_description::BorrowedFormatItem;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::client::Recorder;
+use crate::logfile::{LogRecord, Payload};
+use crate::models::{Lifetime, Version, Vitals, WifiStatus};
+use crate::session::{Session, SessionDetector, SessionEnd, detect_sessions};
+
+const TIMESTAMP: &[BorrowedFormatItem<'_>] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z");
+
+/// Schema changes in order, database version `n` has the first `n` applied.
+const MIGRATIONS: &[&str] = &[
+    // 1: a table per endpoint and the derived sessions
+    "CREATE TABLE vitals (
+        id INTEGER PRIMARY KEY,
+        device TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        contactor_closed INTEGER NOT NULL,
+        vehicle_connected INTEGER NOT NULL,
+        session_s INTEGER NOT NULL,
+        grid_v REAL NOT NULL,
+        grid_hz REAL NOT NULL,
+        vehicle_current_a REAL NOT NULL,
+        current_a_a REAL NOT NULL,
+        current_b_a REAL NOT NULL,
+        current_c_a REAL NOT NULL,
+        current_n_a REAL NOT NULL,
+        voltage_a_v REAL NOT NULL,
+        voltage_b_v REAL NOT NULL,
+        voltage_c_v REAL NOT NULL,
+        relay_coil_v REAL NOT NULL,
+        pcba_temp_c REAL NOT NULL,
+        handle_temp_c REAL NOT NULL,
+        mcu_temp_c REAL NOT NULL,
+        uptime_s INTEGER NOT NULL,
+        input_thermopile_uv INTEGER NOT NULL,
+        prox_v REAL NOT NULL,
+        pilot_high_v REAL NOT NULL,
+        pilot_low_v REAL NOT NULL,
+        session_energy_wh REAL NOT NULL,
+        config_status INTEGER NOT NULL,
+        evse_state INTEGER NOT NULL,
+        current_alerts TEXT NOT NULL,
+        evse_not_ready_reasons TEXT NOT NULL
+    );
+    CREATE INDEX vitals_device_timestamp ON vitals (device, timestamp);
+
+    CREATE TABLE lifetime (
+        id INTEGER PRIMARY KEY,
+        device TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        contactor_cycles INTEGER NOT NULL,
+        contactor_cycles_loaded INTEGER NOT NULL,
+        alert_count INTEGER NOT NULL,
+        thermal_foldbacks INTEGER NOT NULL,
+        avg_startup_temp REAL NOT NULL,
+        charge_starts INTEGER NOT NULL,
+        energy_wh INTEGER NOT NULL,
+        connector_cycles INTEGER NOT NULL,
+        uptime_s INTEGER NOT NULL,
+        charging_time_s INTEGER NOT NULL
+    );
+    CREATE INDEX lifetime_device_timestamp ON lifetime (device, timestamp);
+
+    CREATE TABLE version (
+        id INTEGER PRIMARY KEY,
+        device TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        firmware_version TEXT NOT NULL,
+        git_branch TEXT NOT NULL,
+        part_number TEXT NOT NULL,
+        serial_number TEXT NOT NULL,
+        web_service TEXT
+    );
+    CREATE INDEX version_device_timestamp ON version (device, timestamp);
+
+    CREATE TABLE wifi_status (
+        id INTEGER PRIMARY KEY,
+        device TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        wifi_ssid TEXT NOT NULL,
+        wifi_signal_strength INTEGER NOT NULL,
+        wifi_rssi INTEGER NOT NULL,
+        wifi_snr INTEGER NOT NULL,
+        wifi_connected INTEGER NOT NULL,
+        wifi_infra_ip TEXT NOT NULL,
+        internet INTEGER NOT NULL,
+        wifi_mac TEXT NOT NULL
+    );
+    CREATE INDEX wifi_status_device_timestamp ON wifi_status (device, timestamp);
+
+    CREATE TABLE sessions (
+        id INTEGER PRIMARY KEY,
+        device TEXT NOT NULL,
+        plugged_in TEXT NOT NULL,
+        contactor_closed TEXT,
+        contactor_opened TEXT,
+        ended TEXT NOT NULL,
+        end_reason TEXT NOT NULL,
+        energy_wh REAL NOT NULL,
+        charging_s INTEGER NOT NULL,
+        peak_current_a REAL NOT NULL,
+        avg_current_a REAL NOT NULL,
+        peak_handle_temp_c REAL NOT NULL,
+        samples INTEGER NOT NULL,
+        UNIQUE (device, plugged_in)
+    );",
+    // 2: the file imported samples came from
+    "ALTER TABLE vitals ADD COLUMN source TEXT;
+    ALTER TABLE lifetime ADD COLUMN source TEXT;
+    ALTER TABLE version ADD COLUMN source TEXT;
+    ALTER TABLE wifi_status ADD COLUMN source TEXT;",
+];
+
+/// `timestamp` in UTC as stored in the database.
+pub fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .to_offset(UtcOffset::UTC)
+        .format(TIMESTAMP)
+        .unwrap_or_default()
+}
+
+fn end_reason(end: SessionEnd) -> &'static str {
+    match end {
+        SessionEnd::Unplugged => "unplugged",
+        SessionEnd::Restarted => "restarted",
+        SessionEnd::Reboot => "reboot",
+        SessionEnd::InProgress => "in_progress",
+    }
+}
+
+/// An open history database.
+#[derive(Debug)]
+pub struct Store {
+    conn: Mutex<Connection>,
+    detectors: Mutex<HashMap<String, SessionDetector>>,
+}
+
+impl Store {
+    /// Open or create the database at `path` and bring its schema up to
+    /// date.
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let conn = Connection::open(path)?;
+        // Lets ad-hoc queries read while the monitor writes
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::new(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::new(Connection::open_in_memory()?)
+    }
+
+    fn new(mut conn: Connection) -> Result<Self, Box<dyn std::error::Error>> {
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        migrate(&mut conn)?;
+        let detectors = resume_sessions(&conn)?;
+        Ok(Store {
+            conn: Mutex::new(conn),
+            detectors: Mutex::new(detectors),
+        })
+    }
+
+    /// Version of the schema, the number of migrations applied.
+    pub fn schema_version(&self) -> rusqlite::Result<usize> {
+        user_version(&self.conn.lock().unwrap())
+    }
+
+    /// Run `f` with the connection, e.g. for queries.
+    pub fn with_connection<T>(&self, f: impl FnOnce(&Connection) -> T) -> T {
+        f(&self.conn.lock().unwrap())
+    }
+
+    /// Store `payload` of `device`, updating its sessions for vitals.
+    pub fn insert(
+        &self,
+        device: &str,
+        timestamp: OffsetDateTime,
+        payload: &Payload,
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        insert_payload(&tx, device, timestamp, None, payload)?;
+        if let Payload::Vitals(vitals) = payload {
+            let mut detectors = self.detectors.lock().unwrap();
+            let detector = detectors.entry(device.to_string()).or_default();
+            if let Some(session) = detector.push(timestamp, vitals) {
+                upsert_session(&tx, device, &session)?;
+            }
+            if let Some(session) = detector.current() {
+                upsert_session(&tx, device, &session)?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Store the `records` of `device` read from `source`, skipping the
+    /// ones already stored with the same endpoint and timestamp. The
+    /// sessions of `device` are rebuilt if any vitals were imported.
+    pub fn import(
+        &self,
+        device: &str,
+        source: &str,
+        records: &[LogRecord],
+    ) -> rusqlite::Result<ImportStats> {
+        let mut stats = ImportStats::default();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for record in records {
+            let exists: bool = tx.query_row(
+                &format!(
+                    "SELECT EXISTS (SELECT 1 FROM {} WHERE device = ?1 AND timestamp = ?2)",
+                    record.payload.endpoint()
+                ),
+                params![device, format_timestamp(record.timestamp)],
+                |row| row.get(0),
+            )?;
+            if exists {
+                stats.duplicates += 1;
+                continue;
+            }
+            insert_payload(&tx, device, record.timestamp, Some(source), &record.payload)?;
+            stats.imported += 1;
+            if let Payload::Vitals(_) = record.payload {
+                stats.vitals += 1;
+            }
+        }
+        if stats.vitals > 0 {
+            rebuild_sessions(&tx, device)?;
+        }
+        tx.commit()?;
+        Ok(stats)
+    }
+
+    /// Recorder for a client, storing its responses as `device`.
+    pub fn recorder(self: &Arc<Self>, device: &str) -> StoreRecorder {
+        StoreRecorder {
+            store: Arc::clone(self),
+            device: device.to_string(),
+        }
+    }
+}
+
+/// What [`Store::import`] did with the records of a file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportStats {
+    pub imported: usize,
+    /// Records already stored, e.g. from an overlapping file.
+    pub duplicates: usize,
+    /// Imported records that are vitals.
+    pub vitals: usize,
+}
+
+/// Stores the responses of one client, see [`Store::recorder`].
+#[derive(Debug)]
+pub struct StoreRecorder {
+    store: Arc<Store>,
+    device: String,
+}
+
+impl Recorder for StoreRecorder {
+    fn record(&self, timestamp: OffsetDateTime, payload: &Payload) {
+        if let Err(e) = self.store.insert(&self.device, timestamp, payload) {
+            log::warn!(
+                "storing {} of {} failed: {}",
+                payload.endpoint(),
+                self.device,
+                e
+            );
+        }
+    }
+}
+
+fn insert_payload(
+    conn: &Connection,
+    device: &str,
+    timestamp: OffsetDateTime,
+    source: Option<&str>,
+    payload: &Payload,
+) -> rusqlite::Result<()> {
+    let at = format_timestamp(timestamp);
+    match payload {
+        Payload::Vitals(vitals) => insert_vitals(conn, device, &at, source, vitals),
+        Payload::Lifetime(lifetime) => insert_lifetime(conn, device, &at, source, lifetime),
+        Payload::Version(version) => insert_version(conn, device, &at, source, version),
+        Payload::WifiStatus(wifi) => insert_wifi_status(conn, device, &at, source, wifi),
+    }
+}
+
+/// API name of a `vitals` column.
+fn vitals_field(column: &str) -> &str {
+    match column {
+        "current_a_a" => "currentA_a",
+        "current_b_a" => "currentB_a",
+        "current_c_a" => "currentC_a",
+        "current_n_a" => "currentN_a",
+        "voltage_a_v" => "voltageA_v",
+        "voltage_b_v" => "voltageB_v",
+        "voltage_c_v" => "voltageC_v",
+        column => column,
+    }
+}
+
+/// The vitals of `device` in time order, all of them or those from `since`
+/// on.
+fn read_vitals(
+    conn: &Connection,
+    device: &str,
+    since: Option<&str>,
+) -> rusqlite::Result<Vec<(OffsetDateTime, Vitals)>> {
+    let mut query = conn.prepare(
+        "SELECT * FROM vitals WHERE device = ?1 AND (?2 IS NULL OR timestamp >= ?2)
+        ORDER BY timestamp",
+    )?;
+    let columns: Vec<String> = query.column_names().into_iter().map(String::from).collect();
+    let invalid = |i, e: Box<dyn std::error::Error + Send + Sync>| {
+        rusqlite::Error::FromSqlConversionFailure(i, Type::Text, e)
+    };
+    let rows = query.query_map(params![device, since], |row| {
+        let mut fields = Map::new();
+        let mut timestamp = None;
+        for (i, column) in columns.iter().enumerate() {
+            let value = match (column.as_str(), row.get_ref(i)?) {
+                ("id" | "device" | "source", _) => continue,
+                ("timestamp", value) => {
+                    let parsed = OffsetDateTime::parse(value.as_str()?, &Rfc3339)
+                        .map_err(|e| invalid(i, e.into()))?;
+                    timestamp = Some(parsed);
+                    continue;
+                }
+                ("contactor_closed" | "vehicle_connected", value) => {
+                    Value::Bool(value.as_i64()? != 0)
+                }
+                ("current_alerts" | "evse_not_ready_reasons", value) => {
+                    serde_json::from_str(value.as_str()?).map_err(|e| invalid(i, e.into()))?
+                }
+                (_, ValueRef::Integer(n)) => Value::from(n),
+                (_, ValueRef::Real(f)) => Value::from(f),
+                (_, value) => Value::from(value.as_str()?),
+            };
+            fields.insert(vitals_field(column).to_string(), value);
+        }
+        let vitals =
+            serde_json::from_value(Value::Object(fields)).map_err(|e| invalid(0, e.into()))?;
+        Ok((timestamp.unwrap_or(OffsetDateTime::UNIX_EPOCH), vitals))
+    })?;
+    rows.collect()
+}
+
+/// Derive the sessions of `device` again from all of its vitals.
+fn rebuild_sessions(conn: &Connection, device: &str) -> rusqlite::Result<()> {
+    let vitals = read_vitals(conn, device, None)?;
+    let sessions = detect_sessions(
+        vitals
+            .iter()
+            .map(|(timestamp, vitals)| (*timestamp, vitals)),
+    );
+    conn.execute("DELETE FROM sessions WHERE device = ?1", [device])?;
+    for session in &sessions {
+        upsert_session(conn, device, session)?;
+    }
+    Ok(())
+}
+
+/// Session detectors of the devices with a session in progress, fed with
+/// its vitals so it carries on where the previous run left off. Sessions
+/// that turn out to have ended are stored as such.
+fn resume_sessions(conn: &Connection) -> rusqlite::Result<HashMap<String, SessionDetector>> {
+    let mut query = conn.prepare(
+        "SELECT device, min(plugged_in) FROM sessions WHERE end_reason = ?1 GROUP BY device",
+    )?;
+    let in_progress = query
+        .query_map([end_reason(SessionEnd::InProgress)], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut detectors = HashMap::new();
+    for (device, plugged_in) in in_progress {
+        let mut detector = SessionDetector::new();
+        for (timestamp, vitals) in read_vitals(conn, &device, Some(&plugged_in))? {
+            if let Some(session) = detector.push(timestamp, &vitals) {
+                upsert_session(conn, &device, &session)?;
+            }
+        }
+        detectors.insert(device, detector);
+    }
+    Ok(detectors)
+}
+
+fn user_version(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), Box<dyn std::error::Error>> {
+    let version = user_version(conn)?;
+    if version > MIGRATIONS.len() {
+        return Err(format!(
+            "database schema version {} is newer than this version supports ({})",
+            version,
+            MIGRATIONS.len()
+        )
+        .into());
+    }
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn insert_vitals(
+    conn: &Connection,
+    device: &str,
+    at: &str,
+    source: Option<&str>,
+    v: &Vitals,
+) -> rusqlite::Result<()> {
+    let alerts: Vec<_> = v.current_alerts.iter().map(|alert| &alert.raw).collect();
+    let reasons: Vec<u32> = v.evse_not_ready_reasons.iter().map(|r| r.code()).collect();
+    conn.execute(
+        "INSERT INTO vitals (device, timestamp, contactor_closed, vehicle_connected, session_s,
+            grid_v, grid_hz, vehicle_current_a, current_a_a, current_b_a, current_c_a,
+            current_n_a, voltage_a_v, voltage_b_v, voltage_c_v, relay_coil_v, pcba_temp_c,
+            handle_temp_c, mcu_temp_c, uptime_s, input_thermopile_uv, prox_v, pilot_high_v,
+            pilot_low_v, session_energy_wh, config_status, evse_state, current_alerts,
+            evse_not_ready_reasons, source)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+            ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30)",
+        params![
+            device,
+            at,
+            v.contactor_closed,
+            v.vehicle_connected,
+            v.session_s,
+            v.grid_v,
+            v.grid_hz,
+            v.vehicle_current_a,
+            v.current_a_a,
+            v.current_b_a,
+            v.current_c_a,
+            v.current_n_a,
+            v.voltage_a_v,
+            v.voltage_b_v,
+            v.voltage_c_v,
+            v.relay_coil_v,
+            v.pcba_temp_c,
+            v.handle_temp_c,
+            v.mcu_temp_c,
+            v.uptime_s,
+            v.input_thermopile_uv,
+            v.prox_v,
+            v.pilot_high_v,
+            v.pilot_low_v,
+            v.session_energy_wh,
+            v.config_status.code(),
+            v.evse_state.code(),
+            serde_json::to_string(&alerts).unwrap_or_default(),
+            serde_json::to_string(&reasons).unwrap_or_default(),
+            source,
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_lifetime(
+    conn: &Connection,
+    device: &str,
+    at: &str,
+    source: Option<&str>,
+    l: &Lifetime,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO lifetime (device, timestamp, contactor_cycles, contactor_cycles_loaded,
+            alert_count, thermal_foldbacks, avg_startup_temp, charge_starts, energy_wh,
+            connector_cycles, uptime_s, charging_time_s, source)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            device,
+            at,
+            l.contactor_cycles,
+            l.contactor_cycles_loaded,
+            l.alert_count,
+            l.thermal_foldbacks,
+            l.avg_startup_temp,
+            l.charge_starts,
+            l.energy_wh,
+            l.connector_cycles,
+            l.uptime_s,
+            l.charging_time_s,
+            source,
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_version(
+    conn: &Connection,
+    device: &str,
+    at: &str,
+    source: Option<&str>,
+    v: &Version,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO version (device, timestamp, firmware_version, git_branch, part_number,
+            serial_number, web_service, source)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            device,
+            at,
+            v.firmware_version,
+            v.git_branch,
+            v.part_number,
+            v.serial_number,
+            v.web_service,
+            source,
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_wifi_status(
+    conn: &Connection,
+    device: &str,
+    at: &str,
+    source: Option<&str>,
+    w: &WifiStatus,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO wifi_status (device, timestamp, wifi_ssid, wifi_signal_strength, wifi_rssi,
+            wifi_snr, wifi_connected, wifi_infra_ip, internet, wifi_mac, source)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            device,
+            at,
+            w.wifi_ssid,
+            w.wifi_signal_strength,
+            w.wifi_rssi,
+            w.wifi_snr,
+            w.wifi_connected,
+            w.wifi_infra_ip,
+            w.internet,
+            w.wifi_mac,
+            source,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Insert `session`, or update the row of the same plug-in.
+fn upsert_session(conn: &Connection, device: &str, s: &Session) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO sessions (device, plugged_in, contactor_closed, contactor_opened, ended,
+            end_reason, energy_wh, charging_s, peak_current_a, avg_current_a,
+            peak_handle_temp_c, samples)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        ON CONFLICT (device, plugged_in) DO UPDATE SET
+            contactor_closed = excluded.contactor_closed,
+            contactor_opened = excluded.contactor_opened,
+            ended = excluded.ended,
+            end_reason = excluded.end_reason,
+            energy_wh = excluded.energy_wh,
+            charging_s = excluded.charging_s,
+            peak_current_a = excluded.peak_current_a,
+            avg_current_a = excluded.avg_current_a,
+            peak_handle_temp_c = excluded.peak_handle_temp_c,
+            samples = excluded.samples",
+        params![
+            device,
+            format_timestamp(s.plugged_in),
+            s.contactor_closed.map(format_timestamp),
+            s.contactor_opened.map(format_timestamp),
+            format_timestamp(s.ended),
+            end_reason(s.end),
+            s.energy_wh,
+            s.charging_s,
+            s.peak_current_a,
+            s.avg_current_a,
+            s.peak_handle_temp_c,
+            s.samples,
+        ],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+//! Service notifications to systemd, the protocol of `sd_notify(3)`.
+//!
+//! Units with `Type=notify` get a datagram socket in `$NOTIFY_SOCKET` that
+//! state changes such as `READY=1` are sent to, and with `WatchdogSec=` a
+//! `$WATCHDOG_USEC` within which `WATCHDOG=1` must arrive. Outside systemd
+//! the variables aren't set and notifying does nothing.
+
+use std::ffi::OsStr;
+use std::io;
+use std::time::Duration;
+
+pub const READY: &str = "READY=1";
+pub const RELOADING: &str = "RELOADING=1";
+pub const STOPPING: &str = "STOPPING=1";
+pub const WATCHDOG: &str = "WATCHDOG=1";
+
+/// Send `state`, one or more `KEY=VALUE` lines, to systemd. Returns whether
+/// there was a socket to send to.
+pub fn notify(state: &str) -> io::Result<bool> {
+    match std::env::var_os("NOTIFY_SOCKET") {
+        Some(socket) if !socket.is_empty() => send(&socket, state).map(|()| true),
+        _ => Ok(false),
+    }
+}
+
+#[cfg(unix)]
+fn send(socket: &OsStr, state: &str) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    // A leading '@' names a socket in the abstract namespace
+    let addr = match socket.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            SocketAddr::from_abstract_name(name)?
+        }
+        _ => SocketAddr::from_pathname(socket)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &OsStr, _state: &str) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// How often to send [`WATCHDOG`], half the watchdog timeout, or `None`
+/// when the watchdog isn't enabled for this process.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = std::env::var_os("WATCHDOG_PID")
+        && pid.to_str().and_then(|pid| pid.parse().ok()) != Some(std::process::id())
+    {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
@@ -0,0 +1,291 @@
+//! Electricity tariffs for the cost of charging.
+//!
+//! A tariff has a base rate per kWh and optionally time-of-use periods, each
+//! with its own rate and limited to some days of the week, hours of the day
+//! and months of the year. The first period matching a time applies, times
+//! outside all periods are billed at the base rate. Seasonal tariffs are
+//! periods limited to months, tiered tariffs replace the base rate with
+//! rates by the energy used so far in the month:
+//!
+//! ```toml
+//! [tariff]
+//! currency = "$"
+//! rate = 0.15
+//! utc_offset = "-08:00"
+//!
+//! [[tariff.periods]]
+//! name = "peak"
+//! days = "mon-fri"
+//! hours = "16-21"
+//! months = "6-9"
+//! rate = 0.42
+//!
+//! [[tariff.tiers]]
+//! up_to_kwh = 300
+//! rate = 0.12
+//! ```
+//!
+//! Hours, days and months are comma separated lists of values or ranges,
+//! ranges may wrap around (`hours = "22-6"` is the night, the end hour is
+//! excluded). Times are matched in `utc_offset`, the local offset of the
+//! machine when the tariff was read if not given. Tiers only see the energy
+//! delivered by the wall connector, not the rest of the household.
+
+use serde::Deserialize;
+use std::fmt;
+use time::{OffsetDateTime, UtcOffset};
+
+/// Name of the energy billed at the base rate.
+pub const BASE_NAME: &str = "off-peak";
+
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tariff {
+    /// Symbol printed before amounts, e.g. `$`.
+    #[serde(default)]
+    pub currency: String,
+    /// Rate per kWh outside the periods.
+    #[serde(default)]
+    pub rate: f64,
+    /// Name of the energy billed at the base rate.
+    #[serde(default = "base_name")]
+    pub name: String,
+    #[serde(default, deserialize_with = "deserialize_offset")]
+    pub utc_offset: Option<UtcOffset>,
+    #[serde(default)]
+    pub periods: Vec<Period>,
+    #[serde(default)]
+    pub tiers: Vec<Tier>,
+}
+
+fn base_name() -> String {
+    BASE_NAME.to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Period {
+    pub name: String,
+    pub rate: f64,
+    /// Days of the week, all if not given.
+    #[serde(default)]
+    pub days: Option<Days>,
+    /// Hours of the day, all if not given.
+    #[serde(default)]
+    pub hours: Option<Hours>,
+    /// Months of the year, all if not given.
+    #[serde(default)]
+    pub months: Option<Months>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tier {
+    /// Monthly energy up to which the rate applies, the last tier has none.
+    pub up_to_kwh: Option<f64>,
+    pub rate: f64,
+}
+
+/// Set of values given as a list of values and ranges.
+macro_rules! value_set {
+    ($(#[$meta:meta])* $name:ident, $min:literal..=$max:literal, $what:literal, $parse:expr) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+        #[serde(try_from = "String")]
+        pub struct $name(u32);
+
+        impl $name {
+            pub fn contains(self, value: u32) -> bool {
+                self.0 & (1 << value) != 0
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = String;
+
+            fn try_from(s: String) -> Result<Self, Self::Error> {
+                let parse = |value: &str| -> Result<u32, String> {
+                    let parse: fn(&str) -> Option<u32> = $parse;
+                    parse(value.trim())
+                        .filter(|value| ($min..=$max).contains(value))
+                        .ok_or_else(|| format!("invalid {} '{}'", $what, value.trim()))
+                };
+                let mut bits = 0;
+                for part in s.split(',') {
+                    let (first, last) = match part.split_once('-') {
+                        Some((first, last)) => (parse(first)?, parse(last)?),
+                        None => {
+                            let value = parse(part)?;
+                            (value, value)
+                        }
+                    };
+                    let mut value = first;
+                    loop {
+                        bits |= 1 << value;
+                        if value == last {
+                            break;
+                        }
+                        value = if value == $max { $min } else { value + 1 };
+                    }
+                }
+                Ok($name(bits))
+            }
+        }
+    };
+}
+
+value_set!(
+    /// Days of the week, `mon` to `sun`.
+    Days, 0..=6, "day", |day| DAYS.iter().position(|d| day.eq_ignore_ascii_case(d)).map(|d| d as u32)
+);
+
+value_set!(
+    /// Months, 1 to 12.
+    Months, 1..=12, "month", |month| month.parse().ok()
+);
+
+/// Hours of the day, 0 to 24. A range includes its first hour but not its
+/// last, `16-21` is 16:00 to 21:00.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Hours(u32);
+
+impl Hours {
+    pub fn contains(self, hour: u32) -> bool {
+        self.0 & (1 << hour) != 0
+    }
+}
+
+impl TryFrom<String> for Hours {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let parse = |value: &str| -> Result<u32, String> {
+            value
+                .trim()
+                .parse()
+                .ok()
+                .filter(|hour| *hour <= 24)
+                .ok_or_else(|| format!("invalid hour '{}'", value.trim()))
+        };
+        let mut bits = 0;
+        for part in s.split(',') {
+            let (first, end) = match part.split_once('-') {
+                Some((first, end)) => (parse(first)? % 24, parse(end)? % 24),
+                None => {
+                    let hour = parse(part)? % 24;
+                    (hour, (hour + 1) % 24)
+                }
+            };
+            let mut hour = first;
+            loop {
+                bits |= 1 << hour;
+                hour = (hour + 1) % 24;
+                if hour == end {
+                    break;
+                }
+            }
+        }
+        Ok(Hours(bits))
+    }
+}
+
+fn deserialize_offset<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<UtcOffset>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    let format = time::macros::format_description!("[offset_hour]:[offset_minute]");
+    UtcOffset::parse(&text, format).map(Some).map_err(|_| {
+        serde::de::Error::custom(format!(
+            "invalid UTC offset '{}', expected e.g. +01:00",
+            text
+        ))
+    })
+}
+
+/// What energy used at some time costs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate<'a> {
+    /// Name of the matching period, or of the base rate.
+    pub name: &'a str,
+    pub per_kwh: f64,
+}
+
+impl Tariff {
+    /// Offset the periods are matched in.
+    pub fn offset(&self) -> UtcOffset {
+        self.utc_offset.unwrap_or(UtcOffset::UTC)
+    }
+
+    /// Use the local offset unless one was configured.
+    pub fn with_local_offset(mut self, local: UtcOffset) -> Self {
+        self.utc_offset.get_or_insert(local);
+        self
+    }
+
+    /// Names energy is split by, the periods in order then the base rate.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for name in self
+            .periods
+            .iter()
+            .map(|period| period.name.as_str())
+            .chain([self.name.as_str()])
+        {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// Rate at `time`, `month_kwh` being the energy used earlier in the month
+    /// for tiered tariffs.
+    pub fn rate_at(&self, time: OffsetDateTime, month_kwh: f64) -> Rate<'_> {
+        let local = time.to_offset(self.offset());
+        let day = local.weekday().number_days_from_monday() as u32;
+        let hour = local.hour() as u32;
+        let month = u8::from(local.month()) as u32;
+        let period = self.periods.iter().find(|period| {
+            period.days.is_none_or(|days| days.contains(day))
+                && period.hours.is_none_or(|hours| hours.contains(hour))
+                && period.months.is_none_or(|months| months.contains(month))
+        });
+        match period {
+            Some(period) => Rate {
+                name: &period.name,
+                per_kwh: period.rate,
+            },
+            None => Rate {
+                name: &self.name,
+                per_kwh: self
+                    .tiers
+                    .iter()
+                    .find(|tier| tier.up_to_kwh.is_none_or(|limit| month_kwh < limit))
+                    .map_or(self.rate, |tier| tier.rate),
+            },
+        }
+    }
+
+    /// `amount` with the currency symbol.
+    pub fn format_amount(&self, amount: f64) -> String {
+        format!("{}{:.2}", self.currency, amount)
+    }
+}
+
+impl fmt::Display for Tariff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}/kWh", self.name, self.format_amount(self.rate))?;
+        for period in &self.periods {
+            write!(
+                f,
+                ", {} {}/kWh",
+                period.name,
+                self.format_amount(period.rate)
+            )?;
+        }
+        Ok(())
+    }
+}
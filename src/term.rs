@@ -0,0 +1,71 @@
+//! Keyboard handling and styling shared by the interactive displays.
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::Stylize;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// Wait up to `timeout` (forever if `None`) for a key press. Other events
+/// end a timed wait early and return `None`.
+pub fn read_key(timeout: Option<Duration>) -> Option<KeyEvent> {
+    loop {
+        if let Some(timeout) = timeout
+            && !event::poll(timeout).unwrap()
+        {
+            return None;
+        }
+        if let Event::Key(key_event) = event::read().unwrap() {
+            return Some(key_event);
+        }
+        if timeout.is_some() {
+            return None;
+        }
+    }
+}
+
+/// ESC or Ctrl+C, the keys that leave every interactive display.
+pub fn is_exit_key(key_event: &KeyEvent) -> bool {
+    match key_event.code {
+        KeyCode::Esc => true,
+        KeyCode::Char('c') => key_event.modifiers.contains(KeyModifiers::CONTROL),
+        _ => false,
+    }
+}
+
+/// `current` with the values of the lines that differ from `previous`
+/// shown in bold yellow. The value is what follows the label's `:`, lines
+/// without a label are highlighted whole. Lines are matched by their label,
+/// or their whole text without one, so lines coming and going don't shift
+/// the comparison of the ones after them.
+pub fn highlight_changes(previous: &str, current: &str) -> String {
+    let key = |line: &str| -> String {
+        match line.split_once(':') {
+            Some((label, _)) => label.to_string(),
+            None => line.to_string(),
+        }
+    };
+    // Repeated labels are matched in order
+    let mut before: HashMap<String, VecDeque<&str>> = HashMap::new();
+    for line in previous.lines() {
+        before.entry(key(line)).or_default().push_back(line);
+    }
+    current
+        .lines()
+        .map(|line| {
+            let previous = before.get_mut(&key(line)).and_then(VecDeque::pop_front);
+            if previous == Some(line) {
+                return line.to_string();
+            }
+            let start = match line.split_once(':') {
+                Some((_, value)) => line.len() - value.trim_start().len(),
+                None => line.len() - line.trim_start().len(),
+            };
+            let (unchanged, changed) = line.split_at(start);
+            if changed.is_empty() {
+                return line.to_string();
+            }
+            format!("{}{}", unchanged, changed.bold().yellow())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,240 @@
+//! Web dashboard and JSON API served by the `serve` command.
+//!
+//! A single background thread polls the connector every interval, however
+//! many browsers are watching. The page at `/` shows the vitals, the session
+//! in progress, lifetime stats, Wi-Fi status and a chart of the recent
+//! history, and is updated through the Server-Sent Events of `/events`. The
+//! same data is available as JSON:
+//!
+//! - `/api/status` - everything the page shows
+//! - `/api/vitals`, `/api/lifetime`, `/api/wifi_status`, `/api/version` -
+//!   the latest response of each endpoint
+//! - `/api/session` - the session in progress, `null` when unplugged
+//! - `/api/history` - current and temperatures over the history window
+//!
+//! The endpoints answer 503 until the first poll succeeded.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+use crate::client::WallConnectorClient;
+use crate::format::decode_ssid;
+use crate::http::{self, Request, Response};
+use crate::models::{Lifetime, Version, Vitals, WifiStatus};
+use crate::session::{Session, SessionDetector};
+
+/// Address the web dashboard listens on unless told otherwise.
+pub const DEFAULT_LISTEN: &str = "0.0.0.0:8088";
+
+/// How often an idle event stream gets a comment, so proxies keep it open
+/// and closed browsers are noticed.
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+const INDEX: &str = include_str!("../assets/index.html");
+
+/// A point of the history chart.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryPoint {
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    pub vehicle_current_a: f64,
+    pub grid_v: f64,
+    pub handle_temp_c: f64,
+    pub pcba_temp_c: f64,
+}
+
+/// Everything known about the connector, as of the last poll.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Status {
+    pub addr: String,
+    pub interval_s: u64,
+    /// Time of the last successful poll.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated: Option<OffsetDateTime>,
+    /// Why the last poll failed, the data is stale until the next success.
+    pub error: Option<String>,
+    pub version: Option<Version>,
+    pub vitals: Option<Vitals>,
+    /// Description of `evse_state`.
+    pub state: Option<&'static str>,
+    pub session: Option<Session>,
+    pub lifetime: Option<Lifetime>,
+    pub wifi_status: Option<WifiStatus>,
+    pub wifi_ssid_decoded: Option<String>,
+    pub history: VecDeque<HistoryPoint>,
+}
+
+/// Latest status with a counter of the polls, which event streams wait on.
+struct Shared {
+    status: Mutex<(u64, Status)>,
+    changed: Condvar,
+}
+
+pub struct WebServer {
+    client: WallConnectorClient,
+    interval: Duration,
+    window: Duration,
+    shared: Shared,
+}
+
+impl WebServer {
+    /// Poll `client` every `interval`, keeping `window` of history.
+    pub fn new(client: WallConnectorClient, interval: Duration, window: Duration) -> Self {
+        let status = Status {
+            addr: client.addr().to_string(),
+            interval_s: interval.as_secs(),
+            ..Default::default()
+        };
+        WebServer {
+            client,
+            interval,
+            window,
+            shared: Shared {
+                status: Mutex::new((0, status)),
+                changed: Condvar::new(),
+            },
+        }
+    }
+
+    /// Snapshot of the current status.
+    pub fn status(&self) -> Status {
+        self.shared.status.lock().unwrap().1.clone()
+    }
+
+    /// Poll the connector once and update the status.
+    pub fn poll(&self, detector: &mut SessionDetector) {
+        // The version never changes at runtime, fetch it until it succeeds
+        let known = self.shared.status.lock().unwrap().1.version.clone();
+        let version = match known {
+            Some(version) => Ok(version),
+            None => self.client.version(),
+        };
+        let vitals = version.and_then(|version| Ok((version, self.client.vitals()?)));
+        // Lifetime and Wi-Fi are nice to have, the last ones stay up on errors
+        let lifetime = self.client.lifetime().ok();
+        let wifi = self.client.wifi_status().ok();
+        let now = OffsetDateTime::now_utc();
+
+        let mut guard = self.shared.status.lock().unwrap();
+        let (polls, status) = &mut *guard;
+        match vitals {
+            Ok((version, vitals)) => {
+                // Only the session in progress is shown
+                let _ = detector.push(now, &vitals);
+                status.session = detector.current();
+                status.history.push_back(HistoryPoint {
+                    timestamp: now,
+                    vehicle_current_a: vitals.vehicle_current_a,
+                    grid_v: vitals.grid_v,
+                    handle_temp_c: vitals.handle_temp_c,
+                    pcba_temp_c: vitals.pcba_temp_c,
+                });
+                status.state = Some(vitals.evse_state.description());
+                status.version = Some(version);
+                status.vitals = Some(vitals);
+                status.updated = Some(now);
+                status.error = None;
+            }
+            Err(e) => status.error = Some(e.to_string()),
+        }
+        if let Some(lifetime) = lifetime {
+            status.lifetime = Some(lifetime);
+        }
+        if let Some(wifi) = wifi {
+            status.wifi_ssid_decoded = Some(decode_ssid(&wifi.wifi_ssid));
+            status.wifi_status = Some(wifi);
+        }
+        let oldest = now - self.window;
+        while status.history.front().is_some_and(|p| p.timestamp < oldest) {
+            status.history.pop_front();
+        }
+        *polls += 1;
+        self.shared.changed.notify_all();
+    }
+
+    fn json<T: Serialize>(&self, value: impl FnOnce(&Status) -> &T) -> Response {
+        let status = self.shared.status.lock().unwrap();
+        if status.1.updated.is_none() {
+            let message = status
+                .1
+                .error
+                .as_deref()
+                .unwrap_or("waiting for the first poll");
+            return Response::error(503, message);
+        }
+        match serde_json::to_string(&value(&status.1)) {
+            Ok(json) => Response::json(json),
+            Err(e) => Response::error(500, &e.to_string()),
+        }
+    }
+
+    fn handle(&self, request: &Request) -> Response {
+        match request.path.as_str() {
+            "/" => Response::new(200, "text/html; charset=utf-8", INDEX),
+            "/api/status" => self.json(|s| s),
+            "/api/vitals" => self.json(|s| &s.vitals),
+            "/api/lifetime" => self.json(|s| &s.lifetime),
+            "/api/wifi_status" => self.json(|s| &s.wifi_status),
+            "/api/version" => self.json(|s| &s.version),
+            "/api/session" => self.json(|s| &s.session),
+            "/api/history" => self.json(|s| &s.history),
+            _ => Response::not_found(),
+        }
+    }
+
+    /// Send the status after every poll until the browser goes away.
+    fn events(&self, mut stream: TcpStream) {
+        if http::write_event_stream_head(&mut stream).is_err() {
+            return;
+        }
+        let mut seen = None;
+        loop {
+            let json = {
+                let guard = self.shared.status.lock().unwrap();
+                let (guard, _) = self
+                    .shared
+                    .changed
+                    .wait_timeout_while(guard, KEEPALIVE, |(polls, _)| Some(*polls) == seen)
+                    .unwrap();
+                let (polls, status) = &*guard;
+                if Some(*polls) == seen {
+                    None
+                } else {
+                    seen = Some(*polls);
+                    serde_json::to_string(status).ok()
+                }
+            };
+            let sent = match json {
+                Some(json) => http::write_event(&mut stream, "status", &json),
+                None => http::write_event(&mut stream, "", "keepalive"),
+            };
+            if sent.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Start the poller and serve on `listener` until the process exits.
+    pub fn serve(self, listener: TcpListener) -> std::io::Result<()> {
+        let server = Arc::new(self);
+        let poller = Arc::clone(&server);
+        std::thread::spawn(move || {
+            let mut detector = SessionDetector::new();
+            loop {
+                poller.poll(&mut detector);
+                std::thread::sleep(poller.interval);
+            }
+        });
+        http::serve_connections(listener, move |request, mut stream| {
+            if request.path == "/events" {
+                server.events(stream);
+            } else {
+                let _ = server.handle(&request).write_to(&mut stream);
+            }
+        })
+    }
+}
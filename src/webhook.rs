@@ -0,0 +1,47 @@
+//! Delivery of rule [`Notification`]s to a webhook.
+//!
+//! Each notification is POSTed on its own as a JSON object, e.g.
+//!
+//! ```json
+//! {"device":"garage","rule":"handle_temp_c > 60 for 2m","state":"firing",
+//!  "field":"handle_temp_c","value":61.2,"timestamp":"2025-12-23T02:54:31Z"}
+//! ```
+
+use crate::client::DEFAULT_TIMEOUT;
+use crate::rules::Notification;
+
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl Webhook {
+    pub fn new(url: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let parsed = reqwest::Url::parse(url)?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("unsupported scheme '{}'", parsed.scheme()).into());
+        }
+        let client = reqwest::blocking::Client::builder()
+            .timeout(DEFAULT_TIMEOUT)
+            .build()?;
+        Ok(Webhook {
+            url: url.to_string(),
+            client,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// POST `notification`, any status other than 2xx is an error.
+    pub fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+        self.client
+            .post(&self.url)
+            .json(notification)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+//! Decoding of `current_alerts` and tracking them over successive vitals.
+
+mod common;
+
+use common::vitals_with;
+use serde_json::{Value, json};
+use tesla_wallcon_monitor::Lifetime;
+use tesla_wallcon_monitor::alert::{Alert, AlertSeverity, AlertTracker};
+
+fn lifetime(alert_count: u32) -> Lifetime {
+    let mut json: Value = serde_json::from_str(common::LIFETIME).unwrap();
+    json["alert_count"] = alert_count.into();
+    serde_json::from_value(json).unwrap()
+}
+
+fn codes(alerts: &[Alert]) -> Vec<String> {
+    alerts.iter().map(Alert::key).collect()
+}
+
+#[test]
+fn raw_entries_are_decoded() {
+    let alert = Alert::from_raw(json!("PCS_a052"));
+    assert_eq!(alert.code.as_deref(), Some("PCS_a052"));
+    assert_eq!(alert.severity, AlertSeverity::Unknown);
+    assert_eq!(alert.to_string(), "PCS_a052 [unknown] no description");
+
+    let alert = Alert::from_raw(json!(52));
+    assert_eq!(alert.code.as_deref(), Some("52"));
+
+    let alert = Alert::from_raw(json!({
+        "alert_id": 49,
+        "desc": "Grid voltage low",
+        "level": "WARN",
+    }));
+    assert_eq!(alert.code.as_deref(), Some("49"));
+    assert_eq!(alert.description.as_deref(), Some("Grid voltage low"));
+    assert_eq!(alert.severity, AlertSeverity::Warning);
+    assert_eq!(alert.to_string(), "49 [warning] Grid voltage low");
+
+    let alert = Alert::from_raw(json!({"code": "X", "severity": "critical"}));
+    assert_eq!(alert.severity, AlertSeverity::Error);
+
+    // Unrecognized shapes keep the raw JSON as their key
+    let alert = Alert::from_raw(json!([1, 2]));
+    assert!(alert.is_unknown());
+    assert_eq!(alert.key(), "[1,2]");
+    assert_eq!(alert.to_string(), "unrecognized alert [1,2]");
+}
+
+#[test]
+fn alerts_round_trip_through_our_json() {
+    let raw = json!({"id": "PCS_a052", "message": "Relay fault", "type": "error"});
+    let alert = Alert::from_raw(raw.clone());
+    let value = serde_json::to_value(&alert).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "code": "PCS_a052",
+            "description": "Relay fault",
+            "severity": "error",
+            "raw": raw,
+        })
+    );
+    let again: Alert = serde_json::from_value(value).unwrap();
+    assert_eq!(again, alert);
+
+    // A raw object that merely has a raw key is decoded as is
+    let alert: Alert = serde_json::from_value(json!({"code": "A", "raw": 1})).unwrap();
+    assert_eq!(alert.code.as_deref(), Some("A"));
+    assert_eq!(alert.raw, json!({"code": "A", "raw": 1}));
+}
+
+#[test]
+fn tracker_reports_raised_alerts_and_occurrences() {
+    let mut tracker = AlertTracker::new();
+    let mut update =
+        |alerts: Value| codes(&tracker.update_vitals(&vitals_with(&[("current_alerts", alerts)])));
+
+    // Alerts active on the first update predate the tracker
+    assert!(update(json!(["A"])).is_empty());
+    assert_eq!(update(json!(["A", "B"])), ["B"]);
+    assert!(update(json!(["A", "B"])).is_empty());
+    assert!(update(json!(["B"])).is_empty());
+    assert_eq!(update(json!(["A", "B"])), ["A"]);
+    assert!(update(json!([])).is_empty());
+    assert_eq!(update(json!([{"code": "B", "severity": "error"}])), ["B"]);
+
+    let tracked: Vec<(String, u32, bool)> = tracker
+        .alerts()
+        .iter()
+        .map(|t| (t.alert.key(), t.occurrences, t.active))
+        .collect();
+    assert_eq!(
+        tracked,
+        [("A".to_string(), 1, false), ("B".to_string(), 2, true)]
+    );
+    // The latest form of an alert is kept
+    assert_eq!(tracker.alerts()[1].alert.severity, AlertSeverity::Error);
+}
+
+#[test]
+fn lifetime_count_shows_unobserved_alerts() {
+    let mut tracker = AlertTracker::new();
+    assert_eq!(tracker.count_delta(), 0);
+    tracker.update_lifetime(&lifetime(100));
+    tracker.update_vitals(&vitals_with(&[]));
+    tracker.update_vitals(&vitals_with(&[("current_alerts", json!(["A"]))]));
+    tracker.update_lifetime(&lifetime(104));
+    assert_eq!(tracker.count_delta(), 4);
+    assert_eq!(tracker.unobserved(), 3);
+
+    // A counter that went backwards, e.g. after a reset, isn't negative
+    tracker.update_lifetime(&lifetime(50));
+    assert_eq!(tracker.count_delta(), 0);
+    assert_eq!(tracker.unobserved(), 0);
+}
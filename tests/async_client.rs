@@ -0,0 +1,117 @@
+//! The async client against a connector that misbehaves.
+#![cfg(feature = "async")]
+
+mod common;
+
+use common::{FakeConnector, Fault};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tesla_wallcon_monitor::retry::RetryPolicy;
+use tesla_wallcon_monitor::store::Store;
+use tesla_wallcon_monitor::{AsyncWallConnectorClient, Error};
+
+fn client(fake: &FakeConnector) -> AsyncWallConnectorClient {
+    AsyncWallConnectorClient::new("127.0.0.1")
+        .unwrap()
+        .with_port(fake.port())
+        .with_timeout(Duration::from_secs(2))
+}
+
+fn retrying(fake: &FakeConnector, retries: u32) -> AsyncWallConnectorClient {
+    let retry = RetryPolicy::new(retries).with_backoff(Duration::from_millis(10));
+    client(fake).with_retry(retry)
+}
+
+#[tokio::test]
+async fn healthy_connector() {
+    let fake = FakeConnector::start();
+    let client = client(&fake);
+    assert_eq!(
+        client.version().await.unwrap().serial_number,
+        "TWC123456789"
+    );
+    assert_eq!(client.vitals().await.unwrap().vehicle_current_a, 7.9);
+    assert_eq!(client.retry(), &RetryPolicy::none());
+}
+
+#[tokio::test]
+async fn timeouts() {
+    let fake = FakeConnector::start();
+    let client = client(&fake)
+        .with_timeout(Duration::from_millis(200))
+        .with_connect_timeout(Duration::from_millis(500))
+        .unwrap();
+    assert_eq!(client.connect_timeout(), Duration::from_millis(500));
+    fake.inject(Fault::Latency(Duration::from_secs(2)));
+    let start = Instant::now();
+    assert!(matches!(client.vitals().await, Err(Error::Timeout(_))));
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried() {
+    let fake = FakeConnector::start();
+    let client = retrying(&fake, 3);
+    fake.inject(Fault::Reset);
+    fake.inject(Fault::Status(503));
+    fake.inject(Fault::Truncated);
+    assert_eq!(client.vitals().await.unwrap().grid_v, 251.5);
+    assert_eq!(fake.requests().len(), 4);
+
+    // Until the retries run out
+    let client = retrying(&fake, 1);
+    fake.inject(Fault::Reset);
+    fake.inject(Fault::Reset);
+    assert!(client.vitals().await.is_err());
+    assert_eq!(fake.requests().len(), 6);
+}
+
+#[tokio::test]
+async fn permanent_failures_are_not_retried() {
+    let fake = FakeConnector::start();
+    let client = retrying(&fake, 3);
+    fake.inject(Fault::Status(404));
+    assert!(matches!(
+        client.vitals().await,
+        Err(Error::Status { status: 404, .. })
+    ));
+    fake.inject(Fault::MalformedJson);
+    assert!(matches!(client.vitals().await, Err(Error::NotJson { .. })));
+    fake.inject(Fault::MissingField("grid_v"));
+    assert!(client.vitals().await.is_err());
+    assert_eq!(fake.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn unresolvable_name() {
+    let client = AsyncWallConnectorClient::new("wallcon.invalid").unwrap();
+    let err = client.version().await.unwrap_err();
+    assert!(matches!(err, Error::Dns { .. }), "{:?}", err);
+    assert_eq!(err.exit_code(), 3);
+}
+
+#[tokio::test]
+async fn responses_are_recorded() {
+    let fake = FakeConnector::start();
+    let store = Arc::new(Store::open_in_memory().unwrap());
+    let client = client(&fake).with_recorder(Arc::new(store.recorder("home")));
+    client.vitals().await.unwrap();
+    client.lifetime().await.unwrap();
+    // Failed requests aren't
+    fake.inject(Fault::MalformedJson);
+    assert!(client.vitals().await.is_err());
+
+    let count = |table: &str| -> i64 {
+        store.with_connection(|conn| {
+            conn.query_row(
+                &format!("SELECT count(*) FROM {} WHERE device = 'home'", table),
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+        })
+    };
+    assert_eq!(count("vitals"), 1);
+    assert_eq!(count("lifetime"), 1);
+    assert_eq!(count("sessions"), 1);
+}
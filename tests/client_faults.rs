@@ -0,0 +1,253 @@
+//! Behavior of the client against a connector that misbehaves.
+
+mod common;
+
+use common::{FakeConnector, Fault};
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+use tesla_wallcon_monitor::retry::RetryPolicy;
+use tesla_wallcon_monitor::session::{SessionDetector, SessionEnd};
+use tesla_wallcon_monitor::{Error, WallConnectorClient};
+use time::OffsetDateTime;
+
+#[test]
+fn healthy_connector() {
+    let fake = FakeConnector::start();
+    let client = fake.client();
+    assert_eq!(client.version().unwrap().serial_number, "TWC123456789");
+    assert!(client.wifi_status().unwrap().wifi_connected);
+    assert_eq!(client.lifetime().unwrap().charge_starts, 1054);
+    assert_eq!(client.vitals().unwrap().vehicle_current_a, 7.9);
+}
+
+#[test]
+fn latency_within_timeout() {
+    let fake = FakeConnector::start();
+    fake.inject(Fault::Latency(Duration::from_millis(300)));
+    assert!(fake.client().vitals().is_ok());
+}
+
+#[test]
+fn latency_beyond_timeout() {
+    let fake = FakeConnector::start();
+    let client = fake.client().with_timeout(Duration::from_millis(200));
+    fake.inject(Fault::Latency(Duration::from_secs(2)));
+    let start = Instant::now();
+    assert!(client.vitals().is_err());
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn connection_reset() {
+    let fake = FakeConnector::start();
+    let client = fake.client();
+    fake.inject(Fault::Reset);
+    assert!(client.vitals().is_err());
+    // The same client recovers on the next poll
+    assert!(client.vitals().is_ok());
+}
+
+#[test]
+fn server_error() {
+    let fake = FakeConnector::start();
+    // The body is valid vitals, a 500 must still not be taken as data
+    fake.inject(Fault::Status(500));
+    let err = fake.client().vitals().unwrap_err();
+    assert!(err.to_string().contains("500"), "{}", err);
+}
+
+#[test]
+fn truncated_body() {
+    let fake = FakeConnector::start();
+    fake.inject(Fault::Truncated);
+    assert!(fake.client().lifetime().is_err());
+}
+
+#[test]
+fn malformed_json() {
+    let fake = FakeConnector::start();
+    fake.inject(Fault::MalformedJson);
+    assert!(fake.client().vitals().is_err());
+}
+
+#[test]
+fn missing_field() {
+    let fake = FakeConnector::start();
+    fake.inject(Fault::MissingField("grid_v"));
+    let err = fake.client().vitals().unwrap_err();
+    assert!(err.to_string().contains("grid_v"), "{}", err);
+}
+
+#[test]
+fn faults_apply_to_one_request_each() {
+    let fake = FakeConnector::start();
+    let client = fake.client();
+    fake.inject(Fault::Status(503));
+    fake.inject(Fault::MalformedJson);
+    assert!(client.vitals().is_err());
+    assert!(client.vitals().is_err());
+    assert!(client.vitals().is_ok());
+    assert_eq!(fake.requests().len(), 3);
+}
+
+#[test]
+fn uptime_reset_ends_session() {
+    let fake = FakeConnector::start();
+    let client = fake.client();
+    let start = OffsetDateTime::now_utc();
+    let mut detector = SessionDetector::new();
+
+    let vitals = client.vitals().unwrap();
+    assert!(detector.push(start, &vitals).is_none());
+
+    // The connector rebooted mid session
+    fake.set_vitals("uptime_s", 12);
+    fake.set_vitals("session_s", 3);
+    let vitals = client.vitals().unwrap();
+    let session = detector
+        .push(start + Duration::from_secs(5), &vitals)
+        .unwrap();
+    assert_eq!(session.end, SessionEnd::Reboot);
+    assert!(detector.current().is_some());
+}
+
+fn retrying(fake: &FakeConnector, retries: u32) -> WallConnectorClient {
+    let retry = RetryPolicy::new(retries).with_backoff(Duration::from_millis(10));
+    fake.client().with_retry(retry)
+}
+
+#[test]
+fn transient_failures_are_retried() {
+    let fake = FakeConnector::start();
+    let client = retrying(&fake, 3);
+    fake.inject(Fault::Reset);
+    fake.inject(Fault::Status(503));
+    fake.inject(Fault::Truncated);
+    assert_eq!(client.vitals().unwrap().grid_v, 251.5);
+    assert_eq!(fake.requests().len(), 4);
+}
+
+#[test]
+fn retries_run_out() {
+    let fake = FakeConnector::start();
+    let client = retrying(&fake, 1);
+    fake.inject(Fault::Reset);
+    fake.inject(Fault::Reset);
+    assert!(client.vitals().is_err());
+    assert_eq!(fake.requests().len(), 2);
+}
+
+#[test]
+fn permanent_failures_are_not_retried() {
+    let fake = FakeConnector::start();
+    let client = retrying(&fake, 3);
+    fake.inject(Fault::Status(404));
+    assert!(client.vitals().is_err());
+    fake.inject(Fault::MalformedJson);
+    assert!(client.vitals().is_err());
+    fake.inject(Fault::MissingField("grid_v"));
+    assert!(client.vitals().is_err());
+    assert_eq!(fake.requests().len(), 3);
+}
+
+#[test]
+fn backoff_doubles_with_jitter() {
+    let policy = RetryPolicy::new(5)
+        .with_backoff(Duration::from_millis(100))
+        .with_max_backoff(Duration::from_millis(300));
+    assert_eq!(policy.backoff(0), Duration::from_millis(100));
+    assert_eq!(policy.backoff(1), Duration::from_millis(200));
+    assert_eq!(policy.backoff(2), Duration::from_millis(300));
+    for retry in 0..5 {
+        let delay = policy.delay(retry);
+        assert!(delay >= policy.backoff(retry) / 2 && delay <= policy.backoff(retry));
+    }
+}
+
+#[test]
+fn errors_are_classified() {
+    let fake = FakeConnector::start();
+    let client = fake.client().with_timeout(Duration::from_millis(200));
+
+    fake.inject(Fault::Latency(Duration::from_secs(1)));
+    assert!(matches!(client.vitals(), Err(Error::Timeout(_))));
+    fake.inject(Fault::Status(503));
+    assert!(matches!(
+        client.vitals(),
+        Err(Error::Status { status: 503, .. })
+    ));
+    fake.inject(Fault::MalformedJson);
+    assert!(matches!(client.vitals(), Err(Error::NotJson { .. })));
+    fake.inject(Fault::MissingField("evse_state"));
+    match client.vitals() {
+        Err(Error::Schema {
+            endpoint, field, ..
+        }) => {
+            assert_eq!(endpoint, "vitals");
+            assert_eq!(field, "evse_state");
+        }
+        other => panic!("expected a schema error, got {:?}", other),
+    }
+
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = closed.local_addr().unwrap().port();
+    drop(closed);
+    let client = WallConnectorClient::new("127.0.0.1")
+        .unwrap()
+        .with_port(port);
+    let err = client.vitals().unwrap_err();
+    assert!(matches!(err, Error::ConnectionRefused(_)), "{:?}", err);
+    assert!(err.is_unreachable());
+}
+
+#[test]
+fn unresolvable_name_exits_with_3() {
+    // .invalid never resolves (RFC 6761), also without a network
+    let client = WallConnectorClient::new("wallcon.invalid").unwrap();
+    let err = client.version().unwrap_err();
+    assert!(matches!(err, Error::Dns { .. }), "{:?}", err);
+    assert!(err.is_unreachable());
+    assert_eq!(err.exit_code(), 3);
+    assert!(
+        err.to_string()
+            .starts_with("name lookup of wallcon.invalid failed"),
+        "{}",
+        err
+    );
+
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_tesla-wallcon-monitor"))
+        .args(["wallcon.invalid", "version"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(3));
+}
+
+#[test]
+fn exit_codes_are_distinct() {
+    let fake = FakeConnector::start();
+    let client = fake.client().with_timeout(Duration::from_millis(200));
+    let mut codes = Vec::new();
+    for fault in [
+        Fault::Latency(Duration::from_secs(1)),
+        Fault::Status(500),
+        Fault::MalformedJson,
+        Fault::MissingField("grid_v"),
+    ] {
+        fake.inject(fault);
+        codes.push(client.vitals().unwrap_err().exit_code());
+    }
+    codes.sort();
+    codes.dedup();
+    assert_eq!(codes.len(), 4);
+    assert!(codes.iter().all(|code| *code > 2));
+}
+
+#[test]
+fn address_with_port_resolves() {
+    let fake = FakeConnector::start();
+    let client = WallConnectorClient::new(&format!("127.0.0.1:{}", fake.port())).unwrap();
+    assert!(client.vitals().is_ok());
+    assert_eq!(fake.requests(), ["/api/1/vitals"]);
+}
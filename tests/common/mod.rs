@@ -0,0 +1,177 @@
+//! Fake wall connector for the integration tests.
+//!
+//! Serves the `/api/1` endpoints on an ephemeral port with canned responses
+//! and injects faults into the next requests on demand.
+
+// Each test binary uses only part of the fake
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tesla_wallcon_monitor::http::{read_request, reason};
+use tesla_wallcon_monitor::{Vitals, WallConnectorClient};
+
+pub const VERSION: &str = r#"{"firmware_version":"25.34.1+ge48cc9be91ebc7","git_branch":"HEAD","part_number":"1529455-02-D","serial_number":"TWC123456789","web_service":"0.1.0"}"#;
+pub const WIFI_STATUS: &str = r#"{"wifi_ssid":"TXlOZXR3b3Jr","wifi_signal_strength":66,"wifi_rssi":-57,"wifi_snr":36,"wifi_connected":true,"wifi_infra_ip":"192.168.1.221","internet":true,"wifi_mac":"54:F8:F0:0A:30:AA"}"#;
+pub const LIFETIME: &str = r#"{"contactor_cycles":1054,"contactor_cycles_loaded":66,"alert_count":2243,"thermal_foldbacks":0,"avg_startup_temp":0.0,"charge_starts":1054,"energy_wh":5276620,"connector_cycles":483,"uptime_s":50803200,"charging_time_s":3937000}"#;
+pub const VITALS: &str = r#"{"contactor_closed":true,"vehicle_connected":true,"session_s":39,"grid_v":251.5,"grid_hz":59.911,"vehicle_current_a":7.9,"currentA_a":0.0,"currentB_a":7.9,"currentC_a":0.0,"currentN_a":7.9,"voltageA_v":118.1,"voltageB_v":245.4,"voltageC_v":117.9,"relay_coil_v":5.7,"pcba_temp_c":16.0,"handle_temp_c":14.1,"mcu_temp_c":21.4,"uptime_s":3428403,"input_thermopile_uv":-67,"prox_v":1.5,"pilot_high_v":4.4,"pilot_low_v":4.4,"session_energy_wh":0.500,"config_status":5,"evse_state":11,"current_alerts":[],"evse_not_ready_reasons":[1]}"#;
+
+/// [`VITALS`] with the `fields` replaced.
+pub fn vitals_with(fields: &[(&str, Value)]) -> Vitals {
+    let mut json: Value = serde_json::from_str(VITALS).unwrap();
+    for (field, value) in fields {
+        json[*field] = value.clone();
+    }
+    serde_json::from_value(json).unwrap()
+}
+
+/// What goes wrong with a request.
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// Wait before answering.
+    Latency(Duration),
+    /// Close the connection without answering.
+    Reset,
+    /// Answer with this status and the normal body.
+    Status(u16),
+    /// Announce the full body but send only half of it.
+    Truncated,
+    /// Answer 200 with a body that isn't JSON.
+    MalformedJson,
+    /// Drop this field from the body.
+    MissingField(&'static str),
+}
+
+#[derive(Default)]
+struct State {
+    faults: VecDeque<Fault>,
+    /// Overrides of vitals fields, applied to every response.
+    vitals: Vec<(String, Value)>,
+    requests: Vec<String>,
+}
+
+pub struct FakeConnector {
+    port: u16,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeConnector {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(Mutex::new(State::default()));
+        let shared = Arc::clone(&state);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = Arc::clone(&shared);
+                std::thread::spawn(move || answer(stream, &state));
+            }
+        });
+        FakeConnector { port, state }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Client for the fake with a short timeout so failing tests fail fast.
+    pub fn client(&self) -> WallConnectorClient {
+        WallConnectorClient::new("127.0.0.1")
+            .unwrap()
+            .with_port(self.port)
+            .with_timeout(Duration::from_secs(2))
+    }
+
+    /// Apply `fault` to the next request that doesn't have one yet.
+    pub fn inject(&self, fault: Fault) {
+        self.state.lock().unwrap().faults.push_back(fault);
+    }
+
+    /// Serve `value` for the vitals field `field` from now on.
+    pub fn set_vitals(&self, field: &str, value: impl Into<Value>) {
+        let mut state = self.state.lock().unwrap();
+        state.vitals.retain(|(f, _)| f != field);
+        state.vitals.push((field.to_string(), value.into()));
+    }
+
+    /// Paths requested so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+fn answer(mut stream: TcpStream, state: &Mutex<State>) {
+    let Ok(request) = read_request(&stream) else {
+        return;
+    };
+    let (fault, overrides) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request.path.clone());
+        (state.faults.pop_front(), state.vitals.clone())
+    };
+
+    let body = match request.path.as_str() {
+        "/api/1/version" => VERSION,
+        "/api/1/wifi_status" => WIFI_STATUS,
+        "/api/1/lifetime" => LIFETIME,
+        "/api/1/vitals" => VITALS,
+        _ => {
+            let _ = write_response(&mut stream, 404, b"Not Found", None);
+            return;
+        }
+    };
+    let mut json: Value = serde_json::from_str(body).unwrap();
+    if request.path == "/api/1/vitals" {
+        for (field, value) in overrides {
+            json[field] = value;
+        }
+    }
+
+    let mut status = 200;
+    match fault {
+        Some(Fault::Latency(delay)) => std::thread::sleep(delay),
+        Some(Fault::Reset) => {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+        Some(Fault::Status(code)) => status = code,
+        Some(Fault::MissingField(field)) => {
+            json.as_object_mut().unwrap().remove(field);
+        }
+        Some(Fault::MalformedJson) => {
+            let _ = write_response(&mut stream, 200, b"{\"contactor_closed\":tru", None);
+            return;
+        }
+        Some(Fault::Truncated) => {
+            let full = json.to_string();
+            let half = &full.as_bytes()[..full.len() / 2];
+            let _ = write_response(&mut stream, 200, half, Some(full.len()));
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+        None => {}
+    }
+    let _ = write_response(&mut stream, status, json.to_string().as_bytes(), None);
+}
+
+/// Write a response, `length` overrides the announced Content-Length.
+fn write_response(
+    stream: &mut TcpStream,
+    status: u16,
+    body: &[u8],
+    length: Option<usize>,
+) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason(status),
+        length.unwrap_or(body.len())
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
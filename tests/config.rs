@@ -0,0 +1,198 @@
+//! Reading the config file and how the CLI applies it.
+
+mod common;
+
+use common::FakeConnector;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tesla_wallcon_monitor::config::{Config, SinkConfig};
+use tesla_wallcon_monitor::format::Units;
+use tesla_wallcon_monitor::logfile::LogFormat;
+use tesla_wallcon_monitor::output::OutputFormat;
+
+#[test]
+fn parses_every_section() {
+    let config = Config::parse(
+        r#"
+        command = "vitals"
+        delay = 10
+        log = "/var/log/wallcon.log"
+        log_format = "jsonl"
+        units = "imperial"
+        format = "key=value"
+
+        [devices]
+        home = "192.168.1.221"
+        garage = "192.168.1.222:8080"
+
+        [http]
+        connect_timeout = 3000
+        retries = 3
+
+        [exporter]
+        cache = 5
+
+        [daemon]
+        devices = ["home"]
+        interval = 30
+
+        [[daemon.sinks]]
+        type = "file"
+        path = "/var/lib/wallcon.log"
+
+        [[daemon.sinks]]
+        type = "stdout"
+        device = "garage"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.command.as_deref(), Some("vitals"));
+    assert_eq!(config.delay, Some(10));
+    assert_eq!(
+        config.log.as_deref(),
+        Some(Path::new("/var/log/wallcon.log"))
+    );
+    assert_eq!(config.log_format, Some(LogFormat::Jsonl));
+    assert_eq!(config.units, Some(Units::Imperial));
+    assert_eq!(config.format, Some(OutputFormat::KeyValue));
+    assert_eq!(config.db, None);
+    assert_eq!(config.http.connect_timeout, Some(3000));
+    assert_eq!(config.http.read_timeout, None);
+    assert_eq!(config.http.retries, Some(3));
+    assert_eq!(config.exporter.cache, Some(5));
+    assert_eq!(config.exporter.listen, None);
+    assert_eq!(config.daemon.devices, ["home"]);
+    assert_eq!(config.daemon.interval, Some(30));
+    assert_eq!(
+        config.daemon.sinks,
+        [
+            SinkConfig::File {
+                path: PathBuf::from("/var/lib/wallcon.log"),
+                device: None,
+            },
+            SinkConfig::Stdout {
+                device: Some("garage".to_string()),
+            },
+        ]
+    );
+    assert!(config.tariff.is_none());
+
+    assert_eq!(config.device("home"), Some("192.168.1.221"));
+    assert_eq!(config.device("garage"), Some("192.168.1.222:8080"));
+    assert_eq!(config.device("192.168.1.221"), None);
+    assert_eq!(config.device("Home"), None);
+
+    let empty = Config::parse("").unwrap();
+    assert!(empty.devices.is_empty());
+    assert!(empty.daemon.sinks.is_empty());
+}
+
+#[test]
+fn mistakes_are_rejected() {
+    // Typos aren't silently ignored
+    assert!(Config::parse("dealy = 10").is_err());
+    assert!(Config::parse("[http]\nretry = 3").is_err());
+    assert!(Config::parse("format = \"xml\"").is_err());
+    assert!(Config::parse("delay = -1").is_err());
+    assert!(Config::parse("[devices]\nhome = 1").is_err());
+    assert!(Config::parse("[[daemon.sinks]]\ntype = \"pipe\"").is_err());
+    assert!(Config::parse("[[daemon.sinks]]\ntype = \"file\"").is_err());
+    assert!(Config::read(Path::new("no/such/config.toml")).is_err());
+}
+
+/// A fresh directory holding `config` as `relative`.
+fn config_dir(name: &str, relative: &str, config: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wallcon-config-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, config).unwrap();
+    dir
+}
+
+fn config_for(fake: &FakeConnector) -> String {
+    format!(
+        "command = \"version\"\nformat = \"json\"\n\n[devices]\nfake = \"127.0.0.1:{}\"\n",
+        fake.port()
+    )
+}
+
+/// Run `command`, which must succeed, returning its output.
+fn run(command: &mut Command) -> String {
+    let output = command.output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn monitor() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_tesla-wallcon-monitor"));
+    command.env_remove("XDG_CONFIG_HOME");
+    command
+}
+
+#[test]
+fn command_line_overrides_config() {
+    let fake = FakeConnector::start();
+    let dir = config_dir("cli", "config.toml", &config_for(&fake));
+    let config = dir.join("config.toml");
+
+    // The command and format of the config for the named device
+    let output = run(monitor().arg("fake").arg("--config").arg(&config));
+    let version: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(version["serial_number"], "TWC123456789");
+    assert_eq!(fake.requests(), ["/api/1/version"]);
+
+    let output = run(monitor()
+        .args(["fake", "version", "--format", "key=value", "--config"])
+        .arg(&config));
+    assert!(
+        output.contains("serial_number=TWC123456789\n"),
+        "{}",
+        output
+    );
+
+    // Even when the flag gives the default value
+    let output = run(monitor()
+        .args(["fake", "--format", "text", "--config"])
+        .arg(&config));
+    assert!(serde_json::from_str::<serde_json::Value>(&output).is_err());
+    assert!(output.contains("TWC123456789"), "{}", output);
+
+    // A command given on the command line replaces the config's
+    let output = run(monitor()
+        .args(["fake", "wifi_status", "--config"])
+        .arg(&config));
+    let wifi: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(wifi["wifi_rssi"], -57);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn default_config_is_found_in_the_xdg_directory() {
+    let fake = FakeConnector::start();
+    let relative = "tesla-wallcon-monitor/config.toml";
+    let xdg = config_dir("xdg", relative, &config_for(&fake));
+    let output = run(monitor().arg("fake").env("XDG_CONFIG_HOME", &xdg));
+    assert!(output.starts_with('{'), "{}", output);
+
+    // ~/.config without XDG_CONFIG_HOME, on macOS too
+    let home = config_dir("home", &format!(".config/{}", relative), &config_for(&fake));
+    let output = run(monitor().arg("fake").env("HOME", &home));
+    assert!(output.starts_with('{'), "{}", output);
+    // A relative XDG_CONFIG_HOME is ignored
+    let output = run(monitor()
+        .arg("fake")
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", "relative"));
+    assert!(output.starts_with('{'), "{}", output);
+    assert_eq!(fake.requests().len(), 3);
+
+    for dir in [xdg, home] {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+//! Tariffs and the cost of charging sessions.
+
+mod common;
+
+use serde_json::Value;
+use tesla_wallcon_monitor::Vitals;
+use tesla_wallcon_monitor::config::Config;
+use tesla_wallcon_monitor::cost::CostTracker;
+use tesla_wallcon_monitor::session::SessionEnd;
+use tesla_wallcon_monitor::tariff::Tariff;
+use time::OffsetDateTime;
+use time::macros::{date, datetime};
+
+const TARIFF: &str = r#"
+[tariff]
+currency = "$"
+rate = 0.20
+utc_offset = "+00:00"
+
+[[tariff.periods]]
+name = "peak"
+days = "mon-fri"
+hours = "16-21"
+months = "6-9"
+rate = 0.50
+
+[[tariff.periods]]
+name = "night"
+hours = "22-6"
+rate = 0.10
+"#;
+
+fn tariff(text: &str) -> Tariff {
+    Config::parse(text).unwrap().tariff.unwrap()
+}
+
+fn vitals(connected: bool, session_s: u64, energy_wh: f64) -> Vitals {
+    let mut json: Value = serde_json::from_str(common::VITALS).unwrap();
+    json["vehicle_connected"] = connected.into();
+    json["session_s"] = session_s.into();
+    json["session_energy_wh"] = energy_wh.into();
+    serde_json::from_value(json).unwrap()
+}
+
+#[test]
+fn periods_match_days_hours_and_months() {
+    let tariff = tariff(TARIFF);
+    let rate = |time: OffsetDateTime| tariff.rate_at(time, 0.0).name;
+    // Wednesday in July
+    assert_eq!(rate(datetime!(2025-07-16 16:00 UTC)), "peak");
+    assert_eq!(rate(datetime!(2025-07-16 20:59 UTC)), "peak");
+    assert_eq!(rate(datetime!(2025-07-16 21:00 UTC)), "off-peak");
+    // Saturday and December
+    assert_eq!(rate(datetime!(2025-07-19 17:00 UTC)), "off-peak");
+    assert_eq!(rate(datetime!(2025-12-17 17:00 UTC)), "off-peak");
+    // Wrapping around midnight
+    assert_eq!(rate(datetime!(2025-12-17 23:30 UTC)), "night");
+    assert_eq!(rate(datetime!(2025-12-18 05:59 UTC)), "night");
+    assert_eq!(rate(datetime!(2025-12-18 06:00 UTC)), "off-peak");
+    // Matched in the tariff's offset
+    assert_eq!(rate(datetime!(2025-07-16 10:00 -06:00)), "peak");
+}
+
+#[test]
+fn tiers_replace_the_base_rate() {
+    let tariff = tariff(
+        r#"
+        [tariff]
+        rate = 0.99
+        [[tariff.tiers]]
+        up_to_kwh = 100
+        rate = 0.10
+        [[tariff.tiers]]
+        rate = 0.30
+        "#,
+    );
+    let time = datetime!(2025-07-16 12:00 UTC);
+    assert_eq!(tariff.rate_at(time, 99.0).per_kwh, 0.10);
+    assert_eq!(tariff.rate_at(time, 100.0).per_kwh, 0.30);
+}
+
+#[test]
+fn invalid_schedules_are_rejected() {
+    for schedule in [
+        r#"hours = "16-25""#,
+        r#"days = "mon-fry""#,
+        r#"months = "0""#,
+    ] {
+        let text = format!(
+            "[tariff]\n[[tariff.periods]]\nname = \"p\"\nrate = 1\n{}",
+            schedule
+        );
+        assert!(Config::parse(&text).is_err(), "{}", schedule);
+    }
+}
+
+#[test]
+fn energy_is_split_at_the_hours() {
+    let mut tracker = CostTracker::new(tariff(TARIFF));
+    let start = datetime!(2025-07-16 15:30 UTC);
+    assert!(tracker.push(start, &vitals(true, 0, 0.0)).is_none());
+    // 2 kWh from 15:30 to 16:30, half of it at the peak rate
+    let hour = time::Duration::HOUR;
+    assert!(
+        tracker
+            .push(start + hour, &vitals(true, 3600, 2000.0))
+            .is_none()
+    );
+    let done = tracker
+        .push(start + hour * 2, &vitals(false, 3600, 2000.0))
+        .unwrap();
+
+    assert_eq!(done.session.end, SessionEnd::Unplugged);
+    assert_eq!(done.cost.energy_wh, 2000.0);
+    assert_eq!(done.cost.period("peak").unwrap().energy_wh, 1000.0);
+    assert_eq!(done.cost.period("off-peak").unwrap().energy_wh, 1000.0);
+    assert!((done.cost.cost - 0.70).abs() < 1e-9);
+    assert!((tracker.days()[&date!(2025 - 07 - 16)].cost - 0.70).abs() < 1e-9);
+    assert!(tracker.current().is_none());
+}
+
+#[test]
+fn first_sample_spreads_over_the_session() {
+    let mut tracker = CostTracker::new(tariff(TARIFF));
+    // Plugged in at 21:00, three hours and 3 kWh ago
+    let now = datetime!(2025-12-18 00:00 UTC);
+    tracker.push(now, &vitals(true, 3 * 3600, 3000.0));
+    let current = tracker.current().unwrap();
+    assert_eq!(current.cost.period("off-peak").unwrap().energy_wh, 1000.0);
+    assert_eq!(current.cost.period("night").unwrap().energy_wh, 2000.0);
+    assert_eq!(tracker.days().len(), 1);
+    assert!(tracker.finish().is_some());
+}
@@ -0,0 +1,132 @@
+//! The daemon tool run as systemd would, against the fake connector.
+#![cfg(unix)]
+
+mod common;
+
+use common::FakeConnector;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tesla_wallcon_monitor::logfile::LogFile;
+
+fn expect_notification(socket: &UnixDatagram, expected: &str) {
+    let mut buf = [0; 256];
+    loop {
+        let len = socket
+            .recv(&mut buf)
+            .expect("no notification from the daemon");
+        if &buf[..len] == expected.as_bytes() {
+            return;
+        }
+    }
+}
+
+/// Config polling `fake` every second into a file sink at `log`.
+fn config(fake: &FakeConnector, log: &Path) -> String {
+    format!(
+        "[devices]\nfake = \"127.0.0.1:{}\"\n\n[daemon]\ninterval = 1\n\n[[daemon.sinks]]\ntype = \"file\"\npath = \"{}\"\n",
+        fake.port(),
+        log.display()
+    )
+}
+
+/// Start the daemon with the config in `dir`, once it notified readiness on
+/// the returned socket.
+fn start(dir: &Path) -> (Child, UnixDatagram) {
+    let socket_path = dir.join("notify");
+    let _ = std::fs::remove_file(&socket_path);
+    let socket = UnixDatagram::bind(&socket_path).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+
+    let daemon = Command::new(env!("CARGO_BIN_EXE_tesla-wallcon-monitor"))
+        .args(["daemon", "--config"])
+        .arg(dir.join("config.toml"))
+        .env("NOTIFY_SOCKET", &socket_path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    expect_notification(&socket, "READY=1");
+    (daemon, socket)
+}
+
+/// Wait until `log` holds at least `count` records.
+fn wait_for_records(log: &Path, count: usize) {
+    let start = Instant::now();
+    while LogFile::read(log).map_or(0, |log| log.records.len()) < count {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "no records written"
+        );
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn signal(daemon: &Child, signal: &str) {
+    let status = Command::new("kill")
+        .args([signal, &daemon.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn polls_into_file_and_stops_on_sigterm() {
+    let fake = FakeConnector::start();
+    fake.set_vitals("current_alerts", serde_json::json!(["PCS_a052"]));
+    let dir = std::env::temp_dir().join(format!("wallcon-daemon-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("wallcon.log");
+    std::fs::write(dir.join("config.toml"), config(&fake, &log)).unwrap();
+
+    let (mut daemon, socket) = start(&dir);
+    wait_for_records(&log, 2);
+    signal(&daemon, "-TERM");
+    expect_notification(&socket, "STOPPING=1");
+    assert!(daemon.wait().unwrap().success());
+
+    // Written as the connector sent it, not with the decoded alerts
+    let text = std::fs::read_to_string(&log).unwrap();
+    assert!(
+        text.lines()
+            .filter(|line| line.contains(" vitals: "))
+            .all(|line| line.contains(r#""current_alerts":["PCS_a052"]"#))
+    );
+    let records = LogFile::read(&log).unwrap();
+    assert!(records.skipped.is_empty());
+    assert!(records.vitals().all(|(_, vitals)| vitals.grid_v == 251.5));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn failed_reload_keeps_the_old_sinks() {
+    let fake = FakeConnector::start();
+    let dir = std::env::temp_dir().join(format!("wallcon-reload-{}", std::process::id()));
+    let old = dir.join("old");
+    std::fs::create_dir_all(&old).unwrap();
+    let log = old.join("wallcon.log");
+    std::fs::write(dir.join("config.toml"), config(&fake, &log)).unwrap();
+
+    let (mut daemon, socket) = start(&dir);
+    wait_for_records(&log, 2);
+    // Valid settings, but the new file sink can't be opened, and the old
+    // one couldn't be opened again either
+    let unwritable = dir.join("missing").join("wallcon.log");
+    std::fs::write(dir.join("config.toml"), config(&fake, &unwritable)).unwrap();
+    std::fs::remove_dir_all(&old).unwrap();
+    signal(&daemon, "-HUP");
+    expect_notification(&socket, "RELOADING=1");
+    expect_notification(&socket, "READY=1");
+
+    // Still running on the old sinks
+    std::thread::sleep(Duration::from_millis(1500));
+    assert!(daemon.try_wait().unwrap().is_none());
+    signal(&daemon, "-TERM");
+    expect_notification(&socket, "STOPPING=1");
+    assert!(daemon.wait().unwrap().success());
+    assert!(!unwritable.exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,164 @@
+//! The dashboard's history window, stale data and keyboard navigation.
+
+mod common;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+use serde_json::json;
+use std::time::Duration;
+use tesla_wallcon_monitor::dashboard::{Dashboard, Sample};
+use tesla_wallcon_monitor::format::Units;
+use tesla_wallcon_monitor::{Lifetime, WifiStatus};
+
+fn sample(session_s: u64, rssi: i32) -> Sample {
+    let mut wifi: WifiStatus = serde_json::from_str(common::WIFI_STATUS).unwrap();
+    wifi.wifi_rssi = rssi;
+    Sample {
+        vitals: Ok(common::vitals_with(&[("session_s", json!(session_s))])),
+        lifetime: Some(serde_json::from_str::<Lifetime>(common::LIFETIME).unwrap()),
+        wifi: Some(wifi),
+    }
+}
+
+fn failed(error: &str) -> Sample {
+    Sample {
+        vitals: Err(error.to_string()),
+        lifetime: None,
+        wifi: None,
+    }
+}
+
+/// The rendered screen, one string per row.
+fn screen(dashboard: &Dashboard) -> Vec<String> {
+    let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+    terminal.draw(|frame| dashboard.draw(frame)).unwrap();
+    let buffer = terminal.backend().buffer();
+    (0..buffer.area.height)
+        .map(|y| {
+            (0..buffer.area.width)
+                .map(|x| buffer[(x, y)].symbol())
+                .collect()
+        })
+        .collect()
+}
+
+fn shows(dashboard: &Dashboard, text: &str) -> bool {
+    screen(dashboard).iter().any(|row| row.contains(text))
+}
+
+fn press(dashboard: &mut Dashboard, code: KeyCode) -> bool {
+    dashboard.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+}
+
+#[test]
+fn history_is_limited_to_the_window() {
+    let mut dashboard = Dashboard::new("127.0.0.1", Duration::from_millis(300), Units::Metric);
+    dashboard.push(sample(1, -80));
+    dashboard.push(sample(2, -50));
+    assert_eq!(dashboard.series(|v| v.session_s as f64).len(), 2);
+    assert!(shows(&dashboard, "RSSI low:  -80 dBm in window"));
+
+    std::thread::sleep(Duration::from_millis(400));
+    dashboard.push(sample(3, -60));
+    let series = dashboard.series(|v| v.session_s as f64);
+    assert_eq!(
+        series
+            .iter()
+            .map(|(_, session)| *session)
+            .collect::<Vec<_>>(),
+        [3.0]
+    );
+    assert!(series[0].0 >= 0.4);
+    // A single RSSI reading has no low to show
+    assert!(!shows(&dashboard, "RSSI low"));
+    dashboard.push(sample(4, -55));
+    assert!(shows(&dashboard, "RSSI low:  -60 dBm in window"));
+}
+
+#[test]
+fn failed_polls_keep_the_last_data_as_stale() {
+    let mut dashboard = Dashboard::new("127.0.0.1", Duration::from_secs(600), Units::Metric);
+    assert!(shows(&dashboard, "Waiting for data..."));
+    assert!(shows(&dashboard, "No data yet"));
+
+    dashboard.push(failed("connection refused"));
+    assert!(shows(&dashboard, "Error: connection refused"));
+    assert!(dashboard.latest().is_none());
+
+    dashboard.push(sample(3900, -57));
+    assert!(shows(&dashboard, "Updated "));
+    assert!(!shows(&dashboard, "connection refused"));
+
+    dashboard.push(failed("timed out"));
+    assert!(shows(&dashboard, "Stale since "));
+    assert!(shows(&dashboard, " UTC, timed out"));
+    // The last vitals, wifi and lifetime are still shown
+    assert_eq!(dashboard.latest().unwrap().session_s, 3900);
+    assert_eq!(dashboard.series(|v| v.grid_v).len(), 1);
+    assert!(shows(&dashboard, "Session:   1h 5m"));
+    assert!(shows(&dashboard, "-57 dBm"));
+    assert!(shows(&dashboard, "Charge starts"));
+
+    dashboard.push(sample(3910, -57));
+    assert!(!shows(&dashboard, "Stale since"));
+}
+
+#[test]
+fn keys_move_the_focus_and_zoom() {
+    let mut dashboard = Dashboard::new("127.0.0.1", Duration::from_secs(600), Units::Metric);
+    dashboard.push(sample(39, -57));
+    let titles = [
+        " Charging ",
+        " Current (A) ",
+        " Voltage (V) ",
+        " Temperature (°C) ",
+        " Wi-Fi ",
+        " Lifetime ",
+    ];
+    let visible = |dashboard: &Dashboard| -> Vec<&str> {
+        titles
+            .into_iter()
+            .filter(|title| shows(dashboard, title))
+            .collect()
+    };
+    assert_eq!(visible(&dashboard), titles);
+
+    // Zooming shows only the focused panel, the state one at first
+    assert!(press(&mut dashboard, KeyCode::Enter));
+    assert_eq!(visible(&dashboard), [" Charging "]);
+    assert!(press(&mut dashboard, KeyCode::Tab));
+    assert_eq!(visible(&dashboard), [" Current (A) "]);
+    assert!(press(&mut dashboard, KeyCode::Down));
+    assert!(press(&mut dashboard, KeyCode::Right));
+    assert_eq!(visible(&dashboard), [" Temperature (°C) "]);
+    assert!(press(&mut dashboard, KeyCode::Up));
+    assert_eq!(visible(&dashboard), [" Voltage (V) "]);
+
+    // Wrapping around both ways
+    for _ in 0..4 {
+        press(&mut dashboard, KeyCode::Tab);
+    }
+    assert_eq!(visible(&dashboard), [" Charging "]);
+    assert!(press(&mut dashboard, KeyCode::BackTab));
+    assert_eq!(visible(&dashboard), [" Lifetime "]);
+    assert!(press(&mut dashboard, KeyCode::Left));
+    assert_eq!(visible(&dashboard), [" Wi-Fi "]);
+
+    // Other keys are ignored, z zooms out again
+    assert!(press(&mut dashboard, KeyCode::Char('x')));
+    assert_eq!(visible(&dashboard), [" Wi-Fi "]);
+    assert!(press(&mut dashboard, KeyCode::Char('z')));
+    assert_eq!(visible(&dashboard), titles);
+}
+
+#[test]
+fn exit_keys() {
+    let mut dashboard = Dashboard::new("127.0.0.1", Duration::from_secs(600), Units::Metric);
+    assert!(!press(&mut dashboard, KeyCode::Esc));
+    assert!(!press(&mut dashboard, KeyCode::Char('q')));
+    assert!(!dashboard.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)));
+    assert!(press(&mut dashboard, KeyCode::Char('c')));
+    // q with a modifier is ignored
+    assert!(dashboard.handle_key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::ALT)));
+}
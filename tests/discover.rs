@@ -0,0 +1,49 @@
+//! Scanning for wall connectors among several local stand-ins.
+
+mod common;
+
+use common::{FakeConnector, Fault};
+use std::net::{Ipv4Addr, TcpListener};
+use std::time::Duration;
+use tesla_wallcon_monitor::discover::{Cidr, discover};
+
+#[test]
+fn finds_connectors_on_several_ports() {
+    let first = FakeConnector::start();
+    let second = FakeConnector::start();
+    let broken = FakeConnector::start();
+    broken.inject(Fault::MissingField("serial_number"));
+    // A port nobody listens on
+    let closed = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let cidr: Cidr = "127.0.0.1".parse().unwrap();
+    let ports = [first.port(), closed, broken.port(), second.port()];
+    let found = discover(&cidr, &ports, Duration::from_secs(2), 4).unwrap();
+
+    let mut expected = vec![first.port(), second.port()];
+    expected.sort();
+    let ports: Vec<u16> = found.iter().map(|f| f.port).collect();
+    assert_eq!(ports, expected);
+    assert_eq!(found[0].version.serial_number, "TWC123456789");
+    assert_eq!(found[0].addr(), format!("127.0.0.1:{}", expected[0]));
+}
+
+#[test]
+fn cidr_ranges() {
+    let cidr: Cidr = "192.168.1.77/24".parse().unwrap();
+    assert_eq!(cidr.to_string(), "192.168.1.0/24");
+    let hosts: Vec<Ipv4Addr> = cidr.hosts().collect();
+    assert_eq!(hosts.len(), 254);
+    assert_eq!(hosts[0], Ipv4Addr::new(192, 168, 1, 1));
+    assert_eq!(hosts[253], Ipv4Addr::new(192, 168, 1, 254));
+
+    assert_eq!("10.0.0.4/31".parse::<Cidr>().unwrap().hosts().count(), 2);
+    assert_eq!("10.0.0.4".parse::<Cidr>().unwrap().hosts().count(), 1);
+    assert!("10.0.0.0/8".parse::<Cidr>().is_err());
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    assert!("wallcon/24".parse::<Cidr>().is_err());
+}
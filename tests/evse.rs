@@ -0,0 +1,77 @@
+//! Mapping of the numeric status codes to their typed variants and back.
+
+mod common;
+
+use serde_json::json;
+use tesla_wallcon_monitor::evse::{ConfigStatus, EvseState, NotReadyReason};
+
+#[test]
+fn codes_map_to_variants() {
+    let states = [
+        (0, EvseState::Booting),
+        (1, EvseState::NotConnected),
+        (2, EvseState::Connected),
+        (3, EvseState::Connecting),
+        (4, EvseState::Ready),
+        (6, EvseState::Negotiating),
+        (7, EvseState::Error),
+        (8, EvseState::ChargingFinished),
+        (9, EvseState::WaitingForCar),
+        (10, EvseState::ChargingReduced),
+        (11, EvseState::Charging),
+    ];
+    for (code, state) in states {
+        assert_eq!(EvseState::from(code), state);
+        assert_eq!(state.code(), code);
+        assert_eq!(u32::from(state), code);
+    }
+    assert_eq!(ConfigStatus::from(5), ConfigStatus::Configured);
+    assert_eq!(NotReadyReason::from(8), NotReadyReason::NoVehicle);
+    assert_eq!(NotReadyReason::VehicleNotReady.code(), 4);
+
+    assert_eq!(EvseState::Charging.description(), "Charging");
+    assert_eq!(EvseState::WaitingForCar.to_string(), "Waiting for car (9)");
+}
+
+#[test]
+fn unknown_codes_round_trip() {
+    for code in [5, 12, 255, u32::MAX] {
+        let state = EvseState::from(code);
+        assert_eq!(state, EvseState::Unknown(code));
+        assert_eq!(state.code(), code);
+        assert_eq!(state.description(), "Unknown");
+        assert_eq!(state.to_string(), format!("Unknown ({})", code));
+    }
+    assert_eq!(ConfigStatus::from(4), ConfigStatus::Unknown(4));
+    assert_eq!(NotReadyReason::from(0), NotReadyReason::Unknown(0));
+}
+
+#[test]
+fn serde_keeps_the_numbers() {
+    let state: EvseState = serde_json::from_value(json!(42)).unwrap();
+    assert_eq!(state, EvseState::Unknown(42));
+    assert_eq!(serde_json::to_value(state).unwrap(), json!(42));
+    assert_eq!(
+        serde_json::to_value(EvseState::Charging).unwrap(),
+        json!(11)
+    );
+    assert!(serde_json::from_value::<EvseState>(json!("Charging")).is_err());
+    assert!(serde_json::from_value::<EvseState>(json!(-1)).is_err());
+
+    // New firmware values don't break the vitals
+    let vitals = common::vitals_with(&[
+        ("evse_state", json!(99)),
+        ("config_status", json!(6)),
+        ("evse_not_ready_reasons", json!([1, 16])),
+    ]);
+    assert_eq!(vitals.evse_state, EvseState::Unknown(99));
+    assert_eq!(vitals.config_status, ConfigStatus::Unknown(6));
+    assert_eq!(
+        vitals.evse_not_ready_reasons,
+        [NotReadyReason::VehicleSession, NotReadyReason::Unknown(16)]
+    );
+    let value = serde_json::to_value(&vitals).unwrap();
+    assert_eq!(value["evse_state"], 99);
+    assert_eq!(value["config_status"], 6);
+    assert_eq!(value["evse_not_ready_reasons"], json!([1, 16]));
+}
@@ -0,0 +1,115 @@
+//! The Prometheus text exposition rendered for one scrape.
+
+mod common;
+
+use tesla_wallcon_monitor::exporter::render_metrics;
+use tesla_wallcon_monitor::{Lifetime, Version, Vitals, WifiStatus};
+
+fn version() -> Version {
+    serde_json::from_str(common::VERSION).unwrap()
+}
+
+fn render() -> String {
+    let vitals: Vitals = serde_json::from_str(common::VITALS).unwrap();
+    let lifetime: Lifetime = serde_json::from_str(common::LIFETIME).unwrap();
+    let wifi: WifiStatus = serde_json::from_str(common::WIFI_STATUS).unwrap();
+    render_metrics(&version(), Some((&vitals, &lifetime, &wifi)))
+}
+
+#[test]
+fn every_metric_has_help_type_and_serial() {
+    let metrics = render();
+    let mut declared = Vec::new();
+    let mut lines = metrics.lines().peekable();
+    while let Some(help) = lines.next() {
+        let help = help.strip_prefix("# HELP ").unwrap();
+        let (name, text) = help.split_once(' ').unwrap();
+        assert!(name.starts_with("tesla_wallcon_"), "{}", name);
+        // Base units spelled out
+        for unit in ["_wh", "_db", "_dbm", "_hz_", "_v_", "_a_"] {
+            assert!(!format!("{}_", name).contains(unit), "{}", name);
+        }
+        assert!(!text.is_empty());
+        let kind = lines.next().unwrap();
+        let kind = kind
+            .strip_prefix(&format!("# TYPE {} ", name))
+            .unwrap_or_else(|| panic!("no TYPE for {}", name));
+        assert!(kind == "gauge" || kind == "counter", "{}", kind);
+        // Counters are named as such
+        assert_eq!(kind == "counter", name.ends_with("_total"), "{}", name);
+
+        let mut samples = 0;
+        while let Some(sample) = lines.next_if(|line| !line.starts_with('#')) {
+            let prefix = format!("{}{{serial=\"TWC123456789\"", name);
+            assert!(sample.starts_with(&prefix), "{}", sample);
+            let (_, value) = sample.rsplit_once("} ").unwrap();
+            assert!(value.parse::<f64>().is_ok(), "{}", sample);
+            samples += 1;
+        }
+        assert!(samples > 0, "no samples for {}", name);
+        declared.push(name.to_string());
+    }
+
+    let mut unique = declared.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), declared.len());
+    assert_eq!(declared.len(), 36);
+}
+
+#[test]
+fn samples_carry_the_values_and_labels() {
+    let metrics = render();
+    for sample in [
+        r#"tesla_wallcon_info{serial="TWC123456789",part_number="1529455-02-D",firmware_version="25.34.1+ge48cc9be91ebc7"} 1"#,
+        r#"tesla_wallcon_up{serial="TWC123456789"} 1"#,
+        r#"tesla_wallcon_vehicle_connected{serial="TWC123456789"} 1"#,
+        r#"tesla_wallcon_grid_volts{serial="TWC123456789"} 251.5"#,
+        r#"tesla_wallcon_phase_current_amperes{serial="TWC123456789",phase="a"} 0"#,
+        r#"tesla_wallcon_phase_current_amperes{serial="TWC123456789",phase="b"} 7.9"#,
+        r#"tesla_wallcon_phase_voltage_volts{serial="TWC123456789",phase="c"} 117.9"#,
+        r#"tesla_wallcon_temperature_celsius{serial="TWC123456789",sensor="handle"} 14.1"#,
+        r#"tesla_wallcon_pilot_volts{serial="TWC123456789",level="low"} 4.4"#,
+        r#"tesla_wallcon_input_thermopile_microvolts{serial="TWC123456789"} -67"#,
+        r#"tesla_wallcon_evse_state{serial="TWC123456789"} 11"#,
+        r#"tesla_wallcon_not_ready_reasons{serial="TWC123456789"} 1"#,
+        r#"tesla_wallcon_energy_joules_total{serial="TWC123456789"} 18995832000"#,
+        r#"tesla_wallcon_alerts_total{serial="TWC123456789"} 2243"#,
+        r#"tesla_wallcon_wifi_rssi_decibel_milliwatts{serial="TWC123456789"} -57"#,
+        r#"tesla_wallcon_session_energy_joules{serial="TWC123456789"} 1800"#,
+    ] {
+        assert!(metrics.lines().any(|line| line == sample), "{}", sample);
+    }
+    assert!(metrics.contains(
+        "# HELP tesla_wallcon_energy_joules_total Energy delivered\n\
+         # TYPE tesla_wallcon_energy_joules_total counter\n"
+    ));
+    assert!(metrics.ends_with("} 36\n"));
+}
+
+#[test]
+fn failed_fetch_renders_only_info_and_up() {
+    let metrics = render_metrics(&version(), None);
+    assert_eq!(
+        metrics,
+        "# HELP tesla_wallcon_info Wall connector device information\n\
+         # TYPE tesla_wallcon_info gauge\n\
+         tesla_wallcon_info{serial=\"TWC123456789\",part_number=\"1529455-02-D\",firmware_version=\"25.34.1+ge48cc9be91ebc7\"} 1\n\
+         # HELP tesla_wallcon_up Whether the last fetch from the wall connector succeeded\n\
+         # TYPE tesla_wallcon_up gauge\n\
+         tesla_wallcon_up{serial=\"TWC123456789\"} 0\n"
+    );
+}
+
+#[test]
+fn label_values_are_escaped() {
+    let mut version = version();
+    version.serial_number = "a\"b\\c\nd".to_string();
+    version.firmware_version = "1.0 \"beta\"".to_string();
+    let metrics = render_metrics(&version, None);
+    assert!(metrics.contains(
+        r#"tesla_wallcon_info{serial="a\"b\\c\nd",part_number="1529455-02-D",firmware_version="1.0 \"beta\""} 1"#
+    ));
+    assert!(metrics.contains(r#"tesla_wallcon_up{serial="a\"b\\c\nd"} 0"#));
+    assert_eq!(metrics.lines().count(), 6);
+}
@@ -0,0 +1,130 @@
+//! Parsing device lists and polling several connectors at once.
+
+mod common;
+
+use common::{FakeConnector, Fault};
+use std::collections::BTreeMap;
+use tesla_wallcon_monitor::fleet::{Device, parse_devices, poll_vitals};
+
+fn named() -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("home".to_string(), "192.168.1.221".to_string()),
+        ("garage".to_string(), "192.168.1.222:8080".to_string()),
+    ])
+}
+
+/// Name and URL of the vitals of each device.
+fn parsed(list: &str) -> Vec<(String, String)> {
+    parse_devices(list, &named())
+        .unwrap()
+        .iter()
+        .map(|device| (device.name.clone(), device.client.url("vitals")))
+        .collect()
+}
+
+#[test]
+fn entries_are_addresses_pairs_or_config_names() {
+    assert_eq!(
+        parsed("192.168.1.50, barn=10.0.0.7 ,home"),
+        [
+            (
+                "192.168.1.50".to_string(),
+                "http://192.168.1.50/api/1/vitals".to_string()
+            ),
+            (
+                "barn".to_string(),
+                "http://10.0.0.7/api/1/vitals".to_string()
+            ),
+            (
+                "home".to_string(),
+                "http://192.168.1.221/api/1/vitals".to_string()
+            ),
+        ]
+    );
+
+    // NAME=ADDR is taken as given, even for a name of the config
+    assert_eq!(
+        parsed("home=10.0.0.9,garage"),
+        [
+            (
+                "home".to_string(),
+                "http://10.0.0.9/api/1/vitals".to_string()
+            ),
+            (
+                "garage".to_string(),
+                "http://192.168.1.222:8080/api/1/vitals".to_string()
+            ),
+        ]
+    );
+    // Anything else is an address
+    let device = Device::parse("shed", &named()).unwrap();
+    assert_eq!(device.client.addr(), "shed");
+}
+
+#[test]
+fn bad_lists_are_rejected() {
+    for list in [
+        "",
+        "home,",
+        ",home",
+        "home,,garage",
+        "=10.0.0.7",
+        "barn=",
+        " = ",
+    ] {
+        assert!(parse_devices(list, &named()).is_err(), "{:?}", list);
+    }
+
+    // Names must be unique, whichever way they are given
+    for list in [
+        "home,home",
+        "home,home=10.0.0.9",
+        "10.0.0.7,10.0.0.7",
+        "barn=10.0.0.7,barn=10.0.0.8",
+    ] {
+        let error = parse_devices(list, &named()).unwrap_err().to_string();
+        assert!(
+            error.contains("given more than once"),
+            "{}: {}",
+            list,
+            error
+        );
+    }
+    // Different names for one address are fine
+    assert_eq!(parsed("home,main=192.168.1.221").len(), 2);
+}
+
+#[test]
+fn polling_keeps_the_order_and_reports_failures() {
+    let first = FakeConnector::start();
+    let second = FakeConnector::start();
+    first.set_vitals("session_s", 11);
+    second.set_vitals("session_s", 22);
+    second.inject(Fault::Status(500));
+    let devices = [
+        Device {
+            name: "second".to_string(),
+            client: second.client(),
+        },
+        Device {
+            name: "first".to_string(),
+            client: first.client(),
+        },
+    ];
+
+    let results = poll_vitals(&devices);
+    let names: Vec<_> = results.iter().map(|result| result.name.as_str()).collect();
+    assert_eq!(names, ["second", "first"]);
+    assert!(results.iter().all(|result| result.addr == "127.0.0.1"));
+    assert!(results[0].vitals.is_err());
+    assert_eq!(results[1].vitals.as_ref().unwrap().session_s, 11);
+
+    // The fault was used up
+    let results = poll_vitals(&devices);
+    assert_eq!(results[0].vitals.as_ref().unwrap().session_s, 22);
+    assert_eq!(results[1].vitals.as_ref().unwrap().session_s, 11);
+    assert_eq!(first.requests(), ["/api/1/vitals"; 2]);
+    assert_eq!(second.requests(), ["/api/1/vitals"; 2]);
+
+    assert!(poll_vitals(&[]).is_empty());
+}
@@ -0,0 +1,113 @@
+//! The `--log` files, written by the JSON Lines logger and read back in
+//! both formats.
+
+mod common;
+
+use common::{FakeConnector, Fault};
+use log::LevelFilter;
+use serde_json::Value;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tesla_wallcon_monitor::logfile::{JsonLine, JsonLogger, LogFile};
+
+#[test]
+fn json_lines_read_back_like_text() {
+    let text = LogFile::read(Path::new("data/logs5tt-d-2.txt")).unwrap();
+    let mut jsonl: Vec<String> = text
+        .records
+        .iter()
+        .map(|record| {
+            let line = JsonLine {
+                timestamp: record.timestamp,
+                addr: "192.168.1.221".to_string(),
+                endpoint: record.payload.endpoint().to_string(),
+                status: 200,
+                latency_ms: 12.5,
+                payload: record.payload.to_value(),
+            };
+            serde_json::to_string(&line).unwrap()
+        })
+        .collect();
+    jsonl.insert(
+        1,
+        r#"{"timestamp":"2025-12-23T02:54:31Z","level":"WARN","message":"scrape failed"}"#
+            .to_string(),
+    );
+
+    let json = LogFile::parse(&jsonl.join("\n"));
+    assert_eq!(json.skipped.len(), 1);
+    assert_eq!(json.skipped[0].line, 2);
+    assert_eq!(json.skipped[0].reason, "not a response");
+    assert_eq!(json.records.len(), text.records.len());
+    for (json, text) in json.records.iter().zip(&text.records) {
+        assert_eq!(json.timestamp, text.timestamp);
+        assert_eq!(json.payload.to_value(), text.payload.to_value());
+    }
+}
+
+/// Log output shared with the test.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(data)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn logger_writes_every_response() {
+    let buffer = Buffer::default();
+    JsonLogger::new(LevelFilter::Info, buffer.clone())
+        .init()
+        .unwrap();
+    let fake = FakeConnector::start();
+    let client = fake.client();
+    client.vitals().unwrap();
+    fake.inject(Fault::Status(503));
+    assert!(client.lifetime().is_err());
+
+    let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<Value> = text
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    let names: Vec<&str> = lines[0]
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "timestamp",
+            "addr",
+            "endpoint",
+            "status",
+            "latency_ms",
+            "payload"
+        ]
+    );
+    assert_eq!(lines[0]["addr"], "127.0.0.1");
+    assert_eq!(lines[0]["endpoint"], "vitals");
+    assert_eq!(lines[0]["status"], 200);
+    assert!(lines[0]["latency_ms"].as_f64().unwrap() > 0.0);
+    assert_eq!(lines[0]["payload"]["grid_v"], 251.5);
+    assert_eq!(lines[1]["endpoint"], "lifetime");
+    assert_eq!(lines[1]["status"], 503);
+    assert_eq!(lines[1]["payload"]["alert_count"], 2243);
+
+    // Only the successful response reads back as a record
+    let log = LogFile::parse(&text);
+    assert_eq!(log.records.len(), 1);
+    assert_eq!(log.records[0].payload.endpoint(), "vitals");
+    assert_eq!(log.skipped.len(), 1);
+    assert_eq!(log.skipped[0].reason, "HTTP status 503");
+}
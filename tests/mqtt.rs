@@ -0,0 +1,207 @@
+//! Topics and Home Assistant discovery configs of the MQTT publisher.
+
+mod common;
+
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::time::Duration;
+use tesla_wallcon_monitor::mqtt::{LIFETIME_COUNTERS, MqttConfig, MqttPublisher, QUEUE_CAPACITY};
+use tesla_wallcon_monitor::{Lifetime, Version, Vitals, WifiStatus};
+
+fn version() -> Version {
+    serde_json::from_str(common::VERSION).unwrap()
+}
+
+fn configs<T: serde::Serialize>(
+    config: &MqttConfig,
+    endpoint: &str,
+    sample: &T,
+    counters: &[&str],
+) -> BTreeMap<String, Value> {
+    config
+        .discovery_configs(&version(), endpoint, sample, counters)
+        .unwrap()
+        .into_iter()
+        .collect()
+}
+
+#[test]
+fn broker_and_topics() {
+    let config = MqttConfig::new("broker.lan").unwrap();
+    assert_eq!((config.host.as_str(), config.port), ("broker.lan", 1883));
+    let config = MqttConfig::new("10.0.0.2:8883").unwrap();
+    assert_eq!((config.host.as_str(), config.port), ("10.0.0.2", 8883));
+    assert!(MqttConfig::new("broker.lan:mqtt").is_err());
+
+    assert_eq!(
+        config.topic("TWC123456789", "vitals", "grid_v"),
+        "tesla_wallcon/TWC123456789/vitals/grid_v"
+    );
+    assert_eq!(
+        config.availability_topic("TWC123456789"),
+        "tesla_wallcon/TWC123456789/availability"
+    );
+}
+
+#[test]
+fn vitals_discovery() {
+    let vitals: Vitals = serde_json::from_str(common::VITALS).unwrap();
+    let config = MqttConfig::new("broker.lan").unwrap();
+    let configs = configs(&config, "vitals", &vitals, &["session_energy_wh"]);
+
+    assert_eq!(
+        configs["homeassistant/sensor/TWC123456789/vitals_grid_v/config"],
+        json!({
+            "name": "grid v",
+            "unique_id": "TWC123456789_vitals_grid_v",
+            "state_topic": "tesla_wallcon/TWC123456789/vitals/grid_v",
+            "availability_topic": "tesla_wallcon/TWC123456789/availability",
+            "device": {
+                "identifiers": ["TWC123456789"],
+                "name": "Tesla Wall Connector TWC123456789",
+                "manufacturer": "Tesla",
+                "model": "1529455-02-D",
+                "serial_number": "TWC123456789",
+                "sw_version": "25.34.1+ge48cc9be91ebc7",
+            },
+            "state_class": "measurement",
+            "unit_of_measurement": "V",
+            "device_class": "voltage",
+        })
+    );
+
+    // Booleans are binary sensors without units
+    let connected =
+        &configs["homeassistant/binary_sensor/TWC123456789/vitals_vehicle_connected/config"];
+    assert_eq!(
+        connected["state_topic"],
+        "tesla_wallcon/TWC123456789/vitals/vehicle_connected"
+    );
+    assert!(connected.get("state_class").is_none());
+    assert!(connected.get("unit_of_measurement").is_none());
+
+    let energy = &configs["homeassistant/sensor/TWC123456789/vitals_session_energy_wh/config"];
+    assert_eq!(energy["state_class"], "total_increasing");
+    assert_eq!(energy["unit_of_measurement"], "Wh");
+    assert_eq!(energy["device_class"], "energy");
+    let handle = &configs["homeassistant/sensor/TWC123456789/vitals_handle_temp_c/config"];
+    assert_eq!(handle["unit_of_measurement"], "°C");
+    assert_eq!(handle["device_class"], "temperature");
+    let current = &configs["homeassistant/sensor/TWC123456789/vitals_currentB_a/config"];
+    assert_eq!(current["device_class"], "current");
+    // Numeric codes have no unit
+    let state = &configs["homeassistant/sensor/TWC123456789/vitals_evse_state/config"];
+    assert_eq!(state["state_class"], "measurement");
+    assert!(state.get("device_class").is_none());
+
+    // Lists don't get a sensor
+    assert!(!configs.keys().any(|topic| topic.contains("current_alerts")));
+    assert!(
+        !configs
+            .keys()
+            .any(|topic| topic.contains("not_ready_reasons"))
+    );
+    let fields = serde_json::to_value(&vitals).unwrap();
+    assert_eq!(configs.len(), fields.as_object().unwrap().len() - 2);
+}
+
+#[test]
+fn lifetime_and_wifi_discovery() {
+    let lifetime: Lifetime = serde_json::from_str(common::LIFETIME).unwrap();
+    let wifi: WifiStatus = serde_json::from_str(common::WIFI_STATUS).unwrap();
+    let mut config = MqttConfig::new("broker.lan").unwrap();
+    config.base_topic = "garage".to_string();
+    config.discovery_prefix = "ha".to_string();
+
+    let lifetime = configs(&config, "lifetime", &lifetime, LIFETIME_COUNTERS);
+    for (topic, config) in &lifetime {
+        assert!(
+            topic.starts_with("ha/sensor/TWC123456789/lifetime_"),
+            "{}",
+            topic
+        );
+        assert!(
+            config["state_topic"]
+                .as_str()
+                .unwrap()
+                .starts_with("garage/TWC123456789/lifetime/")
+        );
+        assert_eq!(
+            config["availability_topic"],
+            "garage/TWC123456789/availability"
+        );
+    }
+    let uptime = &lifetime["ha/sensor/TWC123456789/lifetime_uptime_s/config"];
+    assert_eq!(uptime["state_class"], "total_increasing");
+    assert_eq!(uptime["device_class"], "duration");
+    assert_eq!(uptime["unit_of_measurement"], "s");
+    let temp = &lifetime["ha/sensor/TWC123456789/lifetime_avg_startup_temp/config"];
+    assert_eq!(temp["state_class"], "measurement");
+    assert_eq!(temp["unit_of_measurement"], "°C");
+
+    let wifi = configs(&config, "wifi_status", &wifi, &[]);
+    let rssi = &wifi["ha/sensor/TWC123456789/wifi_status_wifi_rssi/config"];
+    assert_eq!(rssi["unit_of_measurement"], "dBm");
+    assert_eq!(rssi["device_class"], "signal_strength");
+    // Strings are sensors without a state class
+    let ssid = &wifi["ha/sensor/TWC123456789/wifi_status_wifi_ssid/config"];
+    assert!(ssid.get("state_class").is_none());
+    assert!(wifi.contains_key("ha/binary_sensor/TWC123456789/wifi_status_internet/config"));
+
+    let config = MqttConfig::new("broker.lan").unwrap();
+    assert!(
+        config
+            .discovery_configs(&version(), "vitals", &[1, 2], &[])
+            .is_err()
+    );
+}
+
+#[test]
+fn broker_down_drops_instead_of_blocking() {
+    // Nothing listens on a port just released
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let config = MqttConfig::new(&format!("127.0.0.1:{}", port)).unwrap();
+    let vitals: Vitals = serde_json::from_str(common::VITALS).unwrap();
+    let fields = serde_json::to_value(&vitals)
+        .unwrap()
+        .as_object()
+        .unwrap()
+        .len();
+
+    let (done, finished) = mpsc::channel();
+    std::thread::spawn(move || {
+        let publisher = MqttPublisher::connect(config, "TWC123456789");
+        publisher.publish_availability(true).unwrap();
+        // Enough polls to fill the queue several times over
+        for _ in 0..4 * QUEUE_CAPACITY / fields + 1 {
+            publisher.publish("vitals", &vitals).unwrap();
+        }
+        let dropped = publisher.dropped();
+        publisher.disconnect().unwrap();
+        done.send(dropped).unwrap();
+    });
+    let dropped = finished
+        .recv_timeout(Duration::from_secs(10))
+        .expect("publishing blocked while the broker is down");
+    assert!(dropped as usize >= 3 * QUEUE_CAPACITY, "{}", dropped);
+
+    // Nor does disconnecting with only a few messages queued
+    let config = MqttConfig::new(&format!("127.0.0.1:{}", port)).unwrap();
+    let (done, finished) = mpsc::channel();
+    std::thread::spawn(move || {
+        let publisher = MqttPublisher::connect(config, "TWC123456789");
+        publisher.publish_availability(true).unwrap();
+        publisher.disconnect().unwrap();
+        done.send(publisher.dropped()).unwrap();
+    });
+    let dropped = finished
+        .recv_timeout(Duration::from_secs(10))
+        .expect("disconnecting blocked while the broker is down");
+    assert_eq!(dropped, 0);
+}
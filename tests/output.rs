@@ -0,0 +1,79 @@
+//! Machine readable output of the single-shot commands.
+
+mod common;
+
+use serde_json::Value;
+use tesla_wallcon_monitor::format::Units;
+use tesla_wallcon_monitor::logfile::Payload;
+use tesla_wallcon_monitor::output::{OutputFormat, Printer};
+
+fn payload(endpoint: &str, json: &str) -> Payload {
+    Payload::parse(endpoint, json).unwrap()
+}
+
+#[test]
+fn json_has_decoded_ssid_next_to_raw() {
+    let mut printer = Printer::new(OutputFormat::Json, Units::Metric);
+    let line = printer.render(&payload("wifi_status", common::WIFI_STATUS), None);
+    let json: Value = serde_json::from_str(&line).unwrap();
+    let names: Vec<&str> = json
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    assert_eq!(&names[..2], ["wifi_ssid", "wifi_ssid_decoded"]);
+    assert_eq!(json["wifi_ssid"], "TXlOZXR3b3Jr");
+    assert_eq!(json["wifi_ssid_decoded"], "MyNetwork");
+}
+
+#[test]
+fn machine_formats_ignore_display_units() {
+    let vitals = payload("vitals", common::VITALS);
+    let mut metric = Printer::new(OutputFormat::KeyValue, Units::Metric);
+    let mut imperial = Printer::new(OutputFormat::KeyValue, Units::Imperial);
+    let text = metric.render(&vitals, None);
+    assert_eq!(text, imperial.render(&vitals, None));
+    assert!(text.lines().any(|line| line == "handle_temp_c=14.1"));
+    assert!(text.lines().any(|line| line == "evse_not_ready_reasons=1"));
+    assert!(text.lines().any(|line| line == "current_alerts=\"\""));
+}
+
+#[test]
+fn csv_header_once() {
+    let version = payload("version", common::VERSION);
+    let mut printer = Printer::new(OutputFormat::Csv, Units::Metric);
+    printer.device = Some("home".to_string());
+    assert_eq!(
+        printer.render(&version, None),
+        "device,firmware_version,git_branch,part_number,serial_number,web_service\n\
+         home,25.34.1+ge48cc9be91ebc7,HEAD,1529455-02-D,TWC123456789,0.1.0"
+    );
+    printer.device = Some("garage, left".to_string());
+    assert_eq!(
+        printer.render(&version, None),
+        "\"garage, left\",25.34.1+ge48cc9be91ebc7,HEAD,1529455-02-D,TWC123456789,0.1.0"
+    );
+}
+
+#[test]
+fn yaml_documents() {
+    let lifetime = payload("lifetime", common::LIFETIME);
+    let mut printer = Printer::new(OutputFormat::Yaml, Units::Metric);
+    let first = printer.render(&lifetime, None);
+    assert!(first.starts_with("contactor_cycles: 1054\n"));
+    assert!(
+        printer
+            .render(&lifetime, None)
+            .starts_with("---\ncontactor_cycles: 1054\n")
+    );
+}
+
+#[test]
+fn format_names() {
+    for name in ["text", "json", "yaml", "csv", "key=value"] {
+        let format: OutputFormat = name.parse().unwrap();
+        assert_eq!(format.to_string(), name);
+    }
+    assert!("xml".parse::<OutputFormat>().is_err());
+}
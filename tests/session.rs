@@ -0,0 +1,272 @@
+//! Charging sessions from scripted vitals sequences.
+
+mod common;
+
+use serde_json::json;
+use tesla_wallcon_monitor::Vitals;
+use tesla_wallcon_monitor::logfile::{LogFile, LogRecord, Payload, format_line};
+use tesla_wallcon_monitor::session::{
+    self, MAX_SAMPLE_GAP, SessionDetector, SessionEnd, detect_sessions,
+};
+use time::OffsetDateTime;
+use time::macros::datetime;
+
+const START: OffsetDateTime = datetime!(2025-12-23 02:54:00 UTC);
+
+/// A sample `seconds` after [`START`].
+struct Sample {
+    seconds: i64,
+    connected: bool,
+    closed: bool,
+    session_s: u64,
+    uptime_s: u64,
+    current_a: f64,
+    energy_wh: f64,
+}
+
+impl Sample {
+    fn at(seconds: i64) -> Self {
+        Sample {
+            seconds,
+            connected: true,
+            closed: false,
+            session_s: 0,
+            uptime_s: 100_000 + seconds as u64,
+            current_a: 0.0,
+            energy_wh: 0.0,
+        }
+    }
+
+    fn unplugged(mut self) -> Self {
+        self.connected = false;
+        self
+    }
+
+    fn charging(mut self, current_a: f64) -> Self {
+        self.closed = true;
+        self.current_a = current_a;
+        self
+    }
+
+    fn session(mut self, session_s: u64, energy_wh: f64) -> Self {
+        self.session_s = session_s;
+        self.energy_wh = energy_wh;
+        self
+    }
+
+    fn uptime(mut self, uptime_s: u64) -> Self {
+        self.uptime_s = uptime_s;
+        self
+    }
+
+    fn timestamp(&self) -> OffsetDateTime {
+        START + time::Duration::seconds(self.seconds)
+    }
+
+    fn vitals(&self) -> Vitals {
+        common::vitals_with(&[
+            ("vehicle_connected", json!(self.connected)),
+            ("contactor_closed", json!(self.closed)),
+            ("session_s", json!(self.session_s)),
+            ("uptime_s", json!(self.uptime_s)),
+            ("vehicle_current_a", json!(self.current_a)),
+            ("session_energy_wh", json!(self.energy_wh)),
+            ("handle_temp_c", json!(20.0 + self.current_a / 4.0)),
+        ])
+    }
+}
+
+fn at(seconds: i64) -> OffsetDateTime {
+    START + time::Duration::seconds(seconds)
+}
+
+fn detect(samples: &[Sample]) -> Vec<tesla_wallcon_monitor::session::Session> {
+    let vitals: Vec<(OffsetDateTime, Vitals)> = samples
+        .iter()
+        .map(|sample| (sample.timestamp(), sample.vitals()))
+        .collect();
+    detect_sessions(
+        vitals
+            .iter()
+            .map(|(timestamp, vitals)| (*timestamp, vitals)),
+    )
+}
+
+#[test]
+fn unplug_ends_the_session() {
+    let samples = [
+        Sample::at(0).unplugged(),
+        Sample::at(10).session(1, 0.0),
+        Sample::at(20).session(11, 50.0).charging(32.0),
+        Sample::at(30).session(21, 150.0).charging(16.0),
+        Sample::at(40).session(31, 200.0),
+        // The energy keeps counting up to the unplug
+        Sample::at(50).session(41, 210.0).unplugged(),
+        Sample::at(60).unplugged(),
+    ];
+    let mut detector = SessionDetector::new();
+    let mut completed = Vec::new();
+    for sample in &samples {
+        completed.extend(detector.push(sample.timestamp(), &sample.vitals()));
+        if sample.seconds == 30 {
+            let current = detector.current().unwrap();
+            assert_eq!(current.end, SessionEnd::InProgress);
+            assert_eq!(current.avg_current_a, 24.0);
+        }
+    }
+    assert!(detector.current().is_none());
+    assert!(detector.finish().is_none());
+
+    assert_eq!(completed.len(), 1);
+    let session = &completed[0];
+    assert_eq!(session.end, SessionEnd::Unplugged);
+    assert_eq!(session.plugged_in, at(10));
+    assert_eq!(session.contactor_closed, Some(at(20)));
+    assert_eq!(session.contactor_opened, Some(at(40)));
+    assert_eq!(session.ended, at(50));
+    assert_eq!(session.duration(), time::Duration::seconds(40));
+    assert_eq!(session.energy_wh, 210.0);
+    assert_eq!(session.charging_s, 20);
+    assert_eq!(session.peak_current_a, 32.0);
+    assert_eq!(session.avg_current_a, 24.0);
+    assert_eq!(session.peak_handle_temp_c, 28.0);
+    assert_eq!(session.samples, 5);
+}
+
+#[test]
+fn session_reset_while_connected_restarts() {
+    let sessions = detect(&[
+        Sample::at(0).session(5, 10.0).charging(8.0),
+        Sample::at(10).session(15, 30.0).charging(8.0),
+        // An automatic retry resets session_s
+        Sample::at(20).session(2, 0.0),
+        Sample::at(30).session(12, 20.0).charging(10.0),
+        Sample::at(40).session(22, 50.0).unplugged(),
+    ]);
+    let ends: Vec<_> = sessions
+        .iter()
+        .map(|s| (s.plugged_in, s.ended, s.end))
+        .collect();
+    assert_eq!(
+        ends,
+        [
+            (at(0), at(10), SessionEnd::Restarted),
+            (at(20), at(40), SessionEnd::Unplugged),
+        ]
+    );
+    assert_eq!(sessions[0].energy_wh, 30.0);
+    assert_eq!(sessions[0].charging_s, 10);
+    // The charging before the reset isn't counted twice
+    assert_eq!(sessions[1].charging_s, 10);
+    assert_eq!(sessions[1].energy_wh, 50.0);
+    assert_eq!(sessions[1].peak_current_a, 10.0);
+}
+
+#[test]
+fn uptime_going_back_is_a_reboot() {
+    let sessions = detect(&[
+        Sample::at(0).session(100, 500.0).charging(32.0),
+        Sample::at(10).session(110, 600.0).charging(32.0),
+        // Rebooted with the vehicle still plugged in
+        Sample::at(20).session(110, 600.0).uptime(5),
+        Sample::at(30).session(120, 0.0).uptime(15),
+        // Rebooted while unplugged doesn't start a session
+        Sample::at(40).session(130, 0.0).uptime(25).unplugged(),
+        Sample::at(50).uptime(3).unplugged(),
+    ]);
+    let ends: Vec<_> = sessions
+        .iter()
+        .map(|s| (s.plugged_in, s.ended, s.end))
+        .collect();
+    assert_eq!(
+        ends,
+        [
+            (at(0), at(10), SessionEnd::Reboot),
+            (at(20), at(40), SessionEnd::Unplugged),
+        ]
+    );
+    assert_eq!(sessions[0].energy_wh, 600.0);
+    assert_eq!(sessions[1].charging_s, 0);
+    assert_eq!(sessions[1].contactor_closed, None);
+}
+
+#[test]
+fn restarts_are_told_apart() {
+    let previous = Sample::at(0).session(100, 500.0);
+    let restart =
+        |sample: Sample| session::restart(previous.uptime_s, previous.session_s, &sample.vitals());
+    assert_eq!(restart(Sample::at(10).session(110, 600.0)), None);
+    assert_eq!(
+        restart(Sample::at(10).session(5, 0.0)),
+        Some(SessionEnd::Restarted)
+    );
+    assert_eq!(
+        restart(Sample::at(10).session(5, 0.0).uptime(5)),
+        Some(SessionEnd::Reboot)
+    );
+    // Unplugged the counter only starts over with the next plug-in
+    assert_eq!(restart(Sample::at(10).session(5, 0.0).unplugged()), None);
+}
+
+#[test]
+fn connected_at_the_end_is_in_progress() {
+    assert!(detect(&[]).is_empty());
+    assert!(detect(&[Sample::at(0).unplugged(), Sample::at(10).unplugged()]).is_empty());
+
+    // Connected from the first sample on
+    let sessions = detect(&[
+        Sample::at(0).session(300, 40.0).charging(16.0),
+        Sample::at(10).session(310, 45.0).charging(16.0),
+        Sample::at(20).session(320, 50.0),
+    ]);
+    assert_eq!(sessions.len(), 1);
+    let session = &sessions[0];
+    assert_eq!(session.end, SessionEnd::InProgress);
+    assert_eq!(session.plugged_in, at(0));
+    assert_eq!(session.ended, at(20));
+    assert_eq!(session.contactor_opened, Some(at(20)));
+    assert_eq!(session.charging_s, 20);
+    assert_eq!(session.avg_current_a, 16.0);
+}
+
+#[test]
+fn gap_between_logs_isnt_charging() {
+    let log = |samples: &[Sample]| {
+        let lines: Vec<String> = samples
+            .iter()
+            .map(|sample| {
+                format_line(&LogRecord {
+                    timestamp: sample.timestamp(),
+                    payload: Payload::Vitals(sample.vitals()),
+                })
+            })
+            .collect();
+        LogFile::parse(&lines.join("\n"))
+    };
+    let first = log(&[
+        Sample::at(0).session(1, 0.0),
+        Sample::at(10).session(11, 50.0).charging(32.0),
+        Sample::at(20).session(21, 150.0).charging(32.0),
+    ]);
+    // The monitor was down for three hours, still charging at either end
+    let second = log(&[
+        Sample::at(10_820).session(10_821, 9000.0).charging(32.0),
+        Sample::at(10_830).session(10_831, 9100.0).charging(32.0),
+        Sample::at(10_840).session(10_841, 9150.0).unplugged(),
+    ]);
+    let sessions = detect_sessions(first.vitals().chain(second.vitals()));
+    assert_eq!(sessions.len(), 1);
+    let session = &sessions[0];
+    assert_eq!(session.end, SessionEnd::Unplugged);
+    assert_eq!(session.duration(), time::Duration::seconds(10_840));
+    assert_eq!(session.energy_wh, 9150.0);
+    assert_eq!(session.charging_s, 30);
+
+    // Gaps up to the limit still count
+    let gap = MAX_SAMPLE_GAP.whole_seconds();
+    let sessions = detect(&[
+        Sample::at(0).session(1, 0.0).charging(16.0),
+        Sample::at(gap).session(gap as u64 + 1, 100.0).unplugged(),
+    ]);
+    assert_eq!(sessions[0].charging_s, gap as u64);
+}
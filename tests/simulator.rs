@@ -0,0 +1,180 @@
+//! The scripted scenario, replaying logs and serving them over HTTP.
+
+mod common;
+
+use serde_json::json;
+use std::net::TcpListener;
+use std::time::Duration;
+use tesla_wallcon_monitor::evse::EvseState;
+use tesla_wallcon_monitor::logfile::{LogFile, LogRecord, Payload};
+use tesla_wallcon_monitor::session::{SessionEnd, detect_sessions};
+use tesla_wallcon_monitor::simulator::{
+    SCENARIO_CYCLE_S, Simulator, payload_json, scenario_lifetime, scenario_vitals,
+};
+use tesla_wallcon_monitor::{Version, WallConnectorClient};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use time::macros::datetime;
+
+const START: OffsetDateTime = datetime!(2025-12-23 02:54:00 UTC);
+
+fn record(seconds: i64, payload: Payload) -> LogRecord {
+    LogRecord {
+        timestamp: START + time::Duration::seconds(seconds),
+        payload,
+    }
+}
+
+/// `record` as `--log` writes it.
+fn log_line(record: &LogRecord) -> String {
+    format!(
+        "{} [INFO] {}: {}",
+        record.timestamp.format(&Rfc3339).unwrap(),
+        record.payload.endpoint(),
+        payload_json(&record.payload).unwrap()
+    )
+}
+
+fn secs(seconds: f64) -> Duration {
+    Duration::from_secs_f64(seconds)
+}
+
+fn session_at(simulator: &Simulator, seconds: f64) -> u64 {
+    match simulator.payload("vitals", secs(seconds)).unwrap() {
+        Payload::Vitals(vitals) => vitals.session_s,
+        other => panic!("{} instead of vitals", other.endpoint()),
+    }
+}
+
+#[test]
+fn scenario_plugs_in_charges_and_unplugs() {
+    let unplugged = scenario_vitals(30.0);
+    assert!(!unplugged.vehicle_connected);
+    assert_eq!(unplugged.evse_state, EvseState::NotConnected);
+    assert_eq!(unplugged.session_energy_wh, 0.0);
+
+    let waiting = scenario_vitals(65.0);
+    assert!(waiting.vehicle_connected && !waiting.contactor_closed);
+    assert_eq!(waiting.evse_state, EvseState::WaitingForCar);
+    assert_eq!(waiting.session_s, 5);
+
+    // Ramps up to 32 A within ten seconds
+    assert_eq!(scenario_vitals(75.0).vehicle_current_a, 16.0);
+    let charging = scenario_vitals(200.0);
+    assert!(charging.contactor_closed);
+    assert_eq!(charging.evse_state, EvseState::Charging);
+    assert_eq!(charging.vehicle_current_a, 32.0);
+    assert!(charging.handle_temp_c > waiting.handle_temp_c);
+
+    let done = scenario_vitals(380.0);
+    assert_eq!(done.evse_state, EvseState::WaitingForCar);
+    assert_eq!(done.vehicle_current_a, 0.0);
+    // 32 A for 295 s at 245 V
+    assert_eq!(done.session_energy_wh, 642.444);
+    // Kept after unplugging until the next plug-in
+    let next = scenario_vitals(SCENARIO_CYCLE_S as f64 + 30.0);
+    assert!(!next.vehicle_connected);
+    assert_eq!(next.session_energy_wh, 642.444);
+    assert_eq!(next.uptime_s, unplugged.uptime_s + SCENARIO_CYCLE_S);
+}
+
+#[test]
+fn scenario_lifetime_follows_the_cycles() {
+    let start = scenario_lifetime(0.0);
+    let mut previous = start.clone();
+    for seconds in (0..3 * SCENARIO_CYCLE_S).step_by(5) {
+        let lifetime = scenario_lifetime(seconds as f64);
+        assert!(lifetime.energy_wh >= previous.energy_wh);
+        assert!(lifetime.charging_time_s >= previous.charging_time_s);
+        assert!(lifetime.charge_starts >= previous.charge_starts);
+        previous = lifetime;
+    }
+    let after = scenario_lifetime(2.0 * SCENARIO_CYCLE_S as f64);
+    assert_eq!(after.charge_starts, start.charge_starts + 2);
+    assert_eq!(after.connector_cycles, start.connector_cycles + 2);
+    assert_eq!(after.charging_time_s, start.charging_time_s + 600);
+    assert_eq!(after.energy_wh, start.energy_wh + 1284);
+
+    // Each cycle is one session to the session detector
+    let vitals: Vec<_> = (0..2 * SCENARIO_CYCLE_S + 30)
+        .step_by(10)
+        .map(|seconds| {
+            (
+                START + time::Duration::seconds(seconds as i64),
+                scenario_vitals(seconds as f64),
+            )
+        })
+        .collect();
+    let sessions = detect_sessions(vitals.iter().map(|(at, vitals)| (*at, vitals)));
+    assert_eq!(sessions.len(), 2);
+    for session in &sessions {
+        assert_eq!(session.end, SessionEnd::Unplugged);
+        assert_eq!(session.charging_s, 300);
+        assert_eq!(session.peak_current_a, 32.0);
+    }
+}
+
+#[test]
+fn replay_steps_through_the_log_and_starts_over() {
+    let vitals =
+        |session_s: u64| Payload::Vitals(common::vitals_with(&[("session_s", json!(session_s))]));
+    let version = Payload::Version(serde_json::from_str(common::VERSION).unwrap());
+    let log: Vec<String> = [
+        record(0, vitals(1)),
+        record(10, vitals(2)),
+        record(5, version),
+        record(20, vitals(3)),
+    ]
+    .iter()
+    .map(log_line)
+    .collect();
+    let records = LogFile::parse(&log.join("\n")).records;
+    let simulator = Simulator::replay(records, 1.0).unwrap();
+
+    let sessions: Vec<u64> = [0.0, 9.9, 10.0, 19.0, 20.0, 20.9, 21.0, 31.5]
+        .into_iter()
+        .map(|seconds| session_at(&simulator, seconds))
+        .collect();
+    assert_eq!(sessions, [1, 1, 2, 2, 3, 3, 1, 2]);
+
+    // The first record of an endpoint is served until its second one
+    let Some(Payload::Version(version)) = simulator.payload("version", secs(0.0)) else {
+        panic!("no version");
+    };
+    assert_eq!(version.serial_number, "TWC123456789");
+    // Endpoints missing in the log come from the scenario
+    assert!(matches!(
+        simulator.payload("lifetime", secs(3.0)),
+        Some(Payload::Lifetime(_))
+    ));
+    assert!(simulator.payload("status", secs(0.0)).is_none());
+
+    assert!(Simulator::replay(Vec::new(), 1.0).is_err());
+}
+
+#[test]
+fn served_payloads_read_like_the_wall_connector() {
+    let vitals = common::vitals_with(&[("current_alerts", json!(["PCS_a052", {"id": 7}]))]);
+    let json = payload_json(&Payload::Vitals(vitals.clone())).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["current_alerts"], json!(["PCS_a052", {"id": 7}]));
+    assert_eq!(value["evse_state"], 11);
+    assert!(value.get("currentB_a").is_some());
+
+    let simulator = Simulator::replay(vec![record(0, Payload::Vitals(vitals))], 1.0).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || simulator.serve(listener));
+    let client = WallConnectorClient::new("127.0.0.1")
+        .unwrap()
+        .with_port(port)
+        .with_timeout(Duration::from_secs(2));
+
+    let served = client.vitals().unwrap();
+    assert_eq!(served.session_s, 39);
+    assert_eq!(served.current_alerts.len(), 2);
+    assert_eq!(served.current_alerts[0].code.as_deref(), Some("PCS_a052"));
+    let version: Version = client.version().unwrap();
+    assert_eq!(version.serial_number, "SIM000000001");
+    assert_eq!(client.wifi_status().unwrap().wifi_infra_ip, "127.0.0.1");
+}
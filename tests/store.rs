@@ -0,0 +1,186 @@
+//! The SQLite history store fed with the logs in `data/`.
+
+mod common;
+
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use tesla_wallcon_monitor::logfile::{LogFile, Payload};
+use tesla_wallcon_monitor::session::detect_sessions;
+use tesla_wallcon_monitor::store::{ImportStats, Store};
+
+fn count(store: &Store, table: &str) -> i64 {
+    store.with_connection(|conn| {
+        conn.query_row(&format!("SELECT count(*) FROM {}", table), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    })
+}
+
+#[test]
+fn stores_samples_and_sessions() {
+    let log = LogFile::read(Path::new("data/logs5tt-d-2.txt")).unwrap();
+    let store = Store::open_in_memory().unwrap();
+    assert_eq!(store.schema_version().unwrap(), 2);
+    for record in &log.records {
+        store
+            .insert("home", record.timestamp, &record.payload)
+            .unwrap();
+    }
+
+    assert_eq!(count(&store, "vitals"), log.vitals().count() as i64);
+    let expected = detect_sessions(log.vitals());
+    let sessions: Vec<(String, String, f64)> = store.with_connection(|conn| {
+        let mut query = conn
+            .prepare("SELECT plugged_in, end_reason, energy_wh FROM sessions ORDER BY plugged_in")
+            .unwrap();
+        query
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    });
+    assert_eq!(sessions.len(), expected.len());
+    for ((plugged_in, end, energy_wh), session) in sessions.iter().zip(&expected) {
+        assert_eq!(
+            plugged_in,
+            &tesla_wallcon_monitor::store::format_timestamp(session.plugged_in)
+        );
+        assert_eq!(
+            end,
+            serde_json::to_value(session.end).unwrap().as_str().unwrap()
+        );
+        assert!((energy_wh - session.energy_wh).abs() < 1e-9);
+    }
+}
+
+fn temp_db(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("wallcon-{}-{}.db", name, std::process::id()));
+    remove_db(&path);
+    path
+}
+
+fn remove_db(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
+
+#[test]
+fn reopening_keeps_data() {
+    let path = temp_db("store");
+    let log = LogFile::read(Path::new("data/logs5tt-d-2.txt")).unwrap();
+    {
+        let store = Store::open(&path).unwrap();
+        let record = &log.records[0];
+        store
+            .insert("home", record.timestamp, &record.payload)
+            .unwrap();
+    }
+    let store = Store::open(&path).unwrap();
+    assert_eq!(store.schema_version().unwrap(), 2);
+    assert_eq!(count(&store, log.records[0].payload.endpoint()), 1);
+    drop(store);
+    remove_db(&path);
+}
+
+#[test]
+fn reopening_continues_the_session_in_progress() {
+    let path = temp_db("resume");
+    let start = time::macros::datetime!(2025-12-23 02:54:00 UTC);
+    let insert = |store: &Store, seconds: i64, connected: bool, energy_wh: f64| {
+        let vitals = common::vitals_with(&[
+            ("vehicle_connected", json!(connected)),
+            ("contactor_closed", json!(connected)),
+            (
+                "vehicle_current_a",
+                json!(if connected { 32.0 } else { 0.0 }),
+            ),
+            ("session_s", json!(seconds)),
+            ("session_energy_wh", json!(energy_wh)),
+        ]);
+        let timestamp = start + time::Duration::seconds(seconds);
+        store
+            .insert("home", timestamp, &Payload::Vitals(vitals))
+            .unwrap();
+    };
+    {
+        let store = Store::open(&path).unwrap();
+        insert(&store, 0, true, 0.0);
+        insert(&store, 10, true, 20.0);
+        insert(&store, 20, true, 40.0);
+    }
+    let store = Store::open(&path).unwrap();
+    insert(&store, 30, true, 60.0);
+    insert(&store, 40, false, 60.0);
+
+    let sessions: Vec<(String, String, i64, i64, f64)> = store.with_connection(|conn| {
+        let mut query = conn
+            .prepare("SELECT plugged_in, end_reason, samples, charging_s, energy_wh FROM sessions")
+            .unwrap();
+        query
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    });
+    assert_eq!(
+        sessions,
+        [(
+            "2025-12-23T02:54:00.000Z".to_string(),
+            "unplugged".to_string(),
+            5,
+            40,
+            60.0
+        )]
+    );
+    drop(store);
+    remove_db(&path);
+}
+
+#[test]
+fn import_skips_duplicates() {
+    let log = LogFile::read(Path::new("data/logs5tt-d-2.txt")).unwrap();
+    let store = Store::open_in_memory().unwrap();
+    let first = store.import("home", "a.txt", &log.records).unwrap();
+    assert_eq!(first.imported, log.records.len());
+    assert_eq!(first.duplicates, 0);
+
+    // An overlapping file only adds the samples that are new
+    let (head, tail) = log.records.split_at(log.records.len() / 2);
+    let store = Store::open_in_memory().unwrap();
+    store.import("home", "a.txt", head).unwrap();
+    let second = store.import("home", "b.txt", &log.records).unwrap();
+    assert_eq!(
+        second,
+        ImportStats {
+            imported: tail.len(),
+            duplicates: head.len(),
+            vitals: tail
+                .iter()
+                .filter(|r| r.payload.endpoint() == "vitals")
+                .count(),
+        }
+    );
+    let from_b: i64 = store.with_connection(|conn| {
+        conn.query_row(
+            "SELECT count(*) FROM vitals WHERE source = 'b.txt'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+    });
+    assert_eq!(from_b, second.vitals as i64);
+    assert_eq!(
+        count(&store, "sessions"),
+        detect_sessions(log.vitals()).len() as i64
+    );
+}
@@ -0,0 +1,54 @@
+//! Highlighting what changed between two renderings of an endpoint.
+
+use crossterm::style::Stylize;
+use tesla_wallcon_monitor::term::highlight_changes;
+
+fn changed(text: &str) -> String {
+    text.bold().yellow().to_string()
+}
+
+#[test]
+fn changed_values_are_highlighted() {
+    let previous = "Vitals:\n  Grid Voltage:  241.0 V\n  Session:       5m";
+    let current = "Vitals:\n  Grid Voltage:  242.5 V\n  Session:       5m";
+    assert_eq!(
+        highlight_changes(previous, current),
+        format!(
+            "Vitals:\n  Grid Voltage:  {}\n  Session:       5m",
+            changed("242.5 V")
+        )
+    );
+    assert_eq!(highlight_changes(current, current), current);
+    // Lines without a label are highlighted whole
+    assert_eq!(
+        highlight_changes("  idle", "  charging"),
+        format!("  {}", changed("charging"))
+    );
+}
+
+#[test]
+fn lines_are_matched_by_label_when_the_count_changes() {
+    let previous = "State:   Charging\n  Current: 32.0 A\n  Session: 5m";
+    let current = "State:   Charging\n  Alert:   PCS_a052\n  Current: 32.0 A\n  Session: 6m";
+    assert_eq!(
+        highlight_changes(previous, current),
+        format!(
+            "State:   Charging\n  Alert:   {}\n  Current: 32.0 A\n  Session: {}",
+            changed("PCS_a052"),
+            changed("6m")
+        )
+    );
+    // And when one goes away
+    assert_eq!(
+        highlight_changes(current, previous),
+        "State:   Charging\n  Current: 32.0 A\n  Session: 5m".replace("5m", &changed("5m"))
+    );
+
+    // Repeated labels are compared in order
+    let previous = "  Alert:   PCS_a049\n  Alert:   PCS_a052";
+    let current = "  Alert:   PCS_a049\n  Alert:   PCS_a052\n  Alert:   PCS_a053";
+    assert_eq!(
+        highlight_changes(previous, current),
+        format!("{}\n  Alert:   {}", previous, changed("PCS_a053"))
+    );
+}
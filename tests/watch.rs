@@ -0,0 +1,222 @@
+//! Rules over scripted vitals and webhook delivery to a local stand-in.
+
+mod common;
+
+use common::vitals_with;
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::time::Duration;
+use tesla_wallcon_monitor::rules::{NotificationState, Rule, RuleEngine, parse_duration};
+use tesla_wallcon_monitor::webhook::Webhook;
+use time::OffsetDateTime;
+use time::macros::datetime;
+
+const START: OffsetDateTime = datetime!(2025-12-23 02:54:00 UTC);
+
+fn engine(rules: &[&str]) -> RuleEngine {
+    let rules = rules.iter().map(|rule| rule.parse().unwrap()).collect();
+    RuleEngine::new("garage", rules)
+}
+
+/// Feed `field` = `value` at `seconds` after [`START`], returning the states
+/// of the notifications.
+fn feed(
+    engine: &mut RuleEngine,
+    seconds: i64,
+    field: &str,
+    value: Value,
+) -> Vec<(NotificationState, Value)> {
+    let vitals = vitals_with(&[(field, value)]);
+    engine
+        .update(START + time::Duration::seconds(seconds), &vitals)
+        .into_iter()
+        .map(|n| (n.state, n.value))
+        .collect()
+}
+
+#[test]
+fn threshold_fires_after_hold_and_resolves_past_hysteresis() {
+    use NotificationState::{Firing, Resolved};
+    let mut engine = engine(&["handle_temp_c > 60 for 2m hysteresis 5"]);
+    let mut temp = |seconds, value: f64| feed(&mut engine, seconds, "handle_temp_c", json!(value));
+
+    // A dip below the threshold restarts the hold
+    assert!(temp(0, 61.0).is_empty());
+    assert!(temp(60, 59.0).is_empty());
+    assert!(temp(90, 62.0).is_empty());
+    assert!(temp(180, 62.0).is_empty());
+    assert_eq!(temp(210, 63.0), [(Firing, json!(63.0))]);
+    assert!(temp(240, 64.0).is_empty());
+    // Within the hysteresis it keeps firing
+    assert!(temp(270, 56.0).is_empty());
+    assert_eq!(temp(300, 55.0), [(Resolved, json!(55.0))]);
+    assert!(temp(330, 50.0).is_empty());
+}
+
+#[test]
+fn threshold_without_hold_fires_and_resolves_at_once() {
+    let mut engine = engine(&["evse_state == 9", "vehicle_connected != true"]);
+    let fired = feed(&mut engine, 0, "evse_state", json!(9));
+    assert_eq!(fired, [(NotificationState::Firing, json!(9))]);
+    let fired = feed(&mut engine, 5, "vehicle_connected", json!(false));
+    assert_eq!(
+        fired,
+        [
+            (NotificationState::Resolved, json!(11)),
+            (NotificationState::Firing, json!(false)),
+        ]
+    );
+}
+
+#[test]
+fn new_alerts_are_reported_once_per_cooldown() {
+    let mut engine = engine(&["current_alerts new cooldown 10m"]);
+    let mut alerts = |seconds, value: Value| {
+        feed(&mut engine, seconds, "current_alerts", value)
+            .into_iter()
+            .map(|(_, alert)| alert["code"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Alerts present at the start aren't new
+    assert!(alerts(0, json!(["PCS_a052"])).is_empty());
+    assert_eq!(alerts(10, json!(["PCS_a052", "PCS_a049"])), ["PCS_a049"]);
+    assert!(alerts(20, json!(["PCS_a052", "PCS_a049"])).is_empty());
+    // Cleared and raised again within the cooldown, reported once when it
+    // expired
+    assert!(alerts(30, json!([])).is_empty());
+    assert_eq!(alerts(40, json!(["PCS_a049", "PCS_a052"])), ["PCS_a052"]);
+    assert!(alerts(50, json!([])).is_empty());
+    assert!(alerts(60, json!(["PCS_a049"])).is_empty());
+    assert!(alerts(600, json!(["PCS_a049"])).is_empty());
+    assert_eq!(alerts(610, json!([])), ["PCS_a049"]);
+    assert!(alerts(620, json!([])).is_empty());
+    // Only raised again after that cooldown
+    assert!(alerts(1200, json!([])).is_empty());
+    assert_eq!(alerts(1210, json!(["PCS_a049"])), ["PCS_a049"]);
+}
+
+#[test]
+fn changes_within_the_cooldown_are_coalesced() {
+    let mut engine = engine(&["evse_state changed cooldown 1m"]);
+    let mut state = |seconds, value: u32| {
+        engine
+            .update(
+                START + time::Duration::seconds(seconds),
+                &vitals_with(&[("evse_state", json!(value))]),
+            )
+            .into_iter()
+            .map(|n| (n.value, n.previous))
+            .collect::<Vec<_>>()
+    };
+
+    assert!(state(0, 11).is_empty());
+    assert_eq!(state(10, 9), [(json!(9), Some(json!(11)))]);
+    assert!(state(20, 11).is_empty());
+    assert!(state(30, 1).is_empty());
+    assert_eq!(state(70, 1), [(json!(1), Some(json!(9)))]);
+    assert!(state(80, 1).is_empty());
+}
+
+#[test]
+fn rules_and_durations_are_validated() {
+    assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+    assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+    assert_eq!(parse_duration("1.5h").unwrap(), Duration::from_secs(5400));
+    assert!(parse_duration("5d").is_err());
+    assert!(parse_duration("1e400").is_err());
+    assert!(parse_duration(&"9".repeat(400)).is_err());
+    assert!(
+        "handle_temp_c > 60 for 99999999999999999999999h"
+            .parse::<Rule>()
+            .is_err()
+    );
+    assert!("handle_temp_c >".parse::<Rule>().is_err());
+    assert!("handle_temp_c > 60 cooldown 1m".parse::<Rule>().is_err());
+    assert!("current_alerts appeared".parse::<Rule>().is_err());
+
+    let unknown = engine(&["no_such_field > 1"]);
+    assert!(unknown.validate(&vitals_with(&[])).is_err());
+    let not_a_list = engine(&["grid_v new"]);
+    assert!(not_a_list.validate(&vitals_with(&[])).is_err());
+}
+
+/// Answer each POST with `status`, sending the JSON bodies to the channel.
+fn stand_in(status: u16) -> (String, mpsc::Receiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(&stream);
+            let mut length = 0;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    length = value.trim().parse().unwrap();
+                }
+                line.clear();
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            tx.send(serde_json::from_slice(&body).unwrap()).unwrap();
+            write!(
+                &stream,
+                "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .unwrap();
+        }
+    });
+    (url, rx)
+}
+
+#[test]
+fn webhook_posts_each_notification() {
+    let (url, received) = stand_in(204);
+    let webhook = Webhook::new(&url).unwrap();
+    let mut engine = engine(&["handle_temp_c > 60", "evse_state changed cooldown 0"]);
+    engine.update(START, &vitals_with(&[]));
+    let vitals = vitals_with(&[("handle_temp_c", json!(61.5)), ("evse_state", json!(9))]);
+    let notifications = engine.update(START + time::Duration::seconds(5), &vitals);
+    assert_eq!(notifications.len(), 2);
+    for notification in &notifications {
+        webhook.send(notification).unwrap();
+    }
+
+    let timeout = Duration::from_secs(5);
+    let first = received.recv_timeout(timeout).unwrap();
+    assert_eq!(
+        first,
+        json!({
+            "device": "garage",
+            "rule": "handle_temp_c > 60",
+            "state": "firing",
+            "field": "handle_temp_c",
+            "value": 61.5,
+            "timestamp": "2025-12-23T02:54:05Z",
+        })
+    );
+    let second = received.recv_timeout(timeout).unwrap();
+    assert_eq!(second["state"], "changed");
+    assert_eq!(second["value"], 9);
+    assert_eq!(second["previous"], 11);
+}
+
+#[test]
+fn webhook_errors_on_non_2xx() {
+    let (url, received) = stand_in(500);
+    let webhook = Webhook::new(&url).unwrap();
+    let mut engine = engine(&["handle_temp_c > 10"]);
+    let notifications = engine.update(START, &vitals_with(&[]));
+    assert!(webhook.send(&notifications[0]).is_err());
+    assert_eq!(
+        received.recv_timeout(Duration::from_secs(5)).unwrap()["rule"],
+        "handle_temp_c > 10"
+    );
+    assert!(Webhook::new("ftp://example.com/").is_err());
+}
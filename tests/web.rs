@@ -0,0 +1,63 @@
+//! The web dashboard's API against the fake connector.
+
+mod common;
+
+use common::FakeConnector;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use tesla_wallcon_monitor::web::WebServer;
+
+#[test]
+fn viewers_share_one_poller() {
+    let fake = FakeConnector::start();
+    let server = WebServer::new(
+        fake.client(),
+        Duration::from_secs(60),
+        Duration::from_secs(600),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || server.serve(listener));
+
+    // The event stream starts with the status of the first poll
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    write!(stream, "GET /events HTTP/1.1\r\nHost: test\r\n\r\n").unwrap();
+    let data = BufReader::new(stream)
+        .lines()
+        .map(Result::unwrap)
+        .filter_map(|line| line.strip_prefix("data: ").map(str::to_string))
+        .find(|data| !data.contains("\"updated\":null"))
+        .unwrap();
+    let status: serde_json::Value = serde_json::from_str(&data).unwrap();
+    assert_eq!(status["vitals"]["grid_v"], 251.5);
+    assert_eq!(status["version"]["serial_number"], "TWC123456789");
+
+    let client = reqwest::blocking::Client::new();
+    for _ in 0..5 {
+        let vitals: serde_json::Value = client
+            .get(format!("http://{}/api/vitals", addr))
+            .send()
+            .unwrap()
+            .json()
+            .unwrap();
+        assert_eq!(vitals["evse_state"], 11);
+    }
+    let session: serde_json::Value = client
+        .get(format!("http://{}/api/session", addr))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(session["end"], "in_progress");
+
+    let polls = fake
+        .requests()
+        .iter()
+        .filter(|path| *path == "/api/1/vitals")
+        .count();
+    assert_eq!(polls, 1);
+}